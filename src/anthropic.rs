@@ -0,0 +1,595 @@
+//! Anthropic Messages API client implementation
+
+use crate::api::{assemble_events, Message, Role, ToolCall, ToolChoice};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::ledger::Ledger;
+use crate::provider::{ChatRequest, Completion, EventStream, Provider, StreamEvent};
+use crate::retry::{RateLimitInfo, RateLimitTracker, RetryPolicy};
+use crate::sse::{self, SseEvent};
+use crate::usage::{self, Usage};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument};
+
+/// Protocol version sent in the `anthropic-version` header
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// A message in the Anthropic wire format (system prompts are sent separately)
+#[derive(Debug, Serialize)]
+struct AnthropicMessage {
+    role: &'static str,
+    content: AnthropicContent,
+}
+
+/// Message content: plain text, or content blocks when tools are involved
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum AnthropicContent {
+    Text(String),
+    Blocks(Vec<RequestBlock>),
+}
+
+/// A content block in a request message
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RequestBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSource,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+}
+
+/// Inline image data for an image block
+#[derive(Debug, Serialize)]
+struct ImageSource {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    media_type: String,
+    data: String,
+}
+
+/// A tool definition in the Messages API format
+#[derive(Debug, Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
+}
+
+/// Anthropic Messages API request
+#[derive(Debug, Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+}
+
+/// A content block in a Messages API response
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Anthropic Messages API response
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: Option<AnthropicUsage>,
+}
+
+/// Token usage in the Messages API vocabulary
+#[derive(Debug, Default, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<AnthropicUsage> for Usage {
+    fn from(usage: AnthropicUsage) -> Self {
+        Self::new(usage.input_tokens, usage.output_tokens)
+    }
+}
+
+/// Message metadata sent at the start of a stream
+#[derive(Debug, Deserialize)]
+struct StartedMessage {
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+/// Server-sent event payload in a streaming Messages API response
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessagesEvent {
+    MessageStart {
+        message: StartedMessage,
+    },
+    ContentBlockStart {
+        #[serde(default)]
+        index: usize,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        #[serde(default)]
+        index: usize,
+        delta: BlockDelta,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+        #[serde(default)]
+        usage: Option<AnthropicUsage>,
+    },
+    Error {
+        error: ErrorDetail,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Delta for a content block in a streaming response
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Top-level message delta carrying the stop reason
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    stop_reason: Option<String>,
+}
+
+/// Anthropic API error response
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorDetail {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+impl ErrorDetail {
+    fn into_app_error(self) -> AppError {
+        match self.error_type.as_str() {
+            "rate_limit_error" => AppError::RateLimitExceeded,
+            _ => AppError::ApiError {
+                message: self.message,
+            },
+        }
+    }
+}
+
+/// Anthropic Messages API client
+pub struct AnthropicClient {
+    client: Client,
+    config: Config,
+    retry: RetryPolicy,
+    rate_limits: RateLimitTracker,
+    ledger: Ledger,
+}
+
+impl AnthropicClient {
+    /// Create a new Anthropic client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be built.
+    pub fn new(config: Config) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+
+        Ok(Self {
+            client,
+            retry: RetryPolicy::from_config(&config.retry),
+            rate_limits: RateLimitTracker::default(),
+            ledger: Ledger::from_config(&config),
+            config,
+        })
+    }
+
+    /// Build a full URL for an API path
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.provider_base_url(), path)
+    }
+
+    /// Content blocks of a user message with images
+    ///
+    /// Images go before the text, as the Messages API docs recommend.
+    fn user_blocks(message: Message) -> Vec<RequestBlock> {
+        let mut blocks: Vec<RequestBlock> = message
+            .images
+            .into_iter()
+            .map(|image| RequestBlock::Image {
+                source: ImageSource {
+                    kind: "base64",
+                    media_type: image.mime_type,
+                    data: image.data,
+                },
+            })
+            .collect();
+        if !message.content.is_empty() {
+            blocks.push(RequestBlock::Text {
+                text: message.content,
+            });
+        }
+        blocks
+    }
+
+    /// Content blocks of an assistant message with tool calls
+    fn assistant_blocks(message: Message) -> Vec<RequestBlock> {
+        let mut blocks = Vec::new();
+        if !message.content.is_empty() {
+            blocks.push(RequestBlock::Text {
+                text: message.content,
+            });
+        }
+        blocks.extend(message.tool_calls.into_iter().map(|call| {
+            RequestBlock::ToolUse {
+                input: call
+                    .function
+                    .parse_arguments()
+                    .unwrap_or_else(|_| serde_json::json!({})),
+                id: call.id,
+                name: call.function.name,
+            }
+        }));
+        blocks
+    }
+
+    /// Convert a chat request into the Messages API request shape
+    ///
+    /// The Messages API has no seed, penalties or logit bias, so those are not sent.
+    fn build_request(&self, request: ChatRequest, stream: bool) -> MessagesRequest {
+        let sampling = self.config.sampling.overridden_by(&request.sampling);
+        let mut system = Vec::new();
+        let mut turns: Vec<AnthropicMessage> = Vec::new();
+
+        for message in request.messages {
+            match message.role {
+                Role::System | Role::Developer => system.push(message.content),
+                Role::User if message.images.is_empty() => turns.push(AnthropicMessage {
+                    role: "user",
+                    content: AnthropicContent::Text(message.content),
+                }),
+                Role::User => turns.push(AnthropicMessage {
+                    role: "user",
+                    content: AnthropicContent::Blocks(Self::user_blocks(message)),
+                }),
+                Role::Assistant if message.tool_calls.is_empty() => turns.push(AnthropicMessage {
+                    role: "assistant",
+                    content: AnthropicContent::Text(message.content),
+                }),
+                Role::Assistant => turns.push(AnthropicMessage {
+                    role: "assistant",
+                    content: AnthropicContent::Blocks(Self::assistant_blocks(message)),
+                }),
+                Role::Tool => {
+                    let block = RequestBlock::ToolResult {
+                        tool_use_id: message.tool_call_id.unwrap_or_default(),
+                        content: message.content,
+                    };
+
+                    // Results for parallel tool calls share a single user turn
+                    match turns.last_mut() {
+                        Some(AnthropicMessage {
+                            role: "user",
+                            content: AnthropicContent::Blocks(blocks),
+                        }) => blocks.push(block),
+                        _ => turns.push(AnthropicMessage {
+                            role: "user",
+                            content: AnthropicContent::Blocks(vec![block]),
+                        }),
+                    }
+                }
+            }
+        }
+
+        // The Messages API has no response_format, so ask for the JSON in the prompt
+        if let Some(format) = &request.response_format {
+            system.push(format.instruction());
+        }
+
+        MessagesRequest {
+            model: self.config.model.clone(),
+            max_tokens: sampling
+                .max_completion_tokens
+                .unwrap_or(self.config.max_tokens),
+            system: if system.is_empty() {
+                None
+            } else {
+                Some(system.join("\n\n"))
+            },
+            messages: turns,
+            temperature: sampling.temperature,
+            top_p: sampling.top_p,
+            stop_sequences: sampling.stop,
+            stream,
+            tools: request
+                .tools
+                .into_iter()
+                .map(|tool| AnthropicTool {
+                    name: tool.function.name,
+                    description: tool.function.description,
+                    input_schema: tool.function.parameters,
+                })
+                .collect(),
+            tool_choice: request.tool_choice.map(|choice| match choice {
+                ToolChoice::Auto => serde_json::json!({"type": "auto"}),
+                ToolChoice::None => serde_json::json!({"type": "none"}),
+                ToolChoice::Required => serde_json::json!({"type": "any"}),
+                ToolChoice::Function(name) => serde_json::json!({"type": "tool", "name": name}),
+            }),
+        }
+    }
+
+    /// Send a request, retrying transient failures, and map non-success responses to errors
+    async fn send(&self, request: &MessagesRequest) -> Result<reqwest::Response> {
+        let api_key = self.config.api_key()?;
+        let url = self.url("/v1/messages");
+
+        self.retry
+            .send(
+                &self.rate_limits,
+                || {
+                    self.client
+                        .post(&url)
+                        .header("x-api-key", api_key)
+                        .header("anthropic-version", ANTHROPIC_VERSION)
+                        .header("Content-Type", "application/json")
+                        .json(request)
+                },
+                |status, error_text| {
+                    if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(error_text) {
+                        return error_response.error.into_app_error();
+                    }
+
+                    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                        return AppError::RateLimitExceeded;
+                    }
+
+                    AppError::ApiError {
+                        message: format!("API request failed with status {status}: {error_text}"),
+                    }
+                },
+            )
+            .await
+    }
+
+    /// Send a chat request and return the full reply
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the reply cannot be parsed.
+    #[instrument(skip(self, request))]
+    pub async fn complete_chat(&self, request: ChatRequest) -> Result<Completion> {
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let request = self.build_request(request, false);
+
+        debug!("Sending Anthropic messages request");
+
+        let started = Instant::now();
+        let response: MessagesResponse = self.send(&request).await?.json().await?;
+
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text } => content.push_str(&text),
+                ContentBlock::ToolUse { id, name, input } => {
+                    tool_calls.push(ToolCall::function(id, name, input.to_string()));
+                }
+                ContentBlock::Other => {}
+            }
+        }
+
+        let message = Message::assistant_with_tool_calls(content, tool_calls);
+        let usage = response
+            .usage
+            .map_or_else(|| Usage::estimate(prompt_tokens, &message), Usage::from);
+        self.ledger
+            .record("chat", &self.config.model, &usage, started.elapsed());
+
+        Ok(Completion {
+            message,
+            finish_reason: response.stop_reason.as_deref().map(finish_reason),
+            usage,
+        })
+    }
+
+    /// Send a streaming chat request
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails before the stream starts.
+    #[instrument(skip(self, request))]
+    pub async fn stream_chat(&self, request: ChatRequest) -> Result<EventStream> {
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let request = self.build_request(request, true);
+
+        debug!("Sending streaming Anthropic messages request");
+
+        let started = Instant::now();
+        let response = self.send(&request).await?;
+
+        // Input tokens arrive with message_start, output tokens with message_delta
+        let mut input_tokens = 0;
+
+        let events = assemble_events(
+            sse::events(response.bytes_stream()),
+            move |event: SseEvent, tool_calls| {
+                let events = match serde_json::from_str::<MessagesEvent>(&event.data)? {
+                    MessagesEvent::MessageStart { message } => {
+                        input_tokens = message.usage.input_tokens;
+                        Vec::new()
+                    }
+                    MessagesEvent::ContentBlockStart {
+                        index,
+                        content_block: ContentBlock::ToolUse { id, name, .. },
+                    } => {
+                        tool_calls.start(index, Some(id), Some(name));
+                        Vec::new()
+                    }
+                    MessagesEvent::ContentBlockDelta { index, delta } => match delta {
+                        BlockDelta::TextDelta { text } => vec![StreamEvent::Text(text)],
+                        BlockDelta::InputJsonDelta { partial_json } => {
+                            tool_calls.append_arguments(index, &partial_json);
+                            Vec::new()
+                        }
+                        BlockDelta::Other => Vec::new(),
+                    },
+                    MessagesEvent::MessageDelta { delta, usage } => {
+                        let mut events: Vec<_> = tool_calls
+                            .finish()
+                            .map(StreamEvent::ToolCalls)
+                            .into_iter()
+                            .collect();
+                        events.extend(
+                            delta
+                                .stop_reason
+                                .as_deref()
+                                .map(|reason| StreamEvent::Finish(finish_reason(reason))),
+                        );
+                        events.extend(usage.map(|usage| {
+                            StreamEvent::Usage(Usage::new(input_tokens, usage.output_tokens))
+                        }));
+                        events
+                    }
+                    MessagesEvent::Error { error } => return Err(error.into_app_error()),
+                    MessagesEvent::ContentBlockStart { .. } | MessagesEvent::Other => Vec::new(),
+                };
+
+                Ok(events)
+            },
+        );
+
+        let events = usage::with_estimated_usage(events, prompt_tokens);
+
+        Ok(self
+            .ledger
+            .track(events, "chat", self.config.model.clone(), started))
+    }
+
+    /// List available models from the API
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the reply cannot be parsed.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct ModelsResponse {
+            data: Vec<ModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct ModelInfo {
+            id: String,
+        }
+
+        let response = self
+            .client
+            .get(self.url("/v1/models"))
+            .header("x-api-key", self.config.api_key()?)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .send()
+            .await?;
+
+        let status = response.status();
+
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(AppError::ApiError {
+                message: format!("Failed to fetch models: {error_text}"),
+            });
+        }
+
+        let models_response: ModelsResponse = response.json().await?;
+
+        Ok(models_response.data.into_iter().map(|m| m.id).collect())
+    }
+}
+
+/// Map an Anthropic stop reason to the OpenAI finish reason vocabulary
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        "end_turn" | "stop_sequence" => "stop",
+        other => other,
+    }
+    .to_string()
+}
+
+#[async_trait]
+impl Provider for AnthropicClient {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    async fn complete_chat(&self, request: ChatRequest) -> Result<Completion> {
+        Self::complete_chat(self, request).await
+    }
+
+    async fn stream_chat(&self, request: ChatRequest) -> Result<EventStream> {
+        Self::stream_chat(self, request).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Self::list_models(self).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limits.latest()
+    }
+}
@@ -1,64 +1,422 @@
 //! OpenAI API client implementation
 
-use crate::config::Config;
+use crate::config::{Config, ProviderKind};
 use crate::error::{AppError, Result};
+use crate::ledger::Ledger;
+use crate::logprobs::{ChoiceLogprobs, TokenLogprob};
+use crate::provider::{
+    self, Alternative, Alternatives, ChatRequest, Completion, EventStream, Provider, StreamEvent,
+};
+use crate::retry::{RateLimitInfo, RateLimitTracker, RetryPolicy};
+use crate::sampling::SamplingParams;
+use crate::sse::{self, SseEvent};
+use crate::usage::{self, Usage};
+use async_trait::async_trait;
 use futures_util::{Stream, StreamExt};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use std::pin::Pin;
-use std::time::Duration;
-use tracing::{debug, instrument};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument, warn};
 
 /// Role in a conversation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     System,
     User,
     Assistant,
+    /// Result of a tool call, sent back to the model
+    Tool,
+    /// Instructions for a reasoning model; system messages are sent with
+    /// this role to models that take it
+    Developer,
 }
 
 /// A message in the conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
+    /// Text content; `null` in API responses that only carry tool calls
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub content: String,
+    /// Images attached to a user message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageAttachment>,
+    /// Tool calls requested by the assistant
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// ID of the tool call a `Role::Tool` message responds to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Tokens the message accounts for, as reported or estimated; never sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<u32>,
+    /// Kept when the history is compacted; never sent
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Still cut off by the token limit after continuing; never sent
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Reasoning the model produced before the reply; never sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
 }
 
 impl Message {
-    /// Create a new system message
-    pub fn system(content: impl Into<String>) -> Self {
+    /// Create a new message with the given role and text
+    fn new(role: Role, content: impl Into<String>) -> Self {
         Self {
-            role: Role::System,
+            role,
             content: content.into(),
+            images: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            tokens: None,
+            pinned: false,
+            truncated: false,
+            reasoning_content: None,
         }
     }
 
+    /// Record a local estimate of the tokens the message accounts for
+    pub fn with_estimated_tokens(mut self) -> Self {
+        self.tokens = Some(usage::estimate_message_tokens(&self));
+        self
+    }
+
+    /// Create a new system message
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
     /// Create a new user message
     pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    /// Create a new user message with attached images
+    pub fn user_with_images(content: impl Into<String>, images: Vec<ImageAttachment>) -> Self {
         Self {
-            role: Role::User,
-            content: content.into(),
+            images,
+            ..Self::new(Role::User, content)
         }
     }
 
     /// Create a new assistant message
     pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// Create an assistant message that requests tool calls
+    pub fn assistant_with_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
         Self {
-            role: Role::Assistant,
-            content: content.into(),
+            tool_calls,
+            ..Self::new(Role::Assistant, content)
+        }
+    }
+
+    /// Create a message carrying the result of a tool call
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+}
+
+/// An image attached to a message, kept inline so saved sessions can be resumed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageAttachment {
+    /// File the image was read from
+    pub path: String,
+    /// MIME type sniffed from the image bytes
+    pub mime_type: String,
+    /// Base64-encoded image bytes
+    pub data: String,
+}
+
+impl ImageAttachment {
+    /// The image as a `data:` URL
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// A part of a multi-part message content array
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: ImageUrl },
+}
+
+/// Image reference inside a content part
+#[derive(Debug, Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+/// Serialize messages for the chat-completions API
+///
+/// Messages with images are sent with a content-part array (text first, then
+/// one `image_url` part per image); all others keep plain string content.
+fn serialize_messages<S: serde::Serializer>(
+    messages: &[Message],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Content<'a> {
+        Text(&'a str),
+        Parts(Vec<ContentPart<'a>>),
+    }
+
+    #[derive(Serialize)]
+    struct WireMessage<'a> {
+        role: &'a Role,
+        content: Content<'a>,
+        #[serde(skip_serializing_if = "<[ToolCall]>::is_empty")]
+        tool_calls: &'a [ToolCall],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_call_id: Option<&'a str>,
+    }
+
+    serializer.collect_seq(messages.iter().map(|message| {
+        let content = if message.images.is_empty() {
+            Content::Text(&message.content)
+        } else {
+            let mut parts = Vec::with_capacity(message.images.len() + 1);
+            if !message.content.is_empty() {
+                parts.push(ContentPart::Text {
+                    text: &message.content,
+                });
+            }
+            parts.extend(message.images.iter().map(|image| ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: image.data_url(),
+                },
+            }));
+            Content::Parts(parts)
+        };
+
+        WireMessage {
+            role: &message.role,
+            content,
+            tool_calls: &message.tool_calls,
+            tool_call_id: message.tool_call_id.as_deref(),
+        }
+    }))
+}
+
+/// Deserialize a string that may be `null`
+fn null_as_empty<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A tool call requested by the assistant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+impl ToolCall {
+    /// Create a function tool call
+    pub fn function(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        arguments: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            kind: default_tool_type(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments: arguments.into(),
+            },
+        }
+    }
+}
+
+/// Function name and JSON-encoded arguments of a tool call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// Arguments as a JSON string, exactly as produced by the model
+    #[serde(default)]
+    pub arguments: String,
+}
+
+impl FunctionCall {
+    /// Parse the arguments, treating an empty string as an empty object
+    pub fn parse_arguments(&self) -> Result<serde_json::Value> {
+        if self.arguments.trim().is_empty() {
+            return Ok(serde_json::Value::Object(serde_json::Map::new()));
+        }
+
+        Ok(serde_json::from_str(&self.arguments)?)
+    }
+}
+
+/// A tool the model may call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+impl ToolDefinition {
+    /// Create a function tool definition from a name, description and JSON schema
+    pub fn function(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: serde_json::Value,
+    ) -> Self {
+        Self {
+            kind: default_tool_type(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
         }
     }
 }
 
+/// Name, description and parameter schema of a function tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments object
+    pub parameters: serde_json::Value,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+/// Whether and which tool the model should call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// The model decides whether to call tools
+    Auto,
+    /// The model must not call tools
+    None,
+    /// The model must call at least one tool
+    Required,
+    /// The model must call the named function
+    Function(String),
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::None => serializer.serialize_str("none"),
+            Self::Required => serializer.serialize_str("required"),
+            Self::Function(name) => serde_json::json!({
+                "type": "function",
+                "function": {"name": name}
+            })
+            .serialize(serializer),
+        }
+    }
+}
+
+impl From<&str> for ToolChoice {
+    /// `auto`, `none` or `required`, or else the name of a function
+    fn from(value: &str) -> Self {
+        match value {
+            "auto" => Self::Auto,
+            "none" => Self::None,
+            "required" => Self::Required,
+            name => Self::Function(name.to_string()),
+        }
+    }
+}
+
+/// Constrain the reply to JSON matching a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseFormat {
+    /// Name of the schema, sent to backends that require one
+    pub name: String,
+    /// The JSON schema itself
+    pub schema: serde_json::Value,
+}
+
+impl ResponseFormat {
+    /// Create a JSON schema response format
+    pub fn json_schema(name: impl Into<String>, schema: serde_json::Value) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+        }
+    }
+
+    /// Prompt text asking for the same output, for backends without native support
+    pub fn instruction(&self) -> String {
+        format!(
+            "Respond with only a JSON value that conforms to the following JSON Schema. \
+             Do not wrap it in code fences or add any other text.\n\n{}",
+            serde_json::to_string_pretty(&self.schema).unwrap_or_default()
+        )
+    }
+}
+
+impl Serialize for ResponseFormat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {"name": self.name, "schema": self.schema}
+        })
+        .serialize(serializer)
+    }
+}
+
 /// OpenAI API request
 #[derive(Debug, Serialize)]
 struct CompletionRequest {
     model: String,
+    #[serde(serialize_with = "serialize_messages")]
     messages: Vec<Message>,
-    max_tokens: u32,
-    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(flatten)]
+    sampling: SamplingParams,
+    /// Number of choices, when more than one is wanted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    /// Ask for the log probability of each reply token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    /// Alternatives returned per token along with its logprob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+}
+
+/// Options for a streaming request
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    /// Ask for a final chunk carrying the token usage
+    include_usage: bool,
 }
 
 /// OpenAI API response choice
@@ -66,8 +424,21 @@ struct CompletionRequest {
 struct Choice {
     message: Message,
     finish_reason: Option<String>,
-    #[allow(dead_code)]
+    /// Position of the choice when several were asked for
+    #[serde(default)]
     index: usize,
+    /// Token logprobs, when they were asked for
+    #[serde(default)]
+    logprobs: Option<ChoiceLogprobs>,
+}
+
+/// Request fields only some calls set
+#[derive(Debug, Default)]
+struct RequestExtras {
+    /// Number of choices, when more than one is wanted
+    n: Option<u32>,
+    /// Alternatives per token, when logprobs are wanted
+    top_logprobs: Option<u8>,
 }
 
 /// OpenAI API response
@@ -82,31 +453,33 @@ struct CompletionResponse {
     #[allow(dead_code)]
     model: String,
     choices: Vec<Choice>,
-    #[allow(dead_code)]
     usage: Option<Usage>,
 }
 
-/// Token usage information
-#[derive(Debug, Deserialize)]
-pub struct Usage {
-    pub prompt_tokens: u32,
-    pub completion_tokens: u32,
-    pub total_tokens: u32,
-}
-
 /// Streaming response chunk from OpenAI API
+///
+/// Metadata fields are defaulted because some compatible servers omit them.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StreamChunk {
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub object: String,
+    #[serde(default)]
     pub created: u64,
+    #[serde(default)]
     pub model: String,
+    #[serde(default)]
     pub choices: Vec<StreamChoice>,
+    /// Token usage, sent in a final chunk without choices when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
 
 /// Choice in a streaming response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StreamChoice {
+    #[serde(default)]
     pub index: usize,
     pub delta: Delta,
     pub finish_reason: Option<String>,
@@ -117,6 +490,115 @@ pub struct StreamChoice {
 pub struct Delta {
     pub role: Option<String>,
     pub content: Option<String>,
+    /// Reasoning streamed before the reply (LM Studio, DeepSeek)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+/// Fragment of a tool call in a streaming response
+///
+/// The first fragment for an `index` carries the ID and function name; later
+/// ones append pieces of the JSON arguments.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<FunctionCallDelta>,
+}
+
+/// Function fragment of a streamed tool call
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+/// Assembles complete tool calls from streamed fragments
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    calls: BTreeMap<usize, ToolCall>,
+}
+
+impl ToolCallAccumulator {
+    /// Start (or update) the tool call at `index`
+    pub(crate) fn start(&mut self, index: usize, id: Option<String>, name: Option<String>) {
+        let call = self
+            .calls
+            .entry(index)
+            .or_insert_with(|| ToolCall::function("", "", ""));
+
+        if let Some(id) = id {
+            call.id = id;
+        }
+        if let Some(name) = name {
+            call.function.name.push_str(&name);
+        }
+    }
+
+    /// Append a fragment of JSON arguments to the tool call at `index`
+    pub(crate) fn append_arguments(&mut self, index: usize, fragment: &str) {
+        self.start(index, None, None);
+        if let Some(call) = self.calls.get_mut(&index) {
+            call.function.arguments.push_str(fragment);
+        }
+    }
+
+    /// Take the completed tool calls, if any were streamed
+    pub(crate) fn finish(&mut self) -> Option<Vec<ToolCall>> {
+        if self.calls.is_empty() {
+            return None;
+        }
+
+        Some(std::mem::take(&mut self.calls).into_values().collect())
+    }
+}
+
+/// Turn a stream of decoded response items into chat events
+///
+/// `handle` maps each item to events and may stash tool call fragments in the
+/// accumulator; calls still pending when the stream ends are emitted last.
+pub(crate) fn assemble_events<S, T, F>(items: S, handle: F) -> EventStream
+where
+    S: Stream<Item = Result<T>> + Send + 'static,
+    T: Send + 'static,
+    F: FnMut(T, &mut ToolCallAccumulator) -> Result<Vec<StreamEvent>> + Send + 'static,
+{
+    let state = (
+        Box::pin(items),
+        handle,
+        ToolCallAccumulator::default(),
+        VecDeque::new(),
+        false,
+    );
+
+    Box::pin(futures_util::stream::unfold(
+        state,
+        |(mut items, mut handle, mut tool_calls, mut pending, mut finished)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((event, (items, handle, tool_calls, pending, finished)));
+                }
+
+                if finished {
+                    return None;
+                }
+
+                match items.next().await {
+                    Some(item) => match item.and_then(|item| handle(item, &mut tool_calls)) {
+                        Ok(events) => pending.extend(events.into_iter().map(Ok)),
+                        Err(e) => pending.push_back(Err(e)),
+                    },
+                    None => {
+                        // Some servers end the stream without a finish reason
+                        finished = true;
+                        pending.extend(tool_calls.finish().map(|calls| Ok(StreamEvent::ToolCalls(calls))));
+                    }
+                }
+            }
+        },
+    ))
 }
 
 /// OpenAI API error response
@@ -134,10 +616,85 @@ struct ErrorDetail {
     code: Option<String>,
 }
 
+impl ErrorDetail {
+    fn into_app_error(self) -> AppError {
+        match self.code.as_deref() {
+            Some("rate_limit_exceeded") => AppError::RateLimitExceeded,
+            _ => AppError::ApiError {
+                message: self.message,
+            },
+        }
+    }
+}
+
+/// Parse one SSE event of a streaming completion
+///
+/// In-stream `error` objects and malformed JSON are reported as errors
+/// rather than skipped, so a broken stream is never mistaken for a short answer.
+fn parse_stream_event(event: &SseEvent) -> Result<StreamChunk> {
+    let value: serde_json::Value = serde_json::from_str(&event.data)?;
+
+    if event.event == "error" || value.get("error").is_some() {
+        return Err(match serde_json::from_value::<ErrorResponse>(value) {
+            Ok(error_response) => error_response.error.into_app_error(),
+            Err(_) => AppError::ApiError {
+                message: format!("Stream error: {}", event.data),
+            },
+        });
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Turn a streaming chunk into events, collecting tool call fragments
+///
+/// Tool calls are emitted as a whole once the choice finishes.
+fn chunk_events(chunk: StreamChunk, tool_calls: &mut ToolCallAccumulator) -> Vec<StreamEvent> {
+    let mut events = Vec::new();
+
+    for choice in chunk.choices {
+        if let Some(reasoning) = choice.delta.reasoning_content.filter(|r| !r.is_empty()) {
+            events.push(StreamEvent::Reasoning(reasoning));
+        }
+
+        if let Some(delta_content) = choice.delta.content.filter(|c| !c.is_empty()) {
+            // Debug: Log individual deltas
+            if std::env::var("DEBUG_STREAMING").is_ok() {
+                eprintln!("[API] Delta content: {delta_content:?}");
+            }
+            events.push(StreamEvent::Text(delta_content));
+        }
+
+        for delta in choice.delta.tool_calls {
+            let (name, arguments) = delta
+                .function
+                .map_or((None, None), |f| (f.name, f.arguments));
+            tool_calls.start(delta.index, delta.id, name);
+            if let Some(arguments) = arguments {
+                tool_calls.append_arguments(delta.index, &arguments);
+            }
+        }
+
+        if let Some(reason) = choice.finish_reason {
+            events.extend(tool_calls.finish().map(StreamEvent::ToolCalls));
+            events.push(StreamEvent::Finish(reason));
+        }
+    }
+
+    events.extend(chunk.usage.map(|usage| StreamEvent::Usage(usage.normalized())));
+
+    events
+}
+
 /// OpenAI API client
 pub struct OpenAIClient {
     client: Client,
     config: Config,
+    retry: RetryPolicy,
+    rate_limits: RateLimitTracker,
+    ledger: Ledger,
+    /// Warns once about parameters dropped for a reasoning model
+    reasoning_warning: Once,
 }
 
 impl OpenAIClient {
@@ -147,183 +704,343 @@ impl OpenAIClient {
             .timeout(Duration::from_secs(config.timeout_seconds))
             .build()?;
 
-        Ok(Self { client, config })
+        if config.provider == ProviderKind::Azure
+            && config.azure.resource.is_none()
+            && config.provider_base_url() == ProviderKind::OpenAI.default_base_url()
+        {
+            return Err(AppError::ConfigError(
+                "Azure OpenAI requires azure.resource or a base_url".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            client,
+            retry: RetryPolicy::from_config(&config.retry),
+            rate_limits: RateLimitTracker::default(),
+            ledger: Ledger::from_config(&config),
+            reasoning_warning: Once::new(),
+            config,
+        })
     }
 
-    /// Send a completion request
-    #[instrument(skip(self, messages))]
-    pub async fn complete(&self, messages: Vec<Message>) -> Result<String> {
-        let request = CompletionRequest {
-            model: self.config.model.clone(),
-            messages,
-            max_tokens: self.config.max_tokens,
-            temperature: 0.7,
-            stream: false,
-        };
+    /// Usage ledger that requests are recorded in
+    pub(crate) fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
 
-        debug!("Sending completion request");
+    /// Authentication header for the configured service, if it needs one
+    ///
+    /// Azure OpenAI expects an `api-key` header instead of a bearer token.
+    /// Ollama's OpenAI-compatible routes take `ollama.api_key`, when set.
+    fn auth_header(&self) -> Result<Option<(&'static str, String)>> {
+        if self.config.provider == ProviderKind::Ollama {
+            return Ok(self
+                .config
+                .ollama
+                .api_key
+                .as_ref()
+                .map(|key| ("Authorization", format!("Bearer {key}"))));
+        }
 
-        let response = self
-            .client
-            .post(&self.config.api_url())
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.api_key()?),
-            )
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        let api_key = self.config.api_key()?;
 
-        let status = response.status();
+        if self.config.provider == ProviderKind::Azure {
+            Ok(Some(("api-key", api_key.to_string())))
+        } else {
+            Ok(Some(("Authorization", format!("Bearer {api_key}"))))
+        }
+    }
 
-        if !status.is_success() {
-            let error_text = response.text().await?;
+    /// Whether streamed requests ask for usage with `stream_options`
+    fn streams_usage(&self) -> bool {
+        self.config.usage.stream_usage.unwrap_or_else(|| {
+            self.config.provider == ProviderKind::Azure
+                || self.config.provider_base_url() == ProviderKind::OpenAI.default_base_url()
+        })
+    }
+
+    /// Attach the authentication header, if any, to a request
+    fn authorize(
+        builder: RequestBuilder,
+        auth: Option<&(&'static str, String)>,
+    ) -> RequestBuilder {
+        match auth {
+            Some((name, value)) => builder.header(*name, value),
+            None => builder,
+        }
+    }
+
+    /// Send a request to the completions endpoint, retrying transient failures
+    async fn send(&self, request: &CompletionRequest) -> Result<reqwest::Response> {
+        self.post(&self.config.api_url(), |builder| {
+            builder
+                .header("Content-Type", "application/json")
+                .json(request)
+        })
+        .await
+    }
+
+    /// URL of an endpoint other than chat completions, e.g. `audio/transcriptions`
+    ///
+    /// On Azure the endpoint lives under the deployment for `model`.
+    pub(crate) fn endpoint_url(&self, endpoint: &str, model: &str) -> String {
+        if self.config.provider == ProviderKind::Azure {
+            let deployment = self
+                .config
+                .azure
+                .deployments
+                .get(model)
+                .map_or(model, String::as_str);
+            return format!(
+                "{}/openai/deployments/{}/{}?api-version={}",
+                self.config.provider_base_url(),
+                deployment,
+                endpoint,
+                self.config.azure.api_version
+            );
+        }
+
+        format!("{}/v1/{}", self.config.provider_base_url(), endpoint)
+    }
+
+    /// The underlying HTTP client, for requests that must not carry the API key
+    pub(crate) fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// POST to an API URL with authentication, retrying transient failures
+    ///
+    /// `body` attaches the payload and is called again for every attempt.
+    pub(crate) async fn post(
+        &self,
+        url: &str,
+        body: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let auth = self.auth_header()?;
+
+        self.retry
+            .send(
+                &self.rate_limits,
+                || body(Self::authorize(self.client.post(url), auth.as_ref())),
+                |status, error_text| {
+                    // Try to parse as error response
+                    if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(error_text) {
+                        return error_response.error.into_app_error();
+                    }
+
+                    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                        return AppError::RateLimitExceeded;
+                    }
 
-            // Try to parse as error response
-            if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&error_text) {
-                return match error_response.error.code.as_deref() {
-                    Some("rate_limit_exceeded") => Err(AppError::RateLimitExceeded),
-                    _ => Err(AppError::ApiError {
-                        message: error_response.error.message,
-                    }),
-                };
+                    AppError::ApiError {
+                        message: format!("API request failed with status {}: {}", status, error_text),
+                    }
+                },
+            )
+            .await
+    }
+
+    /// Build a completions request for the configured model
+    ///
+    /// `max_tokens` is left out when `max_completion_tokens` is set, since
+    /// newer models reject requests that carry both. Reasoning models always
+    /// get `max_completion_tokens`, and their system messages are sent with
+    /// the `developer` role.
+    fn build_request(&self, request: ChatRequest, stream: bool) -> CompletionRequest {
+        let mut sampling = self.config.sampling.overridden_by(&request.sampling);
+        let mut messages = request.messages;
+
+        if self.config.reasoning.applies_to(&self.config.model) {
+            sampling.max_completion_tokens.get_or_insert(self.config.max_tokens);
+            let dropped = sampling.clear_for_reasoning();
+            if !dropped.is_empty() {
+                self.reasoning_warning.call_once(|| {
+                    warn!(
+                        "{} is a reasoning model and does not accept {}; leaving them out",
+                        self.config.model,
+                        dropped.join(", ")
+                    );
+                });
             }
+            for message in messages.iter_mut().filter(|m| m.role == Role::System) {
+                message.role = Role::Developer;
+            }
+        }
 
-            return Err(AppError::ApiError {
-                message: format!("API request failed with status {}: {}", status, error_text),
-            });
+        CompletionRequest {
+            model: self.config.model.clone(),
+            messages,
+            max_tokens: match sampling.max_completion_tokens {
+                Some(_) => None,
+                None => Some(self.config.max_tokens),
+            },
+            sampling,
+            n: None,
+            logprobs: None,
+            top_logprobs: None,
+            stream,
+            stream_options: (stream && self.streams_usage())
+                .then_some(StreamOptions { include_usage: true }),
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            response_format: request.response_format,
         }
+    }
+
+    /// Send a non-streaming request and record its usage; the choices are
+    /// sorted by index
+    async fn request_choices(&self, request: ChatRequest, extras: RequestExtras) -> Result<(Vec<Choice>, Usage)> {
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let mut request = self.build_request(request, false);
+        request.n = extras.n;
+        request.logprobs = extras.top_logprobs.map(|_| true);
+        request.top_logprobs = extras.top_logprobs;
+
+        debug!("Sending completion request");
+
+        let started = Instant::now();
+
+        let response = self.send(&request).await?;
 
         let response: CompletionResponse = response.json().await?;
 
-        let choice = response
-            .choices
-            .into_iter()
-            .next()
-            .ok_or_else(|| AppError::ApiError {
-                message: "No response choices available".to_string(),
-            })?;
+        let mut choices = response.choices;
+        choices.sort_by_key(|choice| choice.index);
 
-        if let Some(reason) = choice.finish_reason {
-            if reason == "length" {
-                return Err(AppError::TokenLimitExceeded);
+        let usage = match response.usage {
+            Some(usage) => usage.normalized(),
+            None => {
+                let mut usage = Usage::new(prompt_tokens, 0);
+                for choice in &choices {
+                    usage += Usage::estimate(0, &choice.message);
+                }
+                usage.estimated = true;
+                usage
             }
+        };
+        self.ledger.record("chat", &self.config.model, &usage, started.elapsed());
+
+        Ok((choices, usage))
+    }
+
+    /// Send a chat request for `n` alternative replies
+    ///
+    /// Servers that ignore `n` send back a single choice; the rest are then
+    /// asked for with separate requests.
+    pub async fn complete_choices(&self, request: ChatRequest, n: u32) -> Result<Alternatives> {
+        let (choices, usage) = self
+            .request_choices(
+                request.clone(),
+                RequestExtras {
+                    n: Some(n).filter(|&n| n > 1),
+                    ..RequestExtras::default()
+                },
+            )
+            .await?;
+        let mut alternatives = Alternatives {
+            choices: choices
+                .into_iter()
+                .map(|choice| Alternative {
+                    message: choice.message,
+                    finish_reason: choice.finish_reason,
+                })
+                .collect(),
+            usage,
+        };
+
+        let missing = (n as usize).saturating_sub(alternatives.choices.len());
+        if missing > 0 {
+            debug!("Server returned {} of {} choices, sending {} more requests", alternatives.choices.len(), n, missing);
+            alternatives.extend(provider::fan_out(self, &request, missing).await?);
         }
 
-        Ok(choice.message.content)
+        Ok(alternatives)
     }
 
-    /// Create a conversation with a single user message
-    pub async fn chat(&self, user_input: &str) -> Result<String> {
-        let messages = vec![
-            Message::system(&self.config.system_prompt),
-            Message::user(user_input),
-        ];
+    /// Send a chat request and return the full reply
+    #[instrument(skip(self, request))]
+    pub async fn complete_chat(&self, request: ChatRequest) -> Result<Completion> {
+        let (choices, usage) = self.request_choices(request, RequestExtras::default()).await?;
+        let choice = choices.into_iter().next().ok_or_else(|| AppError::ApiError {
+            message: "No response choices available".to_string(),
+        })?;
 
-        self.complete(messages).await
+        Ok(Completion {
+            message: choice.message,
+            finish_reason: choice.finish_reason,
+            usage,
+        })
     }
-    
-    /// Send a streaming completion request
-    #[instrument(skip(self, messages))]
-    pub async fn complete_stream(
+
+    /// Send a chat request asking for the logprob of each reply token and
+    /// up to `top_logprobs` alternatives per token
+    ///
+    /// The list is empty when the server ignores the request.
+    #[instrument(skip(self, request))]
+    pub async fn complete_with_logprobs(
         &self,
-        messages: Vec<Message>,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
-        let request = CompletionRequest {
-            model: self.config.model.clone(),
-            messages,
-            max_tokens: self.config.max_tokens,
-            temperature: 0.7,
-            stream: true,
+        request: ChatRequest,
+        top_logprobs: u8,
+    ) -> Result<(Completion, Vec<TokenLogprob>)> {
+        let extras = RequestExtras {
+            top_logprobs: Some(top_logprobs),
+            ..RequestExtras::default()
         };
+        let (choices, usage) = self.request_choices(request, extras).await?;
+        let choice = choices.into_iter().next().ok_or_else(|| AppError::ApiError {
+            message: "No response choices available".to_string(),
+        })?;
 
-        debug!("Sending streaming completion request");
+        let tokens = choice.logprobs.unwrap_or_default().content;
+        let completion = Completion {
+            message: choice.message,
+            finish_reason: choice.finish_reason,
+            usage,
+        };
+        Ok((completion, tokens))
+    }
 
-        let response = self
-            .client
-            .post(&self.config.api_url())
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.api_key()?),
-            )
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+    /// Send a streaming chat request
+    #[instrument(skip(self, request))]
+    pub async fn stream_chat(&self, request: ChatRequest) -> Result<EventStream> {
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let request = self.build_request(request, true);
 
-        let status = response.status();
+        debug!("Sending streaming completion request");
 
-        if !status.is_success() {
-            let error_text = response.text().await?;
+        let started = Instant::now();
+        let response = self.send(&request).await?;
 
-            // Try to parse as error response
-            if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&error_text) {
-                return match error_response.error.code.as_deref() {
-                    Some("rate_limit_exceeded") => Err(AppError::RateLimitExceeded),
-                    _ => Err(AppError::ApiError {
-                        message: error_response.error.message,
-                    }),
-                };
-            }
+        // Decode SSE events incrementally, stopping at the [DONE] sentinel
+        let events = sse::events(response.bytes_stream()).take_while(|event| {
+            futures_util::future::ready(!matches!(event, Ok(e) if e.data == "[DONE]"))
+        });
 
-            return Err(AppError::ApiError {
-                message: format!("API request failed with status {}: {}", status, error_text),
-            });
-        }
+        let events = assemble_events(events, |event: SseEvent, tool_calls| {
+            Ok(chunk_events(parse_stream_event(&event)?, tool_calls))
+        });
 
-        let stream = response.bytes_stream();
-        
-        // Convert the bytes stream to a stream of parsed chunks
-        let chunk_stream = stream
-            .map(move |chunk| {
-                match chunk {
-                    Ok(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-                        
-                        // Parse SSE format
-                        let mut content = String::new();
-                        for line in text.lines() {
-                            if line.starts_with("data: ") {
-                                let data = line.strip_prefix("data: ").unwrap_or("");
-                                
-                                if data == "[DONE]" {
-                                    continue;
-                                }
-                                
-                                if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
-                                    for choice in chunk.choices {
-                                        if let Some(delta_content) = choice.delta.content {
-                                            // Debug: Log individual deltas
-                                            if std::env::var("DEBUG_STREAMING").is_ok() {
-                                                eprintln!("[API] Delta content: {:?}", delta_content);
-                                            }
-                                            // Accumulate content without adding extra spaces
-                                            content.push_str(&delta_content);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        
-                        Ok(content)
-                    }
-                    Err(e) => Err(AppError::Network(e.to_string())),
-                }
-            });
+        let events = usage::with_estimated_usage(events, prompt_tokens);
 
-        Ok(Box::pin(chunk_stream))
+        Ok(self.ledger.track(events, "chat", self.config.model.clone(), started))
     }
-    
+
     /// List available models from the API
     pub async fn list_models(&self) -> Result<Vec<String>> {
-        let url = format!("{}/v1/models", self.config.base_url.trim_end_matches('/'));
+        let url = if self.config.provider == ProviderKind::Azure {
+            format!(
+                "{}/openai/models?api-version={}",
+                self.config.provider_base_url(),
+                self.config.azure.api_version
+            )
+        } else {
+            format!("{}/v1/models", self.config.base_url.trim_end_matches('/'))
+        };
         
         debug!("Fetching models from {}", url);
         
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key()?))
+        let auth = self.auth_header()?;
+        let response = Self::authorize(self.client.get(&url), auth.as_ref())
             .send()
             .await?;
         
@@ -354,3 +1071,34 @@ impl OpenAIClient {
         Ok(model_ids)
     }
 }
+
+#[async_trait]
+impl Provider for OpenAIClient {
+    fn name(&self) -> &'static str {
+        if self.config.provider == ProviderKind::Azure {
+            "azure"
+        } else {
+            "openai"
+        }
+    }
+
+    async fn complete_chat(&self, request: ChatRequest) -> Result<Completion> {
+        Self::complete_chat(self, request).await
+    }
+
+    async fn stream_chat(&self, request: ChatRequest) -> Result<EventStream> {
+        Self::stream_chat(self, request).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Self::list_models(self).await
+    }
+
+    async fn complete_choices(&self, request: ChatRequest, n: u32) -> Result<Alternatives> {
+        Self::complete_choices(self, request, n).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limits.latest()
+    }
+}
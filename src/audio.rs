@@ -0,0 +1,425 @@
+//! Audio endpoints of the OpenAI API: transcription and text-to-speech
+
+use crate::api::OpenAIClient;
+use crate::cli::{SpeechFormat, TranscriptFormat};
+use crate::error::{AppError, Result};
+use crate::usage::Usage;
+use futures_util::StreamExt;
+use indicatif::{HumanBytes, ProgressBar};
+use reqwest::multipart::{Form, Part};
+use serde::Serialize;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::time::Instant;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tracing::debug;
+
+/// Longest input the speech endpoint accepts, in characters
+pub const MAX_SPEECH_CHARS: usize = 4096;
+
+/// Options for a transcription request
+#[derive(Debug, Clone)]
+pub struct TranscriptionOptions {
+    /// Transcription model, e.g. `whisper-1`
+    pub model: String,
+    /// ISO-639-1 language of the audio; detected by the server when `None`
+    pub language: Option<String>,
+    /// Text to guide the style or vocabulary of the transcript
+    pub prompt: Option<String>,
+    /// Format the server should reply in
+    pub format: TranscriptFormat,
+}
+
+/// Upload an audio file to `audio/transcriptions` and return the transcript
+///
+/// SRT and VTT are returned as sent by the server and JSON is pretty-printed.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or the request fails.
+pub async fn transcribe(
+    client: &OpenAIClient,
+    path: &Path,
+    options: &TranscriptionOptions,
+) -> Result<String> {
+    let bytes = tokio::fs::read(path).await?;
+    let file_name = path.file_name().map_or_else(
+        || "audio".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let url = client.endpoint_url("audio/transcriptions", &options.model);
+
+    debug!("Uploading {} ({} bytes) to {}", file_name, bytes.len(), url);
+
+    // The form is rebuilt for every attempt because a sent body cannot be reused
+    let started = Instant::now();
+    let response = client
+        .post(&url, |builder| {
+            let mut form = Form::new()
+                .text("model", options.model.clone())
+                .text("response_format", options.format.to_string())
+                .part(
+                    "file",
+                    Part::bytes(bytes.clone()).file_name(file_name.clone()),
+                );
+            if let Some(language) = &options.language {
+                form = form.text("language", language.clone());
+            }
+            if let Some(prompt) = &options.prompt {
+                form = form.text("prompt", prompt.clone());
+            }
+            builder.multipart(form)
+        })
+        .await?;
+
+    let body = response.text().await?;
+    client.ledger().record(
+        "audio/transcriptions",
+        &options.model,
+        &Usage::default(),
+        started.elapsed(),
+    );
+
+    match options.format {
+        TranscriptFormat::Json => {
+            let value: serde_json::Value = serde_json::from_str(&body)?;
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        TranscriptFormat::Text => Ok(plain_text(&body)),
+        TranscriptFormat::Srt | TranscriptFormat::Vtt => Ok(body.trim_end().to_string()),
+    }
+}
+
+/// Extract the transcript from a `text` reply
+///
+/// Some local whisper servers ignore `response_format` and always answer with
+/// `{"text": ...}`, so JSON replies are unwrapped.
+fn plain_text(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value.get("text")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+        .trim()
+        .to_string()
+}
+
+/// Options for a speech request
+#[derive(Debug, Clone)]
+pub struct SpeechOptions {
+    /// Speech model, e.g. `tts-1`
+    pub model: String,
+    /// Voice to speak with
+    pub voice: String,
+    /// Audio format to write
+    pub format: SpeechFormat,
+    /// Playback speed (server default when `None`)
+    pub speed: Option<f32>,
+}
+
+/// Body of an `audio/speech` request
+#[derive(Debug, Serialize)]
+struct SpeechRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f32>,
+}
+
+/// Guess the audio format from a file extension
+#[must_use]
+pub fn format_for_path(path: &Path) -> Option<SpeechFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "mp3" => Some(SpeechFormat::Mp3),
+        "opus" | "ogg" => Some(SpeechFormat::Opus),
+        "aac" => Some(SpeechFormat::Aac),
+        "flac" => Some(SpeechFormat::Flac),
+        "wav" => Some(SpeechFormat::Wav),
+        "pcm" | "raw" => Some(SpeechFormat::Pcm),
+        _ => None,
+    }
+}
+
+/// Speak `text` into an audio file, returning the number of bytes written
+///
+/// Text longer than [`MAX_SPEECH_CHARS`] is split at sentence boundaries and
+/// each part is requested in turn; `progress` advances once per part. The
+/// parts are appended to the same file as they download.
+///
+/// # Errors
+///
+/// Returns an error if FLAC output would need several parts, a request fails
+/// or the file cannot be written.
+pub async fn speak(
+    client: &OpenAIClient,
+    text: &str,
+    output: &Path,
+    options: &SpeechOptions,
+    progress: &ProgressBar,
+) -> Result<u64> {
+    let segments = split_sentences(text, MAX_SPEECH_CHARS);
+    if segments.len() > 1 && options.format == SpeechFormat::Flac {
+        return Err(AppError::AudioError(format!(
+            "FLAC audio cannot be joined; shorten the text to {MAX_SPEECH_CHARS} characters or choose another format"
+        )));
+    }
+
+    let url = client.endpoint_url("audio/speech", &options.model);
+    let response_format = options.format.to_string();
+    progress.set_length(segments.len() as u64);
+
+    let mut writer = SegmentWriter::create(output, options.format).await?;
+
+    for segment in &segments {
+        let request = SpeechRequest {
+            model: &options.model,
+            input: segment,
+            voice: &options.voice,
+            response_format: response_format.clone(),
+            speed: options.speed,
+        };
+
+        debug!(
+            "Requesting speech for {} characters",
+            segment.chars().count()
+        );
+
+        let started = Instant::now();
+        let response = client.post(&url, |builder| builder.json(&request)).await?;
+        let mut body = response.bytes_stream();
+
+        writer.start_segment();
+        while let Some(bytes) = body.next().await {
+            writer.write(&bytes?).await?;
+            progress.set_message(HumanBytes(writer.written).to_string());
+        }
+        writer.end_segment()?;
+        client.ledger().record(
+            "audio/speech",
+            &options.model,
+            &Usage::default(),
+            started.elapsed(),
+        );
+
+        progress.inc(1);
+    }
+
+    writer.finish().await
+}
+
+/// Split text into chunks of at most `max_chars`, breaking between sentences
+///
+/// Sentences longer than the limit are broken between words, and words
+/// longer than the limit are cut.
+#[must_use]
+pub fn split_sentences(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+
+    for sentence in sentences(text) {
+        for piece in split_long(sentence, max_chars) {
+            let piece_chars = piece.chars().count();
+            if current_chars > 0 && current_chars + 1 + piece_chars > max_chars {
+                chunks.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            if current_chars > 0 {
+                current.push(' ');
+                current_chars += 1;
+            }
+            current.push_str(piece);
+            current_chars += piece_chars;
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Split text after sentence-ending punctuation and at line breaks
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => Some(i),
+            '.' | '!' | '?' => {
+                // Closing quotes and brackets belong to the sentence
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, next)) = chars.peek() {
+                    if !matches!(next, '"' | '\'' | ')' | ']' | '\u{201D}' | '\u{2019}') {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+                match chars.peek() {
+                    Some(&(_, next)) if !next.is_whitespace() => None,
+                    _ => Some(end),
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(end) = end {
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+    }
+
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+
+    sentences
+}
+
+/// Break a sentence longer than `max_chars` between words
+fn split_long(sentence: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = sentence;
+
+    while rest.chars().count() > max_chars {
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(i, _)| i);
+        let cut = rest[..limit]
+            .rfind(char::is_whitespace)
+            .filter(|&i| i > 0)
+            .unwrap_or(limit);
+        pieces.push(rest[..cut].trim_end());
+        rest = rest[cut..].trim_start();
+    }
+
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+
+    pieces
+}
+
+/// Appends downloaded audio segments to one file
+///
+/// Most formats can simply be concatenated. WAV segments each carry a header,
+/// so only the first is kept and its sizes are fixed up at the end.
+struct SegmentWriter {
+    file: tokio::fs::File,
+    format: SpeechFormat,
+    written: u64,
+    /// Start of the current WAV segment, buffered until its header is complete
+    pending_header: Option<Vec<u8>>,
+    /// Position of the data chunk size field in the first WAV header
+    data_size_offset: Option<u64>,
+}
+
+impl SegmentWriter {
+    async fn create(path: &Path, format: SpeechFormat) -> Result<Self> {
+        Ok(Self {
+            file: tokio::fs::File::create(path).await?,
+            format,
+            written: 0,
+            pending_header: None,
+            data_size_offset: None,
+        })
+    }
+
+    fn start_segment(&mut self) {
+        if self.format == SpeechFormat::Wav {
+            self.pending_header = Some(Vec::new());
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        let Some(header) = &mut self.pending_header else {
+            return self.write_through(bytes).await;
+        };
+
+        header.extend_from_slice(bytes);
+        let Some(data_start) = wav_data_offset(header)? else {
+            return Ok(());
+        };
+        let buffered = self.pending_header.take().unwrap_or_default();
+
+        if self.data_size_offset.is_none() {
+            self.data_size_offset = Some(self.written + data_start as u64 - 4);
+            self.write_through(&buffered).await
+        } else {
+            self.write_through(&buffered[data_start..]).await
+        }
+    }
+
+    fn end_segment(&mut self) -> Result<()> {
+        if self.pending_header.take().is_some() {
+            return Err(AppError::AudioError(
+                "the server sent an incomplete WAV file".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn write_through(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes).await?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Fix up WAV sizes and flush, returning the file size
+    async fn finish(mut self) -> Result<u64> {
+        if let Some(offset) = self.data_size_offset {
+            let riff_size = u32::try_from(self.written - 8).unwrap_or(u32::MAX);
+            let data_size = u32::try_from(self.written - offset - 4).unwrap_or(u32::MAX);
+
+            self.file.seek(SeekFrom::Start(4)).await?;
+            self.file.write_all(&riff_size.to_le_bytes()).await?;
+            self.file.seek(SeekFrom::Start(offset)).await?;
+            self.file.write_all(&data_size.to_le_bytes()).await?;
+        }
+
+        self.file.flush().await?;
+        Ok(self.written)
+    }
+}
+
+/// Find where the samples start in a WAV header, or `None` if more bytes are needed
+fn wav_data_offset(header: &[u8]) -> Result<Option<usize>> {
+    if header.len() < 12 {
+        return Ok(None);
+    }
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(AppError::AudioError(
+            "the server did not return a WAV file".to_string(),
+        ));
+    }
+
+    let mut position = 12;
+    while header.len() >= position + 8 {
+        let size = u32::from_le_bytes([
+            header[position + 4],
+            header[position + 5],
+            header[position + 6],
+            header[position + 7],
+        ]) as usize;
+
+        if &header[position..position + 4] == b"data" {
+            return Ok(Some(position + 8));
+        }
+
+        // Chunks are padded to an even length
+        position += 8 + size + size % 2;
+    }
+
+    Ok(None)
+}
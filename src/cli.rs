@@ -1,8 +1,16 @@
 //! CLI interface and command handling
 
-use clap::{Parser, Subcommand};
+use crate::config::ProviderKind;
+use crate::reasoning::{ReasoningDisplay, ReasoningEffort};
+use crate::sampling::SamplingParams;
+use crate::tokenizer::Encoding;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
+/// Most alternative replies asked for at once
+pub const MAX_CHOICES: u32 = 10;
+
 /// LLM CLI - A universal command-line interface for Large Language Models
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -27,6 +35,75 @@ pub struct Cli {
     /// Override maximum tokens
     #[arg(short = 't', long, env = "OPENAI_MAX_TOKENS")]
     pub max_tokens: Option<u32>,
+
+    /// Continue replies cut off by the token limit (up to continuation.max_total_tokens)
+    #[arg(long, global = true)]
+    pub auto_continue: bool,
+
+    /// How to show a reasoning model's thinking (overrides reasoning.display)
+    #[arg(long, global = true, value_name = "DISPLAY")]
+    pub thinking: Option<ReasoningDisplay>,
+
+    #[command(flatten)]
+    pub sampling: SamplingArgs,
+}
+
+/// Sampling parameters that override the `[sampling]` config section
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Sampling")]
+pub struct SamplingArgs {
+    /// Randomness from 0 to 2
+    #[arg(long, global = true)]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling probability mass from 0 to 1
+    #[arg(long, global = true)]
+    pub top_p: Option<f64>,
+
+    /// Seed for best-effort deterministic sampling
+    #[arg(long, global = true)]
+    pub seed: Option<i64>,
+
+    /// Sequence that ends generation (repeatable)
+    #[arg(long = "stop", global = true, value_name = "SEQUENCE")]
+    pub stop: Vec<String>,
+
+    /// Penalty from -2 to 2 for tokens that already appeared
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    pub presence_penalty: Option<f64>,
+
+    /// Penalty from -2 to 2 scaled by how often tokens appeared
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    pub frequency_penalty: Option<f64>,
+
+    /// Bias a token ID from -100 to 100, as TOKEN=BIAS (repeatable)
+    #[arg(long, global = true, value_parser = parse_logit_bias, allow_hyphen_values = true)]
+    pub logit_bias: Vec<(String, i32)>,
+
+    /// Upper bound on generated tokens, sent instead of max_tokens
+    #[arg(long, global = true)]
+    pub max_completion_tokens: Option<u32>,
+
+    /// How much a reasoning model thinks before answering
+    #[arg(long, global = true)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl SamplingArgs {
+    /// The parameters given on the command line
+    pub fn into_params(self) -> SamplingParams {
+        SamplingParams {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            seed: self.seed,
+            stop: self.stop,
+            presence_penalty: self.presence_penalty,
+            frequency_penalty: self.frequency_penalty,
+            logit_bias: self.logit_bias.into_iter().collect(),
+            max_completion_tokens: self.max_completion_tokens,
+            reasoning_effort: self.reasoning_effort,
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -43,6 +120,26 @@ pub enum Commands {
         /// Enable streaming responses
         #[arg(short, long)]
         stream: bool,
+
+        /// Workspace root for the built-in file tools (defaults to the current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+
+        /// Do not offer the built-in file tools to the model
+        #[arg(long)]
+        no_tools: bool,
+
+        /// Attach an image to the first message (repeatable)
+        #[arg(short, long = "image", value_name = "PATH")]
+        images: Vec<PathBuf>,
+
+        /// Answer from the files in this directory, citing the excerpts used
+        #[arg(long, value_name = "DIR")]
+        rag: Option<PathBuf>,
+
+        /// Ask for this many alternative replies and pick the one to keep
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=i64::from(MAX_CHOICES)))]
+        choices: u32,
     },
 
     /// Send a single query and get a response
@@ -57,40 +154,319 @@ pub enum Commands {
         /// Enable streaming responses
         #[arg(short, long)]
         stream: bool,
+
+        /// Attach an image to the query (repeatable)
+        #[arg(short, long = "image", value_name = "PATH")]
+        images: Vec<PathBuf>,
+
+        /// Reply with bare JSON validated against this JSON Schema file
+        #[arg(long, value_name = "FILE")]
+        schema: Option<PathBuf>,
+
+        /// How many times to re-ask when the reply does not match the schema
+        #[arg(long, default_value_t = 2, requires = "schema")]
+        max_repairs: usize,
+
+        /// Ask for this many alternative replies and print them all
+        #[arg(long, default_value_t = 1, conflicts_with = "schema", value_parser = clap::value_parser!(u32).range(1..=i64::from(MAX_CHOICES)))]
+        choices: u32,
+
+        /// Show how confident the model was in each token, highlighting guesses
+        #[arg(long, conflicts_with_all = ["schema", "choices"])]
+        logprobs: bool,
+
+        /// Alternatives listed per token with --logprobs
+        #[arg(long, default_value_t = 5, requires = "logprobs", value_parser = clap::value_parser!(u8).range(0..=crate::logprobs::MAX_TOP_LOGPROBS))]
+        top_logprobs: u8,
     },
 
-    /// Configure the CLI
-    Config {
-        /// Show current configuration
+    /// Transcribe an audio file to text
+    Transcribe {
+        /// Audio file to upload (mp3, mp4, mpeg, mpga, m4a, wav or webm)
+        file: PathBuf,
+
+        /// Language of the audio as an ISO-639-1 code, e.g. `en`
+        #[arg(short, long)]
+        language: Option<String>,
+
+        /// Text to guide the transcript's style or the spelling of unusual words
+        #[arg(short, long)]
+        prompt: Option<String>,
+
+        /// Output format (text, json, srt, vtt)
+        #[arg(short, long, default_value = "text")]
+        format: TranscriptFormat,
+
+        /// Transcription model (defaults to audio.transcription_model)
+        #[arg(long)]
+        model: Option<String>,
+    },
+
+    /// Turn text into speech and save it as an audio file
+    Speak {
+        /// Text to speak
+        #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+        text: Option<String>,
+
+        /// Read the text from standard input
+        #[arg(long)]
+        stdin: bool,
+
+        /// Audio file to write
         #[arg(short, long)]
-        show: bool,
+        output: PathBuf,
 
-        /// Set API key
+        /// Voice, e.g. alloy, ash, coral, echo, fable, nova, onyx, sage or shimmer (defaults to audio.voice)
         #[arg(long)]
-        api_key: Option<String>,
+        voice: Option<String>,
+
+        /// Audio format (defaults to the output file extension, then mp3)
+        #[arg(short, long)]
+        format: Option<SpeechFormat>,
+
+        /// Playback speed from 0.25 to 4.0
+        #[arg(long, value_parser = parse_speed)]
+        speed: Option<f32>,
+
+        /// Speech model (defaults to audio.speech_model)
+        #[arg(long)]
+        model: Option<String>,
+    },
+
+    /// Generate images from a prompt
+    Image {
+        /// Description of the image
+        prompt: String,
 
-        /// Set default model
+        /// Image size, e.g. 1024x1024, 1792x1024, 1024x1536 or auto
+        #[arg(long, default_value = "1024x1024")]
+        size: String,
+
+        /// Number of images to generate, from 1 to 10
+        #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=10))]
+        count: u32,
+
+        /// Image quality, e.g. standard or hd (dall-e-3), low, medium or high (gpt-image-1)
+        #[arg(short, long)]
+        quality: Option<String>,
+
+        /// Directory to write the images to
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+
+        /// Image model (defaults to images.model)
         #[arg(long)]
         model: Option<String>,
+    },
+
+    /// Create embeddings for texts, files or lines of standard input
+    Embed {
+        #[command(flatten)]
+        input: EmbedInput,
 
-        /// Set system prompt
+        /// Output format (json, raw little-endian f32)
+        #[arg(short, long, default_value = "json")]
+        format: EmbeddingFormat,
+
+        /// Embedding model (defaults to embeddings.model)
         #[arg(long)]
-        system_prompt: Option<String>,
-        
-        /// Set base URL for API (e.g., https://api.openai.com or custom endpoint)
+        model: Option<String>,
+    },
+
+    /// Manage local vector indexes
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+
+    /// Count tokens locally, without calling the API
+    Tokens {
+        /// Texts to count
+        #[arg(required_unless_present_any = ["files", "stdin"])]
+        texts: Vec<String>,
+
+        /// Count the tokens in a file (repeatable)
+        #[arg(long = "file", value_name = "PATH")]
+        files: Vec<PathBuf>,
+
+        /// Count the tokens in standard input
         #[arg(long)]
-        base_url: Option<String>,
-        
-        /// Set API path (e.g., /v1/chat/completions)
+        stdin: bool,
+
+        /// Encoding (defaults to tokenizer.encoding, then the one the model uses)
         #[arg(long)]
-        api_path: Option<String>,
+        encoding: Option<Encoding>,
+
+        /// Vocabulary file in .tiktoken format (defaults to tokenizer.vocab)
+        #[arg(long, value_name = "FILE")]
+        vocab: Option<PathBuf>,
     },
 
+    /// Configure the CLI
+    Config(ConfigArgs),
+
     /// List available models
     Models,
 
-    /// Show token usage statistics
-    Stats,
+    /// Manage MCP tool servers
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+
+    /// Show token usage and cost from the usage ledger
+    Stats {
+        /// Only include requests from this date (YYYY-MM-DD) or this long ago (e.g. 7d, 12h, 2w)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Subcommands of `mcp`
+#[derive(Subcommand, Debug)]
+pub enum McpCommand {
+    /// Connect to each configured server and list its tools
+    List,
+}
+
+/// Subcommands of `index`
+#[derive(Subcommand, Debug)]
+pub enum IndexCommand {
+    /// Embed inputs and add them to an index
+    Add {
+        #[command(flatten)]
+        input: EmbedInput,
+
+        /// Name of the index
+        #[arg(long, default_value = "default")]
+        index: String,
+    },
+
+    /// Find the entries most similar to a query
+    Search {
+        /// Text to search for
+        query: String,
+
+        /// Number of results
+        #[arg(short, default_value_t = 5)]
+        k: usize,
+
+        /// Name of the index
+        #[arg(long, default_value = "default")]
+        index: String,
+    },
+}
+
+/// Inputs to embed
+#[derive(Args, Debug)]
+pub struct EmbedInput {
+    /// Texts to embed
+    #[arg(required_unless_present_any = ["files", "stdin"])]
+    pub texts: Vec<String>,
+
+    /// Embed the contents of a file (repeatable)
+    #[arg(long = "file", value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+
+    /// Embed each non-empty line of standard input
+    #[arg(long)]
+    pub stdin: bool,
+}
+
+/// Arguments for the `config` subcommand
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Show current configuration
+    #[arg(short, long)]
+    pub show: bool,
+
+    /// Set API key
+    #[arg(long)]
+    pub api_key: Option<String>,
+
+    /// Set default model
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Set system prompt
+    #[arg(long)]
+    pub system_prompt: Option<String>,
+
+    /// Set base URL for API (e.g., https://api.openai.com or custom endpoint)
+    #[arg(long)]
+    pub base_url: Option<String>,
+
+    /// Set API path (e.g., /v1/chat/completions)
+    #[arg(long)]
+    pub api_path: Option<String>,
+
+    /// Set the backend provider
+    #[arg(long)]
+    pub provider: Option<ProviderKind>,
+
+    /// Set the Azure OpenAI resource name
+    #[arg(long)]
+    pub azure_resource: Option<String>,
+
+    /// Set the Azure OpenAI API version
+    #[arg(long)]
+    pub azure_api_version: Option<String>,
+
+    /// Map a model to an Azure deployment (MODEL=DEPLOYMENT), repeatable
+    #[arg(long, value_parser = parse_key_value)]
+    pub azure_deployment: Vec<(String, String)>,
+}
+
+/// Parse a `KEY=VALUE` argument
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{s}'"))
+}
+
+/// Parse a `TOKEN=BIAS` logit bias
+fn parse_logit_bias(s: &str) -> Result<(String, i32), String> {
+    let (token, bias) = parse_key_value(s)?;
+    let bias = bias
+        .parse()
+        .map_err(|_| format!("expected an integer bias, got '{bias}'"))?;
+    Ok((token, bias))
+}
+
+/// Parse a `--since` date, or a duration back from now such as `7d`
+fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+
+    let invalid = || format!("expected YYYY-MM-DD or a duration like 7d, 12h or 2w, got '{s}'");
+    let split = s.len().checked_sub(1).filter(|&i| s.is_char_boundary(i)).ok_or_else(invalid)?;
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let delta = match unit {
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+
+    Utc::now().checked_sub_signed(delta).ok_or_else(invalid)
+}
+
+/// Parse a speech speed between 0.25 and 4.0
+fn parse_speed(s: &str) -> Result<f32, String> {
+    let speed: f32 = s.parse().map_err(|_| format!("expected a number, got '{s}'"))?;
+    if (0.25..=4.0).contains(&speed) {
+        Ok(speed)
+    } else {
+        Err(format!("speed must be between 0.25 and 4.0, got {speed}"))
+    }
 }
 
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
@@ -109,3 +485,56 @@ impl std::fmt::Display for OutputFormat {
         }
     }
 }
+
+/// Output format of the `transcribe` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TranscriptFormat {
+    Text,
+    Json,
+    Srt,
+    Vtt,
+}
+
+impl std::fmt::Display for TranscriptFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+            Self::Srt => write!(f, "srt"),
+            Self::Vtt => write!(f, "vtt"),
+        }
+    }
+}
+
+/// Audio format written by the `speak` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SpeechFormat {
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+    Pcm,
+}
+
+impl std::fmt::Display for SpeechFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mp3 => write!(f, "mp3"),
+            Self::Opus => write!(f, "opus"),
+            Self::Aac => write!(f, "aac"),
+            Self::Flac => write!(f, "flac"),
+            Self::Wav => write!(f, "wav"),
+            Self::Pcm => write!(f, "pcm"),
+        }
+    }
+}
+
+/// Output format of the `embed` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EmbeddingFormat {
+    /// One JSON array of vectors
+    Json,
+    /// Vectors as consecutive little-endian f32 values
+    Raw,
+}
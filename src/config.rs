@@ -1,13 +1,62 @@
 //! Configuration management for the OpenAI CLI
 
+use crate::context::ContextStrategy;
 use crate::error::{AppError, Result};
+use crate::reasoning::{self, ReasoningDisplay};
+use crate::sampling::SamplingParams;
+use crate::tokenizer::Encoding;
 use dirs::config_dir;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+/// Backend protocol used to talk to the model server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI chat-completions API (also LM Studio and other compatible servers)
+    #[default]
+    #[value(name = "openai")]
+    OpenAI,
+    /// Anthropic Messages API
+    Anthropic,
+    /// Native Ollama API (`/api/chat`, `/api/tags`)
+    Ollama,
+    /// Azure OpenAI deployments
+    Azure,
+}
+
+impl ProviderKind {
+    /// Default base URL for the provider's endpoint
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            Self::OpenAI => "https://api.openai.com",
+            Self::Anthropic => "https://api.anthropic.com",
+            Self::Ollama => "http://localhost:11434",
+            // Azure endpoints are per-resource, see `Config::provider_base_url`
+            Self::Azure => "https://api.openai.com",
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OpenAI => write!(f, "openai"),
+            Self::Anthropic => write!(f, "anthropic"),
+            Self::Ollama => write!(f, "ollama"),
+            Self::Azure => write!(f, "azure"),
+        }
+    }
+}
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Backend provider to use
+    #[serde(default)]
+    pub provider: ProviderKind,
+
     /// OpenAI API key
     pub api_key: Option<String>,
 
@@ -19,6 +68,10 @@ pub struct Config {
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
 
+    /// Context window of the model in tokens (guessed from the model name when unset)
+    #[serde(default)]
+    pub context_window: Option<u32>,
+
     /// Base URL for the API (e.g., "https://api.openai.com" or custom endpoint)
     #[serde(default = "default_base_url")]
     pub base_url: String,
@@ -35,22 +88,470 @@ pub struct Config {
     #[serde(default = "default_timeout")]
     pub timeout_seconds: u64,
 
+    /// Largest image file that can be attached to a message, in bytes
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: u64,
+
     /// Enable debug logging
     #[serde(default)]
     pub debug: bool,
+
+    /// Sampling parameters sent with chat requests (server defaults when unset)
+    #[serde(default)]
+    pub sampling: SamplingParams,
+
+    /// Settings for the native Ollama backend
+    #[serde(default)]
+    pub ollama: OllamaConfig,
+
+    /// Settings for Azure OpenAI
+    #[serde(default)]
+    pub azure: AzureConfig,
+
+    /// Retry and backoff behaviour for failed requests
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Built-in file-system tools offered in chat
+    #[serde(default)]
+    pub tools: ToolsConfig,
+
+    /// MCP servers whose tools are offered in chat, keyed by server name
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+
+    /// Models used by the audio subcommands
+    #[serde(default)]
+    pub audio: AudioConfig,
+
+    /// Settings for the `image` subcommand
+    #[serde(default)]
+    pub images: ImagesConfig,
+
+    /// Settings for the `embed` and `index` subcommands
+    #[serde(default)]
+    pub embeddings: EmbeddingsConfig,
+
+    /// Settings for `chat --rag`
+    #[serde(default)]
+    pub rag: RagConfig,
+
+    /// Usage ledger and prices for `stats`
+    #[serde(default)]
+    pub usage: UsageConfig,
+
+    /// Local tokenizer for `tokens` and context window checks
+    #[serde(default)]
+    pub tokenizer: TokenizerConfig,
+
+    /// How chat keeps long conversations within the context window
+    #[serde(default)]
+    pub context: ContextConfig,
+
+    /// Continuing replies cut off by the token limit
+    #[serde(default)]
+    pub continuation: ContinuationConfig,
+
+    /// Reasoning models and how their reasoning is shown
+    #[serde(default)]
+    pub reasoning: ReasoningConfig,
+}
+
+/// Settings for the `embed` and `index` subcommands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingsConfig {
+    /// Embedding model used for new indexes and `embed`
+    #[serde(default = "default_embedding_model")]
+    pub model: String,
+
+    /// Number of inputs sent per request
+    #[serde(default = "default_embedding_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            model: default_embedding_model(),
+            batch_size: default_embedding_batch_size(),
+        }
+    }
+}
+
+/// Settings for `chat --rag`; embeddings use the `embeddings` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagConfig {
+    /// Largest chunk, in characters
+    #[serde(default = "default_rag_chunk_chars")]
+    pub chunk_chars: usize,
+
+    /// Characters of lines shared by consecutive chunks
+    #[serde(default = "default_rag_overlap_chars")]
+    pub overlap_chars: usize,
+
+    /// Number of chunks added to each question
+    #[serde(default = "default_rag_top_k")]
+    pub top_k: usize,
+
+    /// Files larger than this are not indexed
+    #[serde(default = "default_rag_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            chunk_chars: default_rag_chunk_chars(),
+            overlap_chars: default_rag_overlap_chars(),
+            top_k: default_rag_top_k(),
+            max_file_bytes: default_rag_max_file_bytes(),
+        }
+    }
+}
+
+/// Settings for the usage ledger read by `stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageConfig {
+    /// Append every request to the usage ledger
+    #[serde(default = "default_ledger")]
+    pub ledger: bool,
+
+    /// Ledger file (defaults to `usage.jsonl` in the data directory)
+    #[serde(default)]
+    pub ledger_path: Option<PathBuf>,
+
+    /// Prices per model name or name prefix; replaces the built-in table when set
+    #[serde(default = "default_prices")]
+    pub prices: BTreeMap<String, ModelPrice>,
+
+    /// Ask for usage in streamed replies with `stream_options`; by default
+    /// only OpenAI and Azure are asked, since some compatible servers reject it
+    #[serde(default)]
+    pub stream_usage: Option<bool>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            ledger: default_ledger(),
+            ledger_path: None,
+            prices: default_prices(),
+            stream_usage: None,
+        }
+    }
+}
+
+impl UsageConfig {
+    /// Price of a model, matching the longest configured name prefix
+    ///
+    /// Dated snapshots such as `gpt-4o-2024-08-06` use the price of `gpt-4o`.
+    pub fn price(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    }
+}
+
+/// Price of a model in US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Price of a million prompt tokens
+    #[serde(default)]
+    pub prompt: f64,
+
+    /// Price of a million completion tokens
+    #[serde(default)]
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    /// Cost in US dollars of a request with the given token counts
+    pub fn cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        (f64::from(prompt_tokens) * self.prompt + f64::from(completion_tokens) * self.completion)
+            / 1_000_000.0
+    }
+}
+
+/// Settings for the local tokenizer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenizerConfig {
+    /// Bundled encoding (chosen from the model name when unset)
+    #[serde(default)]
+    pub encoding: Option<Encoding>,
+
+    /// `.tiktoken` vocabulary file used instead of the bundled ones
+    #[serde(default)]
+    pub vocab: Option<PathBuf>,
+}
+
+/// Settings for keeping chat history within the context window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// What to do with old turns when the history gets too long
+    #[serde(default)]
+    pub strategy: ContextStrategy,
+
+    /// Share of the context window a request may fill before old turns are compacted
+    #[serde(default = "default_context_threshold")]
+    pub threshold: f64,
+
+    /// Most recent turns that are never compacted
+    #[serde(default = "default_context_keep_turns")]
+    pub keep_turns: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ContextStrategy::default(),
+            threshold: default_context_threshold(),
+            keep_turns: default_context_keep_turns(),
+        }
+    }
+}
+
+/// Settings for continuing replies cut off by the token limit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContinuationConfig {
+    /// Ask for the rest of a cut-off reply instead of failing
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Completion tokens a reply may use across all of its continuations
+    #[serde(default = "default_continuation_max_total_tokens")]
+    pub max_total_tokens: u32,
+}
+
+impl Default for ContinuationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_total_tokens: default_continuation_max_total_tokens(),
+        }
+    }
+}
+
+/// Settings for reasoning models
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReasoningConfig {
+    /// How the reasoning streamed before an answer is shown
+    #[serde(default)]
+    pub display: ReasoningDisplay,
+
+    /// Further model names (or prefixes) to send reasoning-model requests
+    /// for, such as Azure deployment names; o1, o3, o4 and GPT-5 models are
+    /// recognized by name
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+impl ReasoningConfig {
+    /// Whether requests for `model` take the reasoning-model form
+    pub fn applies_to(&self, model: &str) -> bool {
+        reasoning::is_reasoning_model(model) || self.models.iter().any(|prefix| model.starts_with(prefix.as_str()))
+    }
+}
+
+/// Settings for the `image` subcommand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagesConfig {
+    /// Image generation model
+    #[serde(default = "default_image_model")]
+    pub model: String,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            model: default_image_model(),
+        }
+    }
+}
+
+/// Models used by the audio subcommands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioConfig {
+    /// Model for `transcribe`
+    #[serde(default = "default_transcription_model")]
+    pub transcription_model: String,
+
+    /// Model for `speak`
+    #[serde(default = "default_speech_model")]
+    pub speech_model: String,
+
+    /// Voice for `speak`
+    #[serde(default = "default_speech_voice")]
+    pub voice: String,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            transcription_model: default_transcription_model(),
+            speech_model: default_speech_model(),
+            voice: default_speech_voice(),
+        }
+    }
+}
+
+/// An MCP server spawned as a child process and spoken to over stdio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Executable to run
+    pub command: String,
+
+    /// Command-line arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Extra environment variables for the server
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Built-in file-system tools offered in chat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Offer the file tools to the model
+    #[serde(default = "default_tools_enabled")]
+    pub enabled: bool,
+
+    /// Directory the tools are restricted to (defaults to the current directory)
+    pub workspace: Option<PathBuf>,
+
+    /// Maximum bytes returned by `read_file`
+    #[serde(default = "default_tools_max_read_bytes")]
+    pub max_read_bytes: usize,
+
+    /// Allow `write_file` without asking (use with care)
+    #[serde(default)]
+    pub auto_approve_writes: bool,
+
+    /// Whether the model must call a tool before answering: `auto`, `none`,
+    /// `required` or the name of a tool (the backend decides when unset)
+    #[serde(default)]
+    pub choice: Option<String>,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_tools_enabled(),
+            workspace: None,
+            max_read_bytes: default_tools_max_read_bytes(),
+            auto_approve_writes: false,
+            choice: None,
+        }
+    }
+}
+
+/// Retry and backoff behaviour for failed requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Total attempts per request, including the first (1 disables retries)
+    #[serde(default = "default_retry_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled on each further attempt
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub base_delay_ms: u64,
+
+    /// Upper bound on any single delay; longer server-requested waits are not retried
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+
+    /// Random jitter applied to backoff delays, as a fraction (0.2 = ±20%)
+    #[serde(default = "default_retry_jitter")]
+    pub jitter: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_max_attempts(),
+            base_delay_ms: default_retry_base_delay_ms(),
+            max_delay_ms: default_retry_max_delay_ms(),
+            jitter: default_retry_jitter(),
+        }
+    }
+}
+
+/// Settings for Azure OpenAI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureConfig {
+    /// Azure resource name, the `{resource}` in `{resource}.openai.azure.com`
+    pub resource: Option<String>,
+
+    /// Deployment names keyed by model name (e.g. `gpt-4o = "prod-gpt4o"`)
+    #[serde(default)]
+    pub deployments: BTreeMap<String, String>,
+
+    /// Value of the `api-version` query parameter
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+}
+
+impl Default for AzureConfig {
+    fn default() -> Self {
+        Self {
+            resource: None,
+            deployments: BTreeMap::new(),
+            api_version: default_azure_api_version(),
+        }
+    }
+}
+
+/// Settings for the native Ollama backend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OllamaConfig {
+    /// Model options passed through verbatim (e.g. `num_ctx`, `temperature`, `top_k`)
+    #[serde(default)]
+    pub options: serde_json::Map<String, serde_json::Value>,
+
+    /// How long the model stays loaded after a request (e.g. "10m", or -1 for forever)
+    pub keep_alive: Option<serde_json::Value>,
+
+    /// Send prompts to `/api/generate` verbatim, without the model's chat template
+    #[serde(default)]
+    pub raw: bool,
+
+    /// Bearer token for Ollama instances behind an auth proxy; no
+    /// `Authorization` header is sent when unset
+    #[serde(default)]
+    pub api_key: Option<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            provider: ProviderKind::default(),
             api_key: None,
             model: default_model(),
             max_tokens: default_max_tokens(),
+            context_window: None,
             base_url: default_base_url(),
             api_path: default_api_path(),
             system_prompt: default_system_prompt(),
             timeout_seconds: default_timeout(),
+            max_image_bytes: default_max_image_bytes(),
             debug: false,
+            sampling: SamplingParams::default(),
+            ollama: OllamaConfig::default(),
+            azure: AzureConfig::default(),
+            retry: RetryConfig::default(),
+            tools: ToolsConfig::default(),
+            mcp_servers: BTreeMap::new(),
+            audio: AudioConfig::default(),
+            images: ImagesConfig::default(),
+            embeddings: EmbeddingsConfig::default(),
+            rag: RagConfig::default(),
+            usage: UsageConfig::default(),
+            tokenizer: TokenizerConfig::default(),
+            context: ContextConfig::default(),
+            continuation: ContinuationConfig::default(),
+            reasoning: ReasoningConfig::default(),
         }
     }
 }
@@ -76,26 +577,42 @@ impl Config {
         max_tokens: u32,
     ) -> Self {
         Self {
+            provider: ProviderKind::default(),
             api_key,
             model,
             max_tokens,
+            context_window: None,
             base_url,
             api_path: "/v1/chat/completions".to_string(),
             system_prompt: "Test prompt".to_string(),
             timeout_seconds: 30,
+            max_image_bytes: default_max_image_bytes(),
             debug: false,
+            sampling: SamplingParams::default(),
+            ollama: OllamaConfig::default(),
+            azure: AzureConfig::default(),
+            retry: RetryConfig::default(),
+            tools: ToolsConfig::default(),
+            mcp_servers: BTreeMap::new(),
+            audio: AudioConfig::default(),
+            images: ImagesConfig::default(),
+            embeddings: EmbeddingsConfig::default(),
+            rag: RagConfig::default(),
+            usage: UsageConfig {
+                ledger: false,
+                ..UsageConfig::default()
+            },
+            tokenizer: TokenizerConfig::default(),
+            context: ContextConfig::default(),
+            continuation: ContinuationConfig::default(),
+            reasoning: ReasoningConfig::default(),
         }
     }
     
     /// Validate config (for testing)
     #[doc(hidden)]
     pub fn validate(&self) -> Result<()> {
-        // Check if using local service
-        let is_local = self.base_url.starts_with("http://localhost") 
-            || self.base_url.starts_with("http://127.0.0.1")
-            || self.base_url.starts_with("http://0.0.0.0");
-        
-        if !is_local && self.api_key.is_none() {
+        if !self.is_local_service() && self.api_key.is_none() {
             return Err(AppError::ApiKeyNotFound);
         }
         
@@ -103,43 +620,67 @@ impl Config {
     }
     
     async fn load_with_file_support(use_file: bool) -> Result<Self> {
-        let mut config = if use_file {
+        let config = if use_file {
             Self::load_from_file().await.unwrap_or_default()
         } else {
             Self::default()
         };
 
-        // Override with environment variables
-        if let Ok(api_key) = std::env::var("OPENAI_API_KEY") {
+        config.with_env(|name| std::env::var(name).ok())
+    }
+
+    /// Apply the environment variable overrides, looked up with `var`
+    ///
+    /// The API key is read only from the selected provider's own variable,
+    /// so a key is never sent to another vendor. Ollama's optional key comes
+    /// from `OLLAMA_API_KEY` into `ollama.api_key`.
+    #[doc(hidden)]
+    pub fn with_env(self, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = self;
+
+        if let Some(provider) = var("LLM_PROVIDER") {
+            config.provider = <ProviderKind as clap::ValueEnum>::from_str(&provider, true)
+                .map_err(|_| AppError::ConfigError(format!("Unknown provider: {provider}")))?;
+        }
+
+        let provider_key_var = match config.provider {
+            ProviderKind::OpenAI => Some("OPENAI_API_KEY"),
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderKind::Azure => Some("AZURE_OPENAI_API_KEY"),
+            ProviderKind::Ollama => None,
+        };
+        if let Some(api_key) = provider_key_var.and_then(&var) {
             config.api_key = Some(api_key);
         }
 
-        if let Ok(model) = std::env::var("OPENAI_MODEL") {
+        if let Some(api_key) = var("OLLAMA_API_KEY") {
+            config.ollama.api_key = Some(api_key);
+        }
+
+        if let Some(model) = var("OPENAI_MODEL") {
             config.model = model;
         }
 
-        if let Ok(max_tokens) = std::env::var("OPENAI_MAX_TOKENS") {
+        if let Some(max_tokens) = var("OPENAI_MAX_TOKENS") {
             config.max_tokens = max_tokens
                 .parse()
                 .map_err(|_| AppError::ConfigError("Invalid max_tokens value".to_string()))?;
         }
+
+        config.sampling.validate()?;
         
-        if let Ok(base_url) = std::env::var("OPENAI_BASE_URL") {
+        if let Some(base_url) = var("OPENAI_BASE_URL") {
             config.base_url = base_url;
         }
         
-        if let Ok(api_path) = std::env::var("OPENAI_API_PATH") {
+        if let Some(api_path) = var("OPENAI_API_PATH") {
             config.api_path = api_path;
         }
 
-        // Only require API key for cloud services
-        if config.api_key.is_none() {
+        // Only require API key for cloud services; Ollama uses its own optional key
+        if config.api_key.is_none() && config.provider != ProviderKind::Ollama {
             // Check if using local service (LM Studio, Ollama, etc.)
-            let is_local = config.base_url.starts_with("http://localhost") 
-                || config.base_url.starts_with("http://127.0.0.1")
-                || config.base_url.starts_with("http://0.0.0.0");
-            
-            if !is_local {
+            if !config.is_local_service() {
                 return Err(AppError::ApiKeyNotFound);
             }
             
@@ -197,8 +738,53 @@ impl Config {
     
     /// Get the full API URL
     pub fn api_url(&self) -> String {
+        if self.provider == ProviderKind::Azure {
+            return format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                self.provider_base_url(),
+                self.azure_deployment(),
+                self.azure.api_version
+            );
+        }
+
         format!("{}{}", self.base_url.trim_end_matches('/'), self.api_path)
     }
+
+    /// Get the Azure deployment name for the current model
+    ///
+    /// Models without an entry in `azure.deployments` are assumed to be
+    /// deployed under their own name.
+    pub fn azure_deployment(&self) -> &str {
+        self.azure
+            .deployments
+            .get(&self.model)
+            .map_or(self.model.as_str(), String::as_str)
+    }
+
+    /// Get the base URL for the selected provider
+    ///
+    /// Falls back to the provider's own endpoint when `base_url` was left at
+    /// the OpenAI default, so `provider = "ollama"` alone is enough.
+    pub fn provider_base_url(&self) -> String {
+        if self.provider == ProviderKind::OpenAI || self.base_url != default_base_url() {
+            return self.base_url.trim_end_matches('/').to_string();
+        }
+
+        match (&self.provider, &self.azure.resource) {
+            (ProviderKind::Azure, Some(resource)) => {
+                format!("https://{resource}.openai.azure.com")
+            }
+            _ => self.provider.default_base_url().to_string(),
+        }
+    }
+
+    /// Whether the provider endpoint is a local service (LM Studio, Ollama, etc.)
+    pub fn is_local_service(&self) -> bool {
+        let base_url = self.provider_base_url();
+        base_url.starts_with("http://localhost")
+            || base_url.starts_with("http://127.0.0.1")
+            || base_url.starts_with("http://0.0.0.0")
+    }
     
 }
 
@@ -211,7 +797,7 @@ fn default_max_tokens() -> u32 {
 }
 
 fn default_base_url() -> String {
-    "https://api.openai.com".to_string()
+    ProviderKind::OpenAI.default_base_url().to_string()
 }
 
 fn default_api_path() -> String {
@@ -225,3 +811,107 @@ fn default_system_prompt() -> String {
 fn default_timeout() -> u64 {
     30
 }
+
+fn default_max_image_bytes() -> u64 {
+    20 * 1024 * 1024
+}
+
+fn default_azure_api_version() -> String {
+    "2024-10-21".to_string()
+}
+
+fn default_retry_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    30_000
+}
+
+fn default_retry_jitter() -> f64 {
+    0.2
+}
+
+fn default_tools_enabled() -> bool {
+    true
+}
+
+fn default_tools_max_read_bytes() -> usize {
+    100_000
+}
+
+fn default_transcription_model() -> String {
+    "whisper-1".to_string()
+}
+
+fn default_speech_model() -> String {
+    "tts-1".to_string()
+}
+
+fn default_speech_voice() -> String {
+    "alloy".to_string()
+}
+
+fn default_image_model() -> String {
+    "dall-e-3".to_string()
+}
+
+fn default_embedding_model() -> String {
+    "text-embedding-3-small".to_string()
+}
+
+fn default_embedding_batch_size() -> usize {
+    64
+}
+
+fn default_rag_chunk_chars() -> usize {
+    1500
+}
+
+fn default_rag_overlap_chars() -> usize {
+    200
+}
+
+fn default_rag_top_k() -> usize {
+    4
+}
+
+fn default_rag_max_file_bytes() -> u64 {
+    1_000_000
+}
+
+fn default_ledger() -> bool {
+    true
+}
+
+fn default_prices() -> BTreeMap<String, ModelPrice> {
+    [
+        ("gpt-4o", 2.50, 10.00),
+        ("gpt-4o-mini", 0.15, 0.60),
+        ("gpt-4.1", 2.00, 8.00),
+        ("gpt-4.1-mini", 0.40, 1.60),
+        ("gpt-4.1-nano", 0.10, 0.40),
+        ("o3-mini", 1.10, 4.40),
+        ("text-embedding-3-small", 0.02, 0.0),
+        ("text-embedding-3-large", 0.13, 0.0),
+    ]
+    .into_iter()
+    .map(|(model, prompt, completion)| (model.to_string(), ModelPrice { prompt, completion }))
+    .collect()
+}
+
+fn default_context_threshold() -> f64 {
+    0.8
+}
+
+fn default_context_keep_turns() -> usize {
+    2
+}
+
+fn default_continuation_max_total_tokens() -> u32 {
+    16_384
+}
@@ -0,0 +1,413 @@
+//! Context window limits, checked locally before a request is sent, and
+//! compaction of old chat turns to stay within them
+
+use crate::api::{Message, Role};
+use crate::config::{Config, ContextConfig};
+use crate::error::{AppError, Result};
+use crate::provider::{ChatRequest, Provider};
+use crate::tokenizer::Tokenizer;
+use crate::usage::Usage;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+use tracing::warn;
+
+/// Context windows in tokens by model name prefix; the longest match wins
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4-0125", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4.5", 128_000),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o1-mini", 128_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("claude-", 200_000),
+];
+
+/// Context window of a well-known model
+#[must_use]
+pub fn context_window(model: &str) -> Option<u32> {
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, window)| window)
+}
+
+/// The context window of the configured model and a tokenizer to measure requests with
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    tokenizer: Tokenizer,
+    model: String,
+    window: u32,
+    reply_tokens: u32,
+}
+
+impl ContextBudget {
+    /// Budget for `config.model`, or `None` when its context window is unknown
+    ///
+    /// `context_window` in the config takes precedence over the built-in table.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configured tokenizer cannot be loaded.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(window) = config
+            .context_window
+            .or_else(|| context_window(&config.model))
+        else {
+            return Ok(None);
+        };
+
+        let tokenizer = Tokenizer::for_model(
+            &config.model,
+            config.tokenizer.encoding,
+            config.tokenizer.vocab.as_deref(),
+        )?;
+
+        Ok(Some(Self {
+            tokenizer,
+            model: config.model.clone(),
+            window,
+            reply_tokens: config
+                .sampling
+                .max_completion_tokens
+                .unwrap_or(config.max_tokens),
+        }))
+    }
+
+    /// Tokenizer used to measure requests
+    #[must_use]
+    pub const fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// Context window in tokens
+    #[must_use]
+    pub const fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Tokens reserved for the reply of a request
+    #[must_use]
+    pub fn reply_tokens(&self, request: &ChatRequest) -> u32 {
+        request
+            .sampling
+            .max_completion_tokens
+            .unwrap_or(self.reply_tokens)
+    }
+
+    /// Tokens the messages and tool definitions of a request take up
+    #[must_use]
+    pub fn prompt_tokens(&self, request: &ChatRequest) -> usize {
+        let tools = if request.tools.is_empty() {
+            0
+        } else {
+            serde_json::to_string(&request.tools).map_or(0, |json| self.tokenizer.count(&json))
+        };
+
+        self.tokenizer.count_messages(&request.messages) + tools
+    }
+
+    /// Check that a request and its reply fit the context window
+    #[must_use]
+    pub fn check(&self, request: &ChatRequest) -> Option<ContextOverflow> {
+        let prompt_tokens = self.prompt_tokens(request);
+        let reply_tokens = self.reply_tokens(request);
+
+        (prompt_tokens + reply_tokens as usize > self.window as usize).then(|| ContextOverflow {
+            model: self.model.clone(),
+            prompt_tokens,
+            reply_tokens,
+            window: self.window,
+        })
+    }
+}
+
+/// A request that will not fit the model's context window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextOverflow {
+    /// Model the request is for
+    pub model: String,
+    /// Tokens in the request
+    pub prompt_tokens: usize,
+    /// Tokens reserved for the reply
+    pub reply_tokens: u32,
+    /// Context window of the model
+    pub window: u32,
+}
+
+impl fmt::Display for ContextOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the prompt is about {} tokens and {} more are reserved for the reply, \
+             but {} has a {}-token context window",
+            self.prompt_tokens, self.reply_tokens, self.model, self.window
+        )
+    }
+}
+
+/// What chat does with old turns when the history nears the context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextStrategy {
+    /// Drop the oldest turns
+    #[default]
+    Drop,
+    /// Replace old turns with a summary written by the model
+    Summarize,
+    /// Always send the whole history
+    Off,
+}
+
+impl fmt::Display for ContextStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drop => write!(f, "drop"),
+            Self::Summarize => write!(f, "summarize"),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Instructions for the request that summarizes old turns
+const SUMMARY_PROMPT: &str = "Summarize the conversation below so the summary can replace it. \
+Keep facts, decisions, names, numbers, file and code identifiers, and open questions; \
+leave out pleasantries. Reply with concise notes only.";
+
+/// Heading of the system note that replaces summarized turns
+pub const SUMMARY_HEADING: &str = "Summary of the earlier conversation:";
+
+/// Old turns removed from a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compaction {
+    /// Messages removed
+    pub removed: usize,
+    /// Tokens used to write the summary, when the turns were summarized
+    pub summary_usage: Option<Usage>,
+}
+
+/// Keeps chat history within the context window by compacting old turns
+#[derive(Debug, Clone)]
+pub struct ContextManager {
+    budget: Option<ContextBudget>,
+    settings: ContextConfig,
+}
+
+impl ContextManager {
+    /// Manager for a model whose window may be unknown, in which case
+    /// history is only compacted on request
+    #[must_use]
+    pub const fn new(budget: Option<ContextBudget>, settings: ContextConfig) -> Self {
+        Self { budget, settings }
+    }
+
+    /// Window and tokenizer of the model, when its window is known
+    #[must_use]
+    pub const fn budget(&self) -> Option<&ContextBudget> {
+        self.budget.as_ref()
+    }
+
+    /// Configured strategy
+    #[must_use]
+    pub const fn strategy(&self) -> ContextStrategy {
+        self.settings.strategy
+    }
+
+    /// Compact `messages` when `request`, built from them, fills more of the
+    /// window than the configured threshold
+    ///
+    /// Turns are dropped until the request is back under the threshold, or
+    /// summarized all at once. A summary that fails falls back to dropping.
+    pub async fn fit(
+        &self,
+        client: &dyn Provider,
+        messages: &mut Vec<Message>,
+        request: &ChatRequest,
+        strategy: ContextStrategy,
+    ) -> Option<Compaction> {
+        let budget = self
+            .budget
+            .as_ref()
+            .filter(|_| strategy != ContextStrategy::Off)?;
+
+        // The threshold is a fraction of the window, so the limit fits
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let limit = (f64::from(budget.window()) * self.settings.threshold) as usize;
+        let needed = budget.prompt_tokens(request) + budget.reply_tokens(request) as usize;
+        let excess = needed.checked_sub(limit).filter(|&excess| excess > 0)?;
+
+        if strategy == ContextStrategy::Summarize {
+            match summarize_turns(client, messages, self.settings.keep_turns).await {
+                Ok(compaction) => return compaction,
+                Err(e) => warn!("Summarizing old turns failed, dropping them instead: {}", e),
+            }
+        }
+
+        let mut freed = 0;
+        drop_oldest_turns(messages, self.settings.keep_turns, |turn| {
+            freed += turn
+                .iter()
+                .map(|message| budget.tokenizer().count_message(message))
+                .sum::<usize>();
+            freed >= excess
+        })
+    }
+
+    /// Compact every turn that can be, whatever the size of the history
+    ///
+    /// With `ContextStrategy::Off` the turns are summarized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the summary request fails.
+    pub async fn compact(
+        &self,
+        client: &dyn Provider,
+        messages: &mut Vec<Message>,
+        strategy: ContextStrategy,
+    ) -> Result<Option<Compaction>> {
+        match strategy {
+            ContextStrategy::Drop => Ok(drop_oldest_turns(
+                messages,
+                self.settings.keep_turns,
+                |_| false,
+            )),
+            ContextStrategy::Summarize | ContextStrategy::Off => {
+                summarize_turns(client, messages, self.settings.keep_turns).await
+            }
+        }
+    }
+}
+
+/// Turns that may be compacted, oldest first
+///
+/// A turn starts at a user or system message and runs until the next one.
+/// The leading system prompt, turns with a pinned message and the last
+/// `keep_turns` turns (at least one) are left out.
+fn compactable_turns(messages: &[Message], keep_turns: usize) -> Vec<Range<usize>> {
+    let start = usize::from(messages.first().is_some_and(|m| m.role == Role::System));
+
+    let mut turns: Vec<Range<usize>> = Vec::new();
+    for (index, message) in messages.iter().enumerate().skip(start) {
+        match turns.last_mut() {
+            Some(turn) if !matches!(message.role, Role::User | Role::System) => {
+                turn.end = index + 1;
+            }
+            _ => turns.push(index..index + 1),
+        }
+    }
+
+    turns.truncate(turns.len().saturating_sub(keep_turns.max(1)));
+    turns.retain(|turn| !messages[turn.clone()].iter().any(|m| m.pinned));
+    turns
+}
+
+/// Drop the oldest compactable turns until `enough` returns true for the
+/// messages dropped so far, or none are left
+///
+/// Whole turns are dropped, so tool calls are never separated from their
+/// results.
+pub fn drop_oldest_turns(
+    messages: &mut Vec<Message>,
+    keep_turns: usize,
+    mut enough: impl FnMut(&[Message]) -> bool,
+) -> Option<Compaction> {
+    let mut removed = 0;
+    for turn in compactable_turns(messages, keep_turns) {
+        let turn = turn.start - removed..turn.end - removed;
+        removed += turn.len();
+        let dropped: Vec<Message> = messages.drain(turn).collect();
+        if enough(&dropped) {
+            break;
+        }
+    }
+
+    (removed > 0).then_some(Compaction {
+        removed,
+        summary_usage: None,
+    })
+}
+
+/// Replace every compactable turn with a system note summarizing them,
+/// written by the model
+///
+/// # Errors
+///
+/// Returns an error if the summary request fails.
+pub async fn summarize_turns(
+    client: &dyn Provider,
+    messages: &mut Vec<Message>,
+    keep_turns: usize,
+) -> Result<Option<Compaction>> {
+    let turns = compactable_turns(messages, keep_turns);
+    let Some(position) = turns.first().map(|turn| turn.start) else {
+        return Ok(None);
+    };
+
+    let transcript = turns
+        .iter()
+        .flat_map(|turn| &messages[turn.clone()])
+        .map(transcript_entry)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let completion = client
+        .complete_chat(ChatRequest::new(vec![
+            Message::system(SUMMARY_PROMPT),
+            Message::user(transcript),
+        ]))
+        .await?;
+    let usage = completion.usage;
+    let summary = completion.into_text()?;
+    if summary.trim().is_empty() {
+        return Err(AppError::ApiError {
+            message: "the model returned an empty summary".to_string(),
+        });
+    }
+
+    let removed = turns.iter().map(std::iter::ExactSizeIterator::len).sum();
+    for turn in turns.into_iter().rev() {
+        messages.drain(turn);
+    }
+    messages.insert(
+        position,
+        Message::system(format!("{SUMMARY_HEADING}\n\n{}", summary.trim())).with_estimated_tokens(),
+    );
+
+    Ok(Some(Compaction {
+        removed,
+        summary_usage: Some(usage),
+    }))
+}
+
+/// One message of a transcript to summarize
+fn transcript_entry(message: &Message) -> String {
+    let role = match message.role {
+        Role::System | Role::Developer => "System",
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::Tool => "Tool result",
+    };
+
+    let mut entry = format!("{role}: {}", message.content);
+    if !message.images.is_empty() {
+        let _ = write!(entry, " [{} image(s) attached]", message.images.len());
+    }
+    for call in &message.tool_calls {
+        let _ = write!(
+            entry,
+            "\n(called {} with {})",
+            call.function.name, call.function.arguments
+        );
+    }
+    entry
+}
@@ -0,0 +1,307 @@
+//! Continuing replies cut off by the token limit
+//!
+//! A reply that stops with `finish_reason: length` is sent back as an
+//! assistant message followed by a request to carry on, and the next piece
+//! is stitched onto it. This repeats until the reply finishes or the
+//! configured total is used up.
+
+use crate::api::Message;
+use crate::config::ContinuationConfig;
+use crate::error::{AppError, Result};
+use crate::provider::{ChatRequest, Completion, EventStream, Provider, StreamEvent};
+use futures_util::stream::{BoxStream, Fuse};
+use futures_util::StreamExt;
+
+/// Asks the model to pick up where a truncated reply stopped
+const CONTINUE_PROMPT: &str = "Your reply was cut off. Continue exactly where it stopped, \
+without repeating anything and without any preamble.";
+
+/// Characters at the end of a reply compared against the start of its continuation
+const OVERLAP_WINDOW: usize = 256;
+
+/// Shortest repeated text, in bytes, treated as overlap rather than coincidence
+const MIN_OVERLAP: usize = 16;
+
+/// Whether a reply was cut off by the token limit while writing text
+fn is_cut_off(finish_reason: Option<&str>, message: &Message) -> bool {
+    finish_reason == Some("length") && message.tool_calls.is_empty()
+}
+
+/// Request for the next piece of a reply cut off after `partial`
+#[must_use]
+pub fn continuation_request(request: &ChatRequest, partial: &str) -> ChatRequest {
+    let mut next = request.clone();
+    next.messages.push(Message::assistant(partial));
+    next.messages.push(Message::user(CONTINUE_PROMPT));
+    next
+}
+
+/// Complete a chat request, continuing the reply while it is cut off
+///
+/// With continuation disabled, a cut-off reply is an
+/// `AppError::TokenLimitExceeded` as before. Otherwise a reply still cut off
+/// once `max_total_tokens` completion tokens are used is returned with
+/// `truncated` set; usage covers every request.
+///
+/// # Errors
+///
+/// Returns an error if a request fails, or the reply is cut off with continuation disabled.
+pub async fn complete(
+    client: &dyn Provider,
+    request: ChatRequest,
+    settings: &ContinuationConfig,
+) -> Result<Completion> {
+    let mut completion = client.complete_chat(request.clone()).await?;
+    if !settings.enabled {
+        if is_cut_off(completion.finish_reason.as_deref(), &completion.message) {
+            return Err(AppError::TokenLimitExceeded);
+        }
+        return Ok(completion);
+    }
+
+    while is_cut_off(completion.finish_reason.as_deref(), &completion.message)
+        && completion.usage.completion_tokens < settings.max_total_tokens
+    {
+        let next = client
+            .complete_chat(continuation_request(&request, &completion.message.content))
+            .await?;
+        completion.usage += next.usage;
+        completion.finish_reason = next.finish_reason;
+        if next.message.content.is_empty() {
+            break;
+        }
+        completion.message.content = stitch(&completion.message.content, &next.message.content);
+    }
+
+    completion.message.truncated =
+        is_cut_off(completion.finish_reason.as_deref(), &completion.message);
+    Ok(completion)
+}
+
+/// Stream a chat request, continuing the reply while it is cut off
+///
+/// With continuation disabled this is the backend's stream as is. Otherwise
+/// the text of each continuation is stitched on as it arrives, usage is
+/// reported once per request, and a single `Finish` event ends the stream;
+/// it is `length` only when the reply is still cut off at `max_total_tokens`.
+///
+/// # Errors
+///
+/// Returns an error if the first request fails.
+pub async fn stream<'a>(
+    client: &'a dyn Provider,
+    request: ChatRequest,
+    settings: &ContinuationConfig,
+) -> Result<BoxStream<'a, Result<StreamEvent>>> {
+    let events = client.stream_chat(request.clone()).await?;
+    if !settings.enabled {
+        return Ok(events);
+    }
+
+    let state = ChainState {
+        client,
+        request,
+        max_total_tokens: settings.max_total_tokens,
+        events: events.fuse(),
+        reply: Message::assistant(""),
+        piece_start: 0,
+        stitcher: None,
+        finish_reason: None,
+        completion_tokens: 0,
+        done: false,
+    };
+
+    Ok(Box::pin(futures_util::stream::unfold(
+        state,
+        |mut state| async move {
+            let event = state.next_event().await?;
+            Some((event, state))
+        },
+    )))
+}
+
+/// Progress of a reply streamed over several requests
+struct ChainState<'a> {
+    client: &'a dyn Provider,
+    request: ChatRequest,
+    max_total_tokens: u32,
+    /// Events of the current request; fused, since the end is checked twice
+    /// when held-back text is flushed
+    events: Fuse<EventStream>,
+    /// Reply so far, as stitched
+    reply: Message,
+    /// Length of the reply when the current request started
+    piece_start: usize,
+    /// Joins the current continuation onto the reply, if one is streaming
+    stitcher: Option<Stitcher>,
+    /// Finish reason of the current request, held back until it ends
+    finish_reason: Option<String>,
+    completion_tokens: u32,
+    done: bool,
+}
+
+impl ChainState<'_> {
+    async fn next_event(&mut self) -> Option<Result<StreamEvent>> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            match self.events.next().await {
+                Some(Ok(StreamEvent::Text(text))) => {
+                    let text = match &mut self.stitcher {
+                        Some(stitcher) => stitcher.push(&text),
+                        None => text,
+                    };
+                    if !text.is_empty() {
+                        self.reply.content.push_str(&text);
+                        return Some(Ok(StreamEvent::Text(text)));
+                    }
+                }
+                Some(Ok(StreamEvent::Reasoning(text))) => {
+                    return Some(Ok(StreamEvent::Reasoning(text)))
+                }
+                Some(Ok(StreamEvent::ToolCalls(calls))) => {
+                    self.reply.tool_calls.extend(calls.iter().cloned());
+                    return Some(Ok(StreamEvent::ToolCalls(calls)));
+                }
+                Some(Ok(StreamEvent::Finish(reason))) => self.finish_reason = Some(reason),
+                Some(Ok(StreamEvent::Usage(usage))) => {
+                    self.completion_tokens = self
+                        .completion_tokens
+                        .saturating_add(usage.completion_tokens);
+                    return Some(Ok(StreamEvent::Usage(usage)));
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    if let Some(rest) = self.stitcher.take().map(|mut stitcher| stitcher.finish()) {
+                        if !rest.is_empty() {
+                            self.reply.content.push_str(&rest);
+                            return Some(Ok(StreamEvent::Text(rest)));
+                        }
+                    }
+
+                    let continues = is_cut_off(self.finish_reason.as_deref(), &self.reply)
+                        && self.completion_tokens < self.max_total_tokens
+                        && self.reply.content.len() > self.piece_start;
+                    if !continues {
+                        self.done = true;
+                        return self
+                            .finish_reason
+                            .take()
+                            .map(|reason| Ok(StreamEvent::Finish(reason)));
+                    }
+
+                    let request = continuation_request(&self.request, &self.reply.content);
+                    match self.client.stream_chat(request).await {
+                        Ok(events) => {
+                            self.events = events.fuse();
+                            self.piece_start = self.reply.content.len();
+                            self.stitcher = Some(Stitcher::new(&self.reply.content));
+                            self.finish_reason = None;
+                        }
+                        Err(e) => {
+                            self.done = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Join a continuation onto the text it continues
+#[must_use]
+pub fn stitch(text: &str, continuation: &str) -> String {
+    let mut stitcher = Stitcher::new(text);
+    let mut joined = text.to_string();
+    joined.push_str(&stitcher.push(continuation));
+    joined.push_str(&stitcher.finish());
+    joined
+}
+
+/// Joins a streamed continuation onto the text before it
+///
+/// The start of the continuation is held back until it can be compared with
+/// the end of the text: a repeat of the text's last words is dropped, and so
+/// is a fence reopening the code block the text was cut off in.
+#[derive(Debug)]
+pub struct Stitcher {
+    /// End of the text being continued
+    tail: String,
+    /// Whether the text stopped inside a code block
+    in_code_block: bool,
+    pending: String,
+    resolved: bool,
+}
+
+impl Stitcher {
+    /// Stitcher for a continuation of `text`
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        let tail_start = text
+            .char_indices()
+            .rev()
+            .nth(OVERLAP_WINDOW - 1)
+            .map_or(0, |(index, _)| index);
+
+        Self {
+            tail: text[tail_start..].to_string(),
+            in_code_block: text.lines().filter(|line| is_fence(line)).count() % 2 == 1,
+            pending: String::new(),
+            resolved: false,
+        }
+    }
+
+    /// Add a chunk of the continuation and get the text ready to show
+    pub fn push(&mut self, chunk: &str) -> String {
+        if self.resolved {
+            return chunk.to_string();
+        }
+
+        self.pending.push_str(chunk);
+        let reopens_fence = self.in_code_block && is_fence(&self.pending);
+        let ready = self.pending.chars().count() >= OVERLAP_WINDOW
+            && (!reopens_fence || self.pending.contains('\n'));
+        if ready {
+            self.resolve()
+        } else {
+            String::new()
+        }
+    }
+
+    /// End of the continuation; get the text still held back
+    pub fn finish(&mut self) -> String {
+        if self.resolved {
+            String::new()
+        } else {
+            self.resolve()
+        }
+    }
+
+    fn resolve(&mut self) -> String {
+        self.resolved = true;
+        let mut text = std::mem::take(&mut self.pending);
+
+        if self.in_code_block && is_fence(&text) {
+            let line_end = text.find('\n').map_or(text.len(), |index| index + 1);
+            text.drain(..line_end);
+        }
+
+        let overlap = (MIN_OVERLAP..=self.tail.len().min(text.len()))
+            .rev()
+            .filter(|&len| text.is_char_boundary(len))
+            .find(|&len| self.tail.ends_with(&text[..len]))
+            .unwrap_or(0);
+        text.split_off(overlap)
+    }
+}
+
+/// Whether a line opens or closes a fenced code block
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
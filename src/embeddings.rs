@@ -0,0 +1,92 @@
+//! Embeddings endpoint of the OpenAI API (also served by LM Studio and Ollama)
+
+use crate::api::OpenAIClient;
+use crate::error::{AppError, Result};
+use crate::usage::{self, Usage};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::debug;
+
+/// Body of an `embeddings` request
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+/// `embeddings` response
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Embed each input, sending at most `batch_size` inputs per request
+///
+/// The vectors are returned in the same order as the inputs.
+///
+/// # Errors
+///
+/// Returns an error if a request fails or returns the wrong number of vectors.
+pub async fn embed(
+    client: &OpenAIClient,
+    model: &str,
+    inputs: &[String],
+    batch_size: usize,
+) -> Result<Vec<Vec<f32>>> {
+    let url = client.endpoint_url("embeddings", model);
+    let mut embeddings = Vec::with_capacity(inputs.len());
+
+    for batch in inputs.chunks(batch_size.max(1)) {
+        debug!("Embedding {} inputs with {}", batch.len(), model);
+
+        let request = EmbeddingRequest {
+            model,
+            input: batch,
+        };
+        let started = Instant::now();
+        let mut response: EmbeddingResponse = client
+            .post(&url, |builder| builder.json(&request))
+            .await?
+            .json()
+            .await?;
+
+        if response.data.len() != batch.len() {
+            return Err(AppError::ApiError {
+                message: format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    response.data.len()
+                ),
+            });
+        }
+
+        let usage = response.usage.map_or_else(
+            || Usage {
+                estimated: true,
+                ..Usage::new(
+                    batch
+                        .iter()
+                        .map(|input| usage::estimate_tokens(input))
+                        .sum(),
+                    0,
+                )
+            },
+            Usage::normalized,
+        );
+        client
+            .ledger()
+            .record("embeddings", model, &usage, started.elapsed());
+
+        response.data.sort_by_key(|data| data.index);
+        embeddings.extend(response.data.into_iter().map(|data| data.embedding));
+    }
+
+    Ok(embeddings)
+}
@@ -34,6 +34,24 @@ pub enum AppError {
     
     #[error("Network error: {0}")]
     Network(String),
+
+    #[error("Tool error: {0}")]
+    ToolError(String),
+
+    #[error("Schema validation failed: {0}")]
+    SchemaError(String),
+
+    #[error("Invalid image: {0}")]
+    ImageError(String),
+
+    #[error("Audio error: {0}")]
+    AudioError(String),
+
+    #[error("Vector index error: {0}")]
+    IndexError(String),
+
+    #[error("Tokenizer error: {0}")]
+    TokenizerError(String),
 }
 
 /// Result type alias for the application
@@ -0,0 +1,495 @@
+//! Built-in file-system tools, sandboxed to a workspace root
+
+use crate::api::ToolDefinition;
+use crate::error::{AppError, Result};
+use crate::tools::{Tool, ToolRegistry};
+use async_trait::async_trait;
+use colored::Colorize;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Maximum number of matches returned by `search_text`
+const MAX_SEARCH_MATCHES: usize = 100;
+
+/// Files larger than this are skipped by `search_text`
+const MAX_SEARCH_FILE_BYTES: u64 = 1_000_000;
+
+/// Directories `search_text` and the RAG indexer never descend into
+pub const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// A directory the tools may not escape
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// Create a workspace rooted at an existing directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the root does not exist or is not a directory.
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| {
+            AppError::ConfigError(format!("Invalid workspace {}: {e}", root.display()))
+        })?;
+
+        if !root.is_dir() {
+            return Err(AppError::ConfigError(format!(
+                "Workspace {} is not a directory",
+                root.display()
+            )));
+        }
+
+        Ok(Self { root })
+    }
+
+    /// The canonical workspace root
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a path argument to a location inside the workspace
+    ///
+    /// The longest existing ancestor is canonicalized, so neither `..` nor
+    /// symlinks can lead outside the root. The path itself need not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path would lead outside the workspace.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let candidate = self.root.join(path);
+        let mut existing = candidate.as_path();
+        let mut missing = Vec::new();
+
+        let canonical = loop {
+            if let Ok(canonical) = existing.canonicalize() {
+                break canonical;
+            }
+            // `file_name` is `None` for `..`, which cannot be resolved safely
+            let name = existing.file_name().ok_or_else(|| outside(path))?;
+            missing.push(name);
+            existing = existing.parent().ok_or_else(|| outside(path))?;
+        };
+
+        let resolved = missing
+            .into_iter()
+            .rev()
+            .fold(canonical, |resolved, name| resolved.join(name));
+
+        if resolved.starts_with(&self.root) {
+            Ok(resolved)
+        } else {
+            Err(outside(path))
+        }
+    }
+
+    /// Path relative to the workspace root, for display
+    fn relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if relative.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            relative.display().to_string()
+        }
+    }
+}
+
+fn outside(path: &str) -> AppError {
+    AppError::ToolError(format!("{path} is outside the workspace"))
+}
+
+/// Parse tool arguments into a typed struct
+fn parse_args<T: for<'de> Deserialize<'de>>(arguments: serde_json::Value) -> Result<T> {
+    serde_json::from_value(arguments)
+        .map_err(|e| AppError::ToolError(format!("Invalid arguments: {e}")))
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+/// Reads a text file from the workspace
+pub struct ReadFileTool {
+    workspace: Arc<Workspace>,
+    max_bytes: usize,
+}
+
+#[derive(Deserialize)]
+struct ReadFileArgs {
+    path: String,
+}
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            "read_file",
+            "Read a UTF-8 text file from the workspace. Paths are relative to the workspace root.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path relative to the workspace root"}
+                },
+                "required": ["path"]
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> Result<String> {
+        let args: ReadFileArgs = parse_args(arguments)?;
+        let path = self.workspace.resolve(&args.path)?;
+
+        let bytes = tokio::fs::read(&path).await?;
+        let truncated = bytes.len() > self.max_bytes;
+        let bytes = &bytes[..bytes.len().min(self.max_bytes)];
+
+        // A multi-byte character may have been cut at the limit
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(e) if truncated && e.error_len().is_none() => {
+                std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => {
+                return Err(AppError::ToolError(format!(
+                    "{} is not a UTF-8 text file",
+                    args.path
+                )))
+            }
+        };
+
+        if truncated {
+            Ok(format!(
+                "{text}\n\n[truncated after {} bytes]",
+                self.max_bytes
+            ))
+        } else {
+            Ok(text.to_string())
+        }
+    }
+}
+
+/// Lists the entries of a workspace directory
+pub struct ListDirectoryTool {
+    workspace: Arc<Workspace>,
+}
+
+#[derive(Deserialize)]
+struct ListDirectoryArgs {
+    #[serde(default = "default_path")]
+    path: String,
+}
+
+#[async_trait]
+impl Tool for ListDirectoryTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            "list_directory",
+            "List files and subdirectories of a workspace directory. Directories end with `/`.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Directory relative to the workspace root (default `.`)"}
+                }
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> Result<String> {
+        let args: ListDirectoryArgs = parse_args(arguments)?;
+        let path = self.workspace.resolve(&args.path)?;
+
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&path).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                entries.push(format!("{name}/"));
+            } else {
+                entries.push(format!("{name} ({} bytes)", metadata.len()));
+            }
+        }
+        entries.sort();
+
+        if entries.is_empty() {
+            return Ok(format!("{} is empty", self.workspace.relative(&path)));
+        }
+
+        Ok(entries.join("\n"))
+    }
+}
+
+/// Searches workspace files for a literal string
+pub struct SearchTextTool {
+    workspace: Arc<Workspace>,
+}
+
+#[derive(Deserialize)]
+struct SearchTextArgs {
+    query: String,
+    #[serde(default = "default_path")]
+    path: String,
+    #[serde(default)]
+    case_sensitive: bool,
+}
+
+impl SearchTextTool {
+    /// Collect `path:line: text` matches under `dir`, depth first in name order
+    fn search(&self, dir: &Path, needle: &str, case_sensitive: bool, matches: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries: Vec<_> = entries.filter_map(std::io::Result::ok).collect();
+        entries.sort_by_key(std::fs::DirEntry::file_name);
+
+        for entry in entries {
+            if matches.len() >= MAX_SEARCH_MATCHES {
+                return;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            // Symlinks are skipped so the walk cannot leave the workspace
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    self.search(&entry.path(), needle, case_sensitive, matches);
+                }
+            } else if file_type.is_file() {
+                self.search_file(&entry.path(), needle, case_sensitive, matches);
+            }
+        }
+    }
+
+    fn search_file(
+        &self,
+        path: &Path,
+        needle: &str,
+        case_sensitive: bool,
+        matches: &mut Vec<String>,
+    ) {
+        let too_large = std::fs::metadata(path).map_or(true, |m| m.len() > MAX_SEARCH_FILE_BYTES);
+        if too_large {
+            return;
+        }
+
+        // Binary and non-UTF-8 files are skipped
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return;
+        };
+        if text.contains('\0') {
+            return;
+        }
+
+        for (number, line) in text.lines().enumerate() {
+            let found = if case_sensitive {
+                line.contains(needle)
+            } else {
+                line.to_lowercase().contains(needle)
+            };
+
+            if found {
+                matches.push(format!(
+                    "{}:{}: {}",
+                    self.workspace.relative(path),
+                    number + 1,
+                    line.trim()
+                ));
+                if matches.len() >= MAX_SEARCH_MATCHES {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for SearchTextTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            "search_text",
+            "Search text files in the workspace for a literal string. Returns `path:line: text` matches.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Text to search for"},
+                    "path": {"type": "string", "description": "Directory or file to search (default `.`)"},
+                    "case_sensitive": {"type": "boolean", "description": "Match case exactly (default false)"}
+                },
+                "required": ["query"]
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> Result<String> {
+        let args: SearchTextArgs = parse_args(arguments)?;
+        if args.query.is_empty() {
+            return Err(AppError::ToolError("query must not be empty".to_string()));
+        }
+
+        let path = self.workspace.resolve(&args.path)?;
+        let needle = if args.case_sensitive {
+            args.query.clone()
+        } else {
+            args.query.to_lowercase()
+        };
+
+        let mut matches = Vec::new();
+        if path.is_file() {
+            self.search_file(&path, &needle, args.case_sensitive, &mut matches);
+        } else {
+            self.search(&path, &needle, args.case_sensitive, &mut matches);
+        }
+
+        if matches.is_empty() {
+            return Ok(format!("No matches for {:?}", args.query));
+        }
+
+        if matches.len() >= MAX_SEARCH_MATCHES {
+            matches.push(format!("[stopped after {MAX_SEARCH_MATCHES} matches]"));
+        }
+
+        Ok(matches.join("\n"))
+    }
+}
+
+/// How `write_file` gets permission to modify the workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteApproval {
+    /// Ask on the terminal before each write, offering "always allow"
+    Prompt,
+    /// Allow every write without asking
+    AllowAll,
+    /// Refuse every write
+    DenyAll,
+}
+
+/// Writes a text file in the workspace after approval
+pub struct WriteFileTool {
+    workspace: Arc<Workspace>,
+    approval: WriteApproval,
+    /// Set when the user picks "always allow" for the rest of the session
+    always_allow: AtomicBool,
+}
+
+#[derive(Deserialize)]
+struct WriteFileArgs {
+    path: String,
+    content: String,
+}
+
+impl WriteFileTool {
+    /// Ask whether the write may proceed
+    fn approve(&self, path: &Path, content: &str) -> bool {
+        match self.approval {
+            WriteApproval::AllowAll => return true,
+            WriteApproval::DenyAll => return false,
+            WriteApproval::Prompt if self.always_allow.load(Ordering::Relaxed) => return true,
+            WriteApproval::Prompt => {}
+        }
+
+        let action = if path.exists() { "Overwrite" } else { "Create" };
+        println!(
+            "{} {} {} ({} lines, {} bytes)",
+            "⚠".yellow(),
+            action.yellow().bold(),
+            self.workspace.relative(path).bold(),
+            content.lines().count(),
+            content.len()
+        );
+
+        let choice = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+            .with_prompt("Allow this write?")
+            .items(&["Allow", "Deny", "Always allow for this session"])
+            .default(1)
+            .interact();
+
+        match choice {
+            Ok(0) => true,
+            Ok(2) => {
+                self.always_allow.store(true, Ordering::Relaxed);
+                true
+            }
+            // Denied, or no terminal to ask on
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            "write_file",
+            "Create or overwrite a UTF-8 text file in the workspace. The user must approve each write.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path relative to the workspace root"},
+                    "content": {"type": "string", "description": "Complete new file content"}
+                },
+                "required": ["path", "content"]
+            }),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> Result<String> {
+        let args: WriteFileArgs = parse_args(arguments)?;
+        let path = self.workspace.resolve(&args.path)?;
+
+        if path.is_dir() {
+            return Err(AppError::ToolError(format!("{} is a directory", args.path)));
+        }
+
+        if !self.approve(&path, &args.content) {
+            return Err(AppError::ToolError(format!(
+                "The user denied writing {}",
+                args.path
+            )));
+        }
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, &args.content).await?;
+
+        Ok(format!(
+            "Wrote {} bytes to {}",
+            args.content.len(),
+            self.workspace.relative(&path)
+        ))
+    }
+}
+
+/// Register the file-system tools for a workspace
+pub fn register(
+    registry: &mut ToolRegistry,
+    workspace: Workspace,
+    max_read_bytes: usize,
+    approval: WriteApproval,
+) {
+    let workspace = Arc::new(workspace);
+
+    registry.register(ReadFileTool {
+        workspace: Arc::clone(&workspace),
+        max_bytes: max_read_bytes,
+    });
+    registry.register(ListDirectoryTool {
+        workspace: Arc::clone(&workspace),
+    });
+    registry.register(SearchTextTool {
+        workspace: Arc::clone(&workspace),
+    });
+    registry.register(WriteFileTool {
+        workspace,
+        approval,
+        always_allow: AtomicBool::new(false),
+    });
+}
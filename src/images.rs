@@ -0,0 +1,173 @@
+//! Image generation endpoint of the OpenAI API
+
+use crate::api::OpenAIClient;
+use crate::error::{AppError, Result};
+use crate::usage::Usage;
+use crate::vision;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::debug;
+
+/// Options for an image generation request
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    /// Image model, e.g. `dall-e-3` or `gpt-image-1`
+    pub model: String,
+    /// Size such as `1024x1024`, or `auto`
+    pub size: String,
+    /// Number of images to generate
+    pub count: u32,
+    /// Quality level (model default when `None`)
+    pub quality: Option<String>,
+}
+
+/// An image written to disk
+#[derive(Debug, Clone)]
+pub struct GeneratedImage {
+    /// File the image was written to
+    pub path: PathBuf,
+    /// The prompt the model actually used, when it rewrote ours
+    pub revised_prompt: Option<String>,
+}
+
+/// Body of an `images/generations` request
+#[derive(Debug, Serialize)]
+struct GenerationRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    n: u32,
+    size: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<&'a str>,
+}
+
+/// `images/generations` response
+#[derive(Debug, Deserialize)]
+struct GenerationResponse {
+    data: Vec<ImageData>,
+    /// Token counts, sent by token-priced models such as `gpt-image-1`
+    usage: Option<ImageUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+/// One generated image, either inline or as a temporary URL
+#[derive(Debug, Deserialize)]
+struct ImageData {
+    b64_json: Option<String>,
+    url: Option<String>,
+    revised_prompt: Option<String>,
+}
+
+/// Generate images and write them to numbered files in `output_dir`
+///
+/// Files are named `image-1.png`, `image-2.png`, ..., skipping names that
+/// already exist. JPEG and WebP output gets the matching extension.
+///
+/// # Errors
+///
+/// Returns an error if the request fails or a file cannot be written.
+pub async fn generate(
+    client: &OpenAIClient,
+    prompt: &str,
+    options: &ImageOptions,
+    output_dir: &Path,
+) -> Result<Vec<GeneratedImage>> {
+    let url = client.endpoint_url("images/generations", &options.model);
+    let request = GenerationRequest {
+        model: &options.model,
+        prompt,
+        n: options.count,
+        size: &options.size,
+        quality: options.quality.as_deref(),
+    };
+
+    debug!("Requesting {} image(s) from {}", options.count, url);
+
+    let started = Instant::now();
+    let response: GenerationResponse = client
+        .post(&url, |builder| builder.json(&request))
+        .await?
+        .json()
+        .await?;
+
+    let usage = response
+        .usage
+        .as_ref()
+        .map_or_else(Usage::default, |usage| {
+            Usage::new(usage.input_tokens, usage.output_tokens)
+        });
+    client.ledger().record(
+        "images/generations",
+        &options.model,
+        &usage,
+        started.elapsed(),
+    );
+
+    tokio::fs::create_dir_all(output_dir).await?;
+
+    let mut images = Vec::with_capacity(response.data.len());
+    let mut number = 1;
+
+    for image in response.data {
+        let bytes = match (image.b64_json, image.url) {
+            (Some(data), _) => base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|e| AppError::ApiError {
+                    message: format!("Invalid base64 image data: {e}"),
+                })?,
+            (None, Some(url)) => download(client, &url).await?,
+            (None, None) => {
+                return Err(AppError::ApiError {
+                    message: "Image response has neither b64_json nor url".to_string(),
+                })
+            }
+        };
+
+        let extension = match vision::sniff_mime_type(&bytes) {
+            Some("image/jpeg") => "jpg",
+            Some("image/webp") => "webp",
+            _ => "png",
+        };
+
+        let path = loop {
+            let path = output_dir.join(format!("image-{number}.{extension}"));
+            number += 1;
+            if !tokio::fs::try_exists(&path).await? {
+                break path;
+            }
+        };
+
+        tokio::fs::write(&path, &bytes).await?;
+        images.push(GeneratedImage {
+            path,
+            revised_prompt: image.revised_prompt,
+        });
+    }
+
+    Ok(images)
+}
+
+/// Fetch an image from a temporary URL
+///
+/// The URL is pre-signed, so the API key is deliberately not sent.
+async fn download(client: &OpenAIClient, url: &str) -> Result<Vec<u8>> {
+    debug!("Downloading generated image from {}", url);
+
+    let response = client.http_client().get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(AppError::ApiError {
+            message: format!("Failed to download image: {}", response.status()),
+        });
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
@@ -0,0 +1,265 @@
+//! Persistent usage ledger behind the `stats` command
+//!
+//! Every API request is appended to a JSON Lines file with its token counts,
+//! latency and estimated cost, so spending can be reported across sessions.
+
+use crate::config::{Config, UsageConfig};
+use crate::error::Result;
+use crate::provider::{EventStream, StreamEvent};
+use crate::usage::Usage;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// One request in the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// When the request finished
+    pub timestamp: DateTime<Utc>,
+    /// API endpoint, e.g. `chat` or `embeddings`
+    pub endpoint: String,
+    /// Model that served the request
+    pub model: String,
+    /// Tokens in the request
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    /// Tokens in the reply
+    #[serde(default)]
+    pub completion_tokens: u32,
+    /// Whether the token counts were estimated locally
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+    /// Time from sending the request to the end of the reply
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Cost in US dollars, when the model has a price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+/// Appends requests to the ledger file, or does nothing when disabled
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    path: Option<PathBuf>,
+    usage: Arc<UsageConfig>,
+}
+
+impl Ledger {
+    /// Ledger configured by the `usage` section
+    #[must_use]
+    pub fn from_config(config: &Config) -> Self {
+        let path = config.usage.ledger.then(|| ledger_path(&config.usage));
+
+        Self {
+            path,
+            usage: Arc::new(config.usage.clone()),
+        }
+    }
+
+    /// Append a finished request
+    ///
+    /// Failing to write is logged rather than returned, so a read-only data
+    /// directory never fails the request itself.
+    pub fn record(&self, endpoint: &str, model: &str, usage: &Usage, latency: Duration) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let entry = LedgerEntry {
+            timestamp: Utc::now(),
+            endpoint: endpoint.to_string(),
+            model: model.to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated: usage.estimated,
+            latency_ms: u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+            cost: self
+                .usage
+                .price(model)
+                .map(|price| price.cost(usage.prompt_tokens, usage.completion_tokens)),
+        };
+
+        if let Err(e) = append(path, &entry) {
+            warn!("Failed to write usage ledger {}: {}", path.display(), e);
+        }
+    }
+
+    /// Record a streaming request sent at `started` once its usage arrives
+    #[must_use]
+    pub fn track(
+        &self,
+        events: EventStream,
+        endpoint: &'static str,
+        model: String,
+        started: Instant,
+    ) -> EventStream {
+        let ledger = self.clone();
+
+        Box::pin(events.inspect(move |event| {
+            if let Ok(StreamEvent::Usage(usage)) = event {
+                ledger.record(endpoint, &model, usage, started.elapsed());
+            }
+        }))
+    }
+}
+
+/// Ledger file: `usage.ledger_path`, or `usage.jsonl` in the data directory
+#[must_use]
+pub fn ledger_path(usage: &UsageConfig) -> PathBuf {
+    if let Some(path) = &usage.ledger_path {
+        return path.clone();
+    }
+
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("llm-cli");
+    path.push("usage.jsonl");
+    path
+}
+
+fn append(path: &Path, entry: &LedgerEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Read every entry of a ledger file, skipping lines that do not parse
+///
+/// A missing file is an empty ledger.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub async fn read(path: &Path) -> Result<Vec<LedgerEntry>> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping malformed usage ledger line: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Totals over a set of requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    /// Number of requests
+    pub requests: u64,
+    /// Tokens sent
+    pub prompt_tokens: u64,
+    /// Tokens received
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars of the priced requests
+    pub cost: f64,
+    /// Requests whose model has no price, left out of `cost`
+    pub unpriced_requests: u64,
+    /// Mean request latency
+    pub average_latency_ms: u64,
+    #[serde(skip)]
+    total_latency_ms: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, entry: &LedgerEntry, cost: Option<f64>) {
+        self.requests += 1;
+        self.prompt_tokens += u64::from(entry.prompt_tokens);
+        self.completion_tokens += u64::from(entry.completion_tokens);
+        match cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced_requests += 1,
+        }
+        self.total_latency_ms = self.total_latency_ms.saturating_add(entry.latency_ms);
+        self.average_latency_ms = self.total_latency_ms / self.requests;
+    }
+
+    /// Prompt and completion tokens together
+    #[must_use]
+    pub const fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Ledger totals grouped by day, model and endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageReport {
+    /// Earliest request included, if the report is limited
+    pub since: Option<DateTime<Utc>>,
+    /// Totals over every included request
+    pub total: UsageTotals,
+    /// Totals per UTC day
+    pub by_day: BTreeMap<NaiveDate, UsageTotals>,
+    /// Totals per model
+    pub by_model: BTreeMap<String, UsageTotals>,
+    /// Totals per endpoint
+    pub by_endpoint: BTreeMap<String, UsageTotals>,
+}
+
+impl UsageReport {
+    /// Summarize the entries from `since` on
+    ///
+    /// Entries recorded without a cost are priced with the current table, so
+    /// adding a price later also covers past requests.
+    #[must_use]
+    pub fn new(entries: &[LedgerEntry], since: Option<DateTime<Utc>>, usage: &UsageConfig) -> Self {
+        let mut report = Self {
+            since,
+            ..Self::default()
+        };
+
+        for entry in entries {
+            if since.is_some_and(|since| entry.timestamp < since) {
+                continue;
+            }
+
+            let cost = entry.cost.or_else(|| {
+                usage
+                    .price(&entry.model)
+                    .map(|price| price.cost(entry.prompt_tokens, entry.completion_tokens))
+            });
+
+            report.total.add(entry, cost);
+            report
+                .by_day
+                .entry(entry.timestamp.date_naive())
+                .or_default()
+                .add(entry, cost);
+            report
+                .by_model
+                .entry(entry.model.clone())
+                .or_default()
+                .add(entry, cost);
+            report
+                .by_endpoint
+                .entry(entry.endpoint.clone())
+                .or_default()
+                .add(entry, cost);
+        }
+
+        report
+    }
+}
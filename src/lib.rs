@@ -1,11 +1,34 @@
 //! LLM CLI Library - A universal CLI for LLMs
 
+pub mod anthropic;
 pub mod api;
+pub mod audio;
 pub mod cli;
 pub mod config;
+pub mod context;
+pub mod continuation;
+pub mod embeddings;
 pub mod error;
+pub mod fs_tools;
+pub mod images;
+pub mod ledger;
+pub mod logprobs;
+pub mod mcp;
+pub mod ollama;
+pub mod provider;
+pub mod rag;
+pub mod reasoning;
+pub mod retry;
+pub mod sampling;
 pub mod session;
+pub mod sse;
 pub mod streaming_buffer;
+pub mod structured;
+pub mod tokenizer;
+pub mod tools;
 pub mod ui;
+pub mod usage;
+pub mod vector_index;
+pub mod vision;
 
 pub use error::{AppError, Result};
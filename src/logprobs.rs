@@ -0,0 +1,122 @@
+//! Token log probabilities of a reply, and how confident the model was
+//!
+//! OpenAI-compatible servers return, for each token of the reply, its log
+//! probability and the most likely tokens that could have been chosen in its
+//! place. Low probabilities mark the spots where the model was guessing.
+
+use serde::{Deserialize, Serialize};
+
+/// Most alternatives per token the API returns
+pub const MAX_TOP_LOGPROBS: i64 = 20;
+
+/// Tokens listed after a reply as the least confident
+pub const LOWEST_SHOWN: usize = 10;
+
+/// How sure the model was of a token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Below 20%
+    VeryLow,
+    /// 20% to 50%
+    Low,
+    /// 50% to 90%
+    Medium,
+    /// 90% and above
+    High,
+}
+
+impl Confidence {
+    /// Confidence band of a probability between 0 and 1
+    #[must_use]
+    pub fn of(probability: f64) -> Self {
+        if probability >= 0.9 {
+            Self::High
+        } else if probability >= 0.5 {
+            Self::Medium
+        } else if probability >= 0.2 {
+            Self::Low
+        } else {
+            Self::VeryLow
+        }
+    }
+}
+
+/// A token of the reply with the alternatives the model considered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    /// Text of the token
+    pub token: String,
+    /// Natural log of the token's probability
+    pub logprob: f64,
+    /// Most likely tokens at this position, the chosen one included
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+impl TokenLogprob {
+    /// Probability of the token, between 0 and 1
+    #[must_use]
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+
+    /// Confidence band of the token
+    #[must_use]
+    pub fn confidence(&self) -> Confidence {
+        Confidence::of(self.probability())
+    }
+
+    /// Alternatives other than the chosen token, most likely first
+    pub fn alternatives(&self) -> impl Iterator<Item = &TopLogprob> {
+        self.top_logprobs
+            .iter()
+            .filter(move |top| top.token != self.token)
+    }
+}
+
+/// A token the model could have chosen instead
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    /// Text of the token
+    pub token: String,
+    /// Natural log of the token's probability
+    pub logprob: f64,
+}
+
+impl TopLogprob {
+    /// Probability of the token, between 0 and 1
+    #[must_use]
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+/// Logprobs of a choice, as returned by the API
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChoiceLogprobs {
+    /// Tokens of the text content; `null` from servers that send none
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub content: Vec<TokenLogprob>,
+}
+
+/// Positions of the least confident tokens, least confident first
+///
+/// Tokens that are only whitespace are left out.
+#[must_use]
+pub fn lowest(tokens: &[TokenLogprob], count: usize) -> Vec<usize> {
+    let mut positions: Vec<usize> = (0..tokens.len())
+        .filter(|&position| !tokens[position].token.trim().is_empty())
+        .collect();
+    positions.sort_by(|&a, &b| tokens[a].logprob.total_cmp(&tokens[b].logprob));
+    positions.truncate(count);
+    positions
+}
+
+/// Deserialize a list that may be `null`
+fn null_as_empty<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
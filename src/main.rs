@@ -1,19 +1,42 @@
 //! Modern LLM CLI with best practices for 2025 - Supports OpenAI, LM Studio, Ollama, and more
 
+mod anthropic;
 mod api;
+mod audio;
 mod cli;
 mod config;
+mod context;
+mod continuation;
+mod embeddings;
 mod error;
+mod fs_tools;
+mod images;
+mod ledger;
+mod logprobs;
+mod mcp;
+mod ollama;
+mod provider;
+mod rag;
+mod reasoning;
+mod retry;
+mod sampling;
 mod session;
+mod sse;
 mod streaming_buffer;
+mod structured;
+mod tokenizer;
+mod tools;
 mod ui;
+mod usage;
+mod vector_index;
+mod vision;
 
 use anyhow::Context;
 use clap::Parser;
-use cli::{Cli, Commands, OutputFormat};
+use cli::{Cli, Commands, IndexCommand, McpCommand, OutputFormat};
 use colored::Colorize;
 use config::Config;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -36,30 +59,134 @@ async fn main() -> anyhow::Result<()> {
     if let Some(max_tokens) = cli.max_tokens {
         config.max_tokens = max_tokens;
     }
+    if cli.auto_continue {
+        config.continuation.enabled = true;
+    }
+    if let Some(display) = cli.thinking {
+        config.reasoning.display = display;
+    }
+    config.sampling = config.sampling.overridden_by(&cli.sampling.into_params());
+    config.sampling.validate()?;
 
     // Execute command
     match cli.command {
         None | Some(Commands::Chat { .. }) => {
             run_chat_mode(config, cli.command).await?;
         }
-        Some(Commands::Query { message, format, stream }) => {
-            run_query_mode(config, message, format, stream).await?;
+        Some(Commands::Query {
+            message,
+            images,
+            schema: Some(schema),
+            max_repairs,
+            ..
+        }) => {
+            run_structured_query(config, message, &images, &schema, max_repairs).await?;
+        }
+        Some(Commands::Query {
+            message,
+            format,
+            images,
+            logprobs: true,
+            top_logprobs,
+            ..
+        }) => {
+            run_logprobs_query(config, message, &images, format, top_logprobs).await?;
+        }
+        Some(Commands::Query {
+            message,
+            format,
+            stream,
+            images,
+            choices,
+            ..
+        }) => {
+            run_query_mode(config, message, &images, format, stream, choices).await?;
+        }
+        Some(Commands::Transcribe {
+            file,
+            language,
+            prompt,
+            format,
+            model,
+        }) => {
+            let options = audio::TranscriptionOptions {
+                model: model.unwrap_or_else(|| config.audio.transcription_model.clone()),
+                language,
+                prompt,
+                format,
+            };
+            run_transcribe(config, &file, &options).await?;
         }
-        Some(Commands::Config {
-            show,
-            api_key,
+        Some(Commands::Speak {
+            text,
+            stdin,
+            output,
+            voice,
+            format,
+            speed,
             model,
-            system_prompt,
-            base_url,
-            api_path,
         }) => {
-            run_config_command(config, show, api_key, model, system_prompt, base_url, api_path).await?;
+            let options = audio::SpeechOptions {
+                model: model.unwrap_or_else(|| config.audio.speech_model.clone()),
+                voice: voice.unwrap_or_else(|| config.audio.voice.clone()),
+                format: format
+                    .or_else(|| audio::format_for_path(&output))
+                    .unwrap_or(cli::SpeechFormat::Mp3),
+                speed,
+            };
+            let text = if stdin { None } else { text };
+            run_speak(config, text, &output, &options).await?;
+        }
+        Some(Commands::Image {
+            prompt,
+            size,
+            count,
+            quality,
+            output_dir,
+            model,
+        }) => {
+            let options = images::ImageOptions {
+                model: model.unwrap_or_else(|| config.images.model.clone()),
+                size,
+                count,
+                quality,
+            };
+            run_image_generation(config, &prompt, &options, &output_dir).await?;
+        }
+        Some(Commands::Embed {
+            input,
+            format,
+            model,
+        }) => {
+            let model = model.unwrap_or_else(|| config.embeddings.model.clone());
+            run_embed(config, &input, format, &model).await?;
+        }
+        Some(Commands::Index { command }) => match command {
+            IndexCommand::Add { input, index } => run_index_add(config, &input, &index).await?,
+            IndexCommand::Search { query, k, index } => {
+                run_index_search(config, &query, k, &index).await?;
+            }
+        },
+        Some(Commands::Tokens {
+            texts,
+            files,
+            stdin,
+            encoding,
+            vocab,
+        }) => {
+            run_tokens(&config, texts, &files, stdin, encoding, vocab).await?;
+        }
+        Some(Commands::Config(args)) => {
+            run_config_command(config, args).await?;
         }
         Some(Commands::Models) => {
             list_models(config).await?;
         }
-        Some(Commands::Stats) => {
-            show_stats().await?;
+        Some(Commands::Mcp { command }) => match command {
+            McpCommand::List => list_mcp_servers(config).await?,
+        },
+        Some(Commands::Stats { since, json }) => {
+            show_stats(&config, since, json).await?;
         }
     }
 
@@ -103,26 +230,98 @@ async fn run_chat_mode(config: Config, command: Option<Commands>) -> anyhow::Res
         })
     );
 
-    let initial_message = match command {
+    let (initial_message, workspace, no_tools, images, rag, choices) = match command {
         Some(Commands::Chat {
-            message: Some(msg), ..
-        }) => Some(msg),
-        _ => None,
+            message,
+            workspace,
+            no_tools,
+            images,
+            rag,
+            choices,
+            ..
+        }) => (message, workspace, no_tools, images, rag, choices),
+        _ => (None, None, false, Vec::new(), None, 1),
     };
 
+    // Images wait here until the next user message is sent
+    let mut pending_images = vision::load_images(&images, config.max_image_bytes).await?;
+
     ui::clear_screen();
     ui::show_welcome();
 
-    let client = api::OpenAIClient::new(config.clone())?;
+    let client = provider::from_config(config.clone())?;
+    let mut tools = tools::ToolRegistry::new();
+
+    if config.tools.enabled && !no_tools {
+        let root = workspace
+            .or_else(|| config.tools.workspace.clone())
+            .map_or_else(std::env::current_dir, Ok)?;
+        let workspace = fs_tools::Workspace::new(root)?;
+        println!(
+            "{} {}",
+            "File tools enabled in".dimmed(),
+            workspace.root().display().to_string().dimmed()
+        );
+
+        // Without a terminal there is no one to ask, so writes are refused
+        let approval = if config.tools.auto_approve_writes {
+            fs_tools::WriteApproval::AllowAll
+        } else if io::stdin().is_terminal() {
+            fs_tools::WriteApproval::Prompt
+        } else {
+            fs_tools::WriteApproval::DenyAll
+        };
+        fs_tools::register(&mut tools, workspace, config.tools.max_read_bytes, approval);
+    }
+
+    let timeout = std::time::Duration::from_secs(config.timeout_seconds);
+    for (name, server) in &config.mcp_servers {
+        match mcp::register_server(&mut tools, name, server, timeout).await {
+            Ok(count) => println!("{}", format!("MCP server {name}: {count} tools").dimmed()),
+            Err(e) => ui::display_error(&format!("MCP server {name} unavailable: {e}")),
+        }
+    }
+
+    let tool_choice = config.tools.choice.as_deref().map(api::ToolChoice::from);
+    if let Some(api::ToolChoice::Function(name)) = &tool_choice {
+        if !tools.definitions().is_empty() && !tools.contains(name) {
+            anyhow::bail!("tools.choice names a tool that is not available: {name}");
+        }
+    }
+
+    let retriever = match rag {
+        Some(root) => Some(open_retriever(&config, &root).await?),
+        None => None,
+    };
+
+    let context = context::ContextManager::new(context_budget(&config), config.context.clone());
+    let mut options = ReplyOptions {
+        stream,
+        continuation: config.continuation.clone(),
+        choices,
+        reasoning: config.reasoning.display,
+        tool_choice,
+    };
+
     let mut session_manager = session::SessionManager::new();
     let session = session_manager.new_session(config.model.clone());
 
     // Add system message
-    session.add_message(api::Message::system(&config.system_prompt));
+    session.add_message(api::Message::system(&config.system_prompt).with_estimated_tokens());
 
     // Process initial message if provided
     if let Some(message) = initial_message {
-        process_chat_message(&client, session, &message, stream).await?;
+        let message = api::Message::user_with_images(message, std::mem::take(&mut pending_images));
+        process_chat_message(
+            client.as_ref(),
+            session,
+            &tools,
+            retriever.as_ref(),
+            &context,
+            message,
+            &options,
+        )
+        .await?;
     }
 
     // Main chat loop
@@ -164,6 +363,109 @@ async fn run_chat_mode(config: Config, command: Option<Commands>) -> anyhow::Res
                 println!("Model changed to: {}", model_name);
                 continue;
             }
+            "/set" => {
+                let sampling = config.sampling.overridden_by(&session.sampling);
+                println!("{} {}", "Sampling:".dimmed(), sampling);
+                continue;
+            }
+            _ if input.starts_with("/set ") || input.starts_with("/unset ") => {
+                let (command, args) = input.split_once(' ').unwrap_or_default();
+                let (name, value) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+                let result = if command == "/set" {
+                    session.sampling.set(name, value.trim())
+                } else {
+                    session.sampling.unset(name)
+                };
+                match result {
+                    Ok(()) => {
+                        let sampling = config.sampling.overridden_by(&session.sampling);
+                        println!("{} {}", "Sampling:".dimmed(), sampling);
+                    }
+                    Err(e) => ui::display_error(&e.to_string()),
+                }
+                continue;
+            }
+            "/pin" => {
+                match session.pin_last_turn() {
+                    0 => ui::display_error("Nothing to pin yet"),
+                    count => println!("{}", format!("Pinned {count} messages; they will survive compaction").dimmed()),
+                }
+                continue;
+            }
+            "/context" => {
+                let strategy = session.context_strategy.unwrap_or(context.strategy());
+                ui::display_context_status(context.budget(), session.history(), strategy);
+                continue;
+            }
+            _ if input.starts_with("/context ") => {
+                let name = input.strip_prefix("/context ").unwrap().trim();
+                match <context::ContextStrategy as clap::ValueEnum>::from_str(name, true) {
+                    Ok(strategy) => {
+                        session.context_strategy = Some(strategy);
+                        println!("{}", format!("Context strategy: {strategy}").dimmed());
+                    }
+                    Err(_) => ui::display_error(&format!(
+                        "Unknown context strategy '{name}'; use drop, summarize or off"
+                    )),
+                }
+                continue;
+            }
+            _ if input == "/compact" || input.starts_with("/compact ") => {
+                let name = input.strip_prefix("/compact").unwrap().trim();
+                let strategy = if name.is_empty() {
+                    Ok(session.context_strategy.unwrap_or(context.strategy()))
+                } else {
+                    <context::ContextStrategy as clap::ValueEnum>::from_str(name, true)
+                        .map_err(|_| format!("Unknown context strategy '{name}'; use drop or summarize"))
+                };
+                match strategy {
+                    Ok(strategy) => compact_history(client.as_ref(), session, &context, strategy).await,
+                    Err(e) => ui::display_error(&e),
+                }
+                continue;
+            }
+            "/thinking" => {
+                let reasoning = session
+                    .messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == api::Role::Assistant)
+                    .and_then(|m| m.reasoning_content.as_deref());
+                match reasoning {
+                    Some(reasoning) => ui::display_reasoning(reasoning, reasoning::ReasoningDisplay::Expanded),
+                    None => println!("{}", "The last reply came without reasoning".dimmed()),
+                }
+                continue;
+            }
+            _ if input.starts_with("/choices ") => {
+                let value = input.strip_prefix("/choices ").unwrap().trim();
+                match value.parse::<u32>() {
+                    Ok(choices) if (1..=cli::MAX_CHOICES).contains(&choices) => {
+                        options.choices = choices;
+                        println!("{}", format!("Replies per message: {choices}").dimmed());
+                    }
+                    _ => ui::display_error(&format!(
+                        "Invalid number of replies '{value}'; use 1 to {}",
+                        cli::MAX_CHOICES
+                    )),
+                }
+                continue;
+            }
+            _ if input.starts_with("/image ") => {
+                let path = input.strip_prefix("/image ").unwrap().trim();
+                match vision::load_image(std::path::Path::new(path), config.max_image_bytes).await {
+                    Ok(image) => {
+                        println!(
+                            "{}",
+                            format!("Attached {} ({}); it will be sent with your next message", image.path, image.mime_type)
+                                .dimmed()
+                        );
+                        pending_images.push(image);
+                    }
+                    Err(e) => ui::display_error(&e.to_string()),
+                }
+                continue;
+            }
             _ => {}
         }
 
@@ -171,169 +473,504 @@ async fn run_chat_mode(config: Config, command: Option<Commands>) -> anyhow::Res
             continue;
         }
 
-        process_chat_message(&client, session, input, stream).await?;
+        let message = api::Message::user_with_images(input, std::mem::take(&mut pending_images));
+        process_chat_message(
+            client.as_ref(),
+            session,
+            &tools,
+            retriever.as_ref(),
+            &context,
+            message,
+            &options,
+        )
+        .await?;
     }
 
     Ok(())
 }
 
+/// Index a directory for `chat --rag` and report what changed
+async fn open_retriever(config: &Config, root: &std::path::Path) -> anyhow::Result<rag::Retriever> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Cannot open {}", root.display()))?;
+    let options = rag::RagOptions {
+        model: config.embeddings.model.clone(),
+        batch_size: config.embeddings.batch_size,
+        chunk_chars: config.rag.chunk_chars,
+        overlap_chars: config.rag.overlap_chars,
+        max_file_bytes: config.rag.max_file_bytes,
+        top_k: config.rag.top_k,
+    };
+    let client = embeddings_client(config.clone())?;
+
+    let progress = ui::create_progress_bar("Indexing");
+    let result = rag::Retriever::open(client, &root, &rag::index_path(&root), options, &progress).await;
+    progress.finish_and_clear();
+
+    let (retriever, report) = result?;
+    println!(
+        "{}",
+        format!(
+            "Indexed {}: {} ({} chunks)",
+            root.display(),
+            report,
+            retriever.index().chunk_count()
+        )
+        .dimmed()
+    );
+    Ok(retriever)
+}
+
+/// How chat replies are requested and shown
+struct ReplyOptions {
+    /// Show replies as they are generated
+    stream: bool,
+    /// Continuing replies cut off by the token limit
+    continuation: config::ContinuationConfig,
+    /// Alternative replies to choose from; 1 shows a single reply
+    choices: u32,
+    /// How a reasoning model's thinking is shown
+    reasoning: reasoning::ReasoningDisplay,
+    /// Whether the model must call a tool before answering a message
+    tool_choice: Option<api::ToolChoice>,
+}
+
 /// Process a chat message
+///
+/// When the model asks for tools, each call is executed and its result sent
+/// back until the model answers in text (or `MAX_TOOL_ROUNDS` is reached).
+/// With a retriever, excerpts relevant to the message are sent along with it
+/// for this turn only and cited beneath the answer. Old turns are compacted
+/// when a request nears the context window, and a warning is shown before
+/// sending one that still will not fit.
 async fn process_chat_message(
-    client: &api::OpenAIClient,
+    client: &dyn provider::Provider,
     session: &mut session::Session,
-    input: &str,
-    stream: bool,
+    tools: &tools::ToolRegistry,
+    retriever: Option<&rag::Retriever>,
+    context: &context::ContextManager,
+    message: api::Message,
+    options: &ReplyOptions,
 ) -> anyhow::Result<()> {
-    use futures_util::StreamExt;
-    
-    // Add user message to session
-    session.add_message(api::Message::user(input));
+    let mut turn_start = session.messages.len();
 
-    if stream {
-        // Streaming mode with table support
-        use crate::streaming_buffer::StreamingBuffer;
-        
-        match client.complete_stream(session.history().to_vec()).await {
-            Ok(mut stream) => {
-                ui::display_streaming_header();
-                
-                let mut full_response = String::new();
-                let mut buffer = StreamingBuffer::new();
-                let mut needs_indent = true;  // Start with indent for first line
-                let mut table_spinner: Option<indicatif::ProgressBar> = None;
-                
-                while let Some(chunk_result) = stream.next().await {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            if !chunk.is_empty() {
-                                full_response.push_str(&chunk);
-                                
-                                // Process chunk through buffer for table/code block detection
-                                let (text_output, special_output, is_buffering) = buffer.process_chunk(&chunk);
-                                
-                                // Don't show spinner for code blocks, only for tables
-                                if is_buffering && buffer.is_buffering_table() && table_spinner.is_none() {
-                                    // Start spinner for table buffering only
-                                    if needs_indent {
-                                        println!(); // New line before spinner
-                                        needs_indent = false;
-                                    }
-                                    let spinner = ui::create_spinner("  Buffering table...");
-                                    table_spinner = Some(spinner);
-                                } else if !buffer.is_buffering_table() && table_spinner.is_some() {
-                                    // Stop spinner when table buffering is done
-                                    if let Some(spinner) = table_spinner.take() {
-                                        spinner.finish_and_clear();
-                                    }
-                                }
-                                
-                                // Display any immediate text
-                                if !text_output.is_empty() {
-                                    ui::display_streaming_chunk_smart(&text_output, needs_indent);
-                                    // Only reset needs_indent if we're at the start of a new line
-                                    needs_indent = false;  // We've printed something, no more indent until newline
-                                }
-                                
-                                // Display any completed special content (table or code block)
-                                if let Some(special) = special_output {
-                                    if needs_indent {
-                                        println!(); // New line before special content
-                                        needs_indent = false;
-                                    }
-                                    // Just print the formatted content directly
-                                    print!("{}", special);
-                                    std::io::Write::flush(&mut std::io::stdout()).unwrap();
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            // Clean up spinner if active
-                            if let Some(spinner) = table_spinner.take() {
-                                spinner.finish_and_clear();
-                            }
-                            ui::finish_streaming_display();
-                            ui::display_error(&e.to_string());
-                            session.messages.pop();
-                            return Ok(());
-                        }
-                    }
-                }
-                
-                // Clean up any remaining spinner
-                if let Some(spinner) = table_spinner.take() {
-                    spinner.finish_and_clear();
-                }
-                
-                // Flush any remaining content (could be formatted code block or table)
-                if let Some(remaining) = buffer.flush() {
-                    // The flush might return formatted content (code blocks/tables)
-                    // so we print it directly instead of passing through chunk display
-                    print!("{}", remaining);
-                    io::stdout().flush().unwrap();
+    let retrieval = match retriever {
+        Some(retriever) => {
+            let spinner = ui::create_spinner("Searching documents...");
+            let result = retriever.retrieve(&message.content).await;
+            spinner.finish_and_clear();
+
+            match result {
+                Ok(retrieval) => Some(retrieval).filter(|r| !r.is_empty()),
+                Err(e) => {
+                    ui::display_error(&format!("Document search failed: {e}"));
+                    None
                 }
-                
-                ui::finish_streaming_display();
-                
-                // Add assistant message to session
-                session.add_message(api::Message::assistant(&full_response));
+            }
+        }
+        None => None,
+    };
+
+    // Add user message to session
+    session.add_message(message.with_estimated_tokens());
+
+    let mut turn_usage = usage::Usage::default();
+
+    for round in 0..tools::MAX_TOOL_ROUNDS {
+        let build_request = |session: &session::Session, turn_start: usize| {
+            let mut messages = session.history().to_vec();
+            if let Some(retrieval) = &retrieval {
+                messages.insert(turn_start, retrieval.context_message());
+            }
+            let definitions = tools.definitions();
+            let offers_tools = !definitions.is_empty();
+            let request = provider::ChatRequest::new(messages)
+                .with_tools(definitions)
+                .with_sampling(session.sampling.clone());
+            match &options.tool_choice {
+                // Tool results are answered freely, or a forced call would repeat forever
+                Some(choice) if round == 0 && offers_tools => request.with_tool_choice(choice.clone()),
+                _ => request,
+            }
+        };
+
+        let mut request = build_request(session, turn_start);
+        let strategy = session.context_strategy.unwrap_or(context.strategy());
+        let turn_len = session.messages.len() - turn_start;
+        if let Some(compaction) = context.fit(client, &mut session.messages, &request, strategy).await {
+            // Compaction only touches turns before this one
+            turn_start = session.messages.len() - turn_len;
+            if let Some(usage) = &compaction.summary_usage {
+                session.record_usage(usage);
+            }
+            ui::display_compaction(&compaction);
+            request = build_request(session, turn_start);
+        }
+        warn_on_overflow(context.budget(), &request);
+
+        let reply = if options.choices > 1 {
+            choose_reply(client, request, options.choices).await
+        } else if options.stream {
+            stream_reply(client, request, &options.continuation, options.reasoning).await
+        } else {
+            complete_reply(client, request, &options.continuation, options.reasoning).await
+        };
+
+        let reply = match reply {
+            Ok((mut reply, usage)) => {
+                session.record_usage(&usage);
+                turn_usage += usage;
+                reply.tokens.get_or_insert(usage.completion_tokens);
+                reply
             }
             Err(e) => {
                 ui::display_error(&e.to_string());
-                // Remove the user message if the request failed
-                session.messages.pop();
+                // Remove the user message (and any tool exchange) if the request failed
+                session.messages.truncate(turn_start);
+                return Ok(());
+            }
+        };
+
+        let tool_calls = reply.tool_calls.clone();
+
+        // Add assistant message to session
+        session.add_message(reply);
+
+        if tool_calls.is_empty() {
+            ui::display_usage(&turn_usage, session.total_tokens);
+            if let Some(retrieval) = &retrieval {
+                ui::display_citations(&retrieval.citations);
             }
+            if let Some(info) = client.rate_limit().filter(|info| info.is_low()) {
+                ui::display_rate_limit_warning(&info);
+            }
+            return Ok(());
         }
-    } else {
-        // Non-streaming mode
-        // Show spinner
-        let spinner = ui::create_spinner("Thinking...");
 
-        // Get response
-        match client.complete(session.history().to_vec()).await {
-            Ok(response) => {
-                spinner.finish_and_clear();
+        for call in &tool_calls {
+            ui::display_tool_call(call);
+            let output = tools.execute(call).await;
+            session.add_message(api::Message::tool(&call.id, output).with_estimated_tokens());
+        }
+    }
 
-                // Add assistant message to session
-                session.add_message(api::Message::assistant(&response));
+    ui::display_error(&format!(
+        "Stopped after {} rounds of tool calls",
+        tools::MAX_TOOL_ROUNDS
+    ));
 
-                // Display response
-                ui::display_response(&response, OutputFormat::Text);
+    Ok(())
+}
+
+/// Compact the history of a session on request (the `/compact` command)
+async fn compact_history(
+    client: &dyn provider::Provider,
+    session: &mut session::Session,
+    context: &context::ContextManager,
+    strategy: context::ContextStrategy,
+) {
+    let spinner = ui::create_spinner("Compacting...");
+    let result = context.compact(client, &mut session.messages, strategy).await;
+    spinner.finish_and_clear();
+
+    match result {
+        Ok(Some(compaction)) => {
+            if let Some(usage) = &compaction.summary_usage {
+                session.record_usage(usage);
             }
+            ui::display_compaction(&compaction);
+        }
+        Ok(None) => println!("{}", "Nothing to compact".dimmed()),
+        Err(e) => ui::display_error(&format!("Compaction failed: {e}")),
+    }
+}
+
+/// Context budget of the configured model, or `None` when it is unknown
+///
+/// A vocabulary file that fails to load turns the check off rather than
+/// stopping the command.
+fn context_budget(config: &Config) -> Option<context::ContextBudget> {
+    context::ContextBudget::from_config(config).unwrap_or_else(|e| {
+        ui::display_error(&format!("Context window check disabled: {e}"));
+        None
+    })
+}
+
+/// Warn before sending a request that will not fit the context window
+fn warn_on_overflow(budget: Option<&context::ContextBudget>, request: &provider::ChatRequest) {
+    if let Some(overflow) = budget.and_then(|budget| budget.check(request)) {
+        ui::display_context_warning(&overflow);
+    }
+}
+
+/// Get a reply without streaming, showing a spinner while waiting
+async fn complete_reply(
+    client: &dyn provider::Provider,
+    request: provider::ChatRequest,
+    continuation: &config::ContinuationConfig,
+    display: reasoning::ReasoningDisplay,
+) -> error::Result<(api::Message, usage::Usage)> {
+    // Show spinner
+    let spinner = ui::create_spinner("Thinking...");
+
+    // Get response, continuing it if it is cut off
+    let completion = continuation::complete(client, request, continuation).await;
+    spinner.finish_and_clear();
+
+    let completion = completion?;
+
+    // Display reasoning and response
+    if let Some(reasoning) = &completion.message.reasoning_content {
+        ui::display_reasoning(reasoning, display);
+    }
+    if !completion.message.content.is_empty() {
+        ui::display_response(&completion.message.content, OutputFormat::Text);
+    }
+    if completion.message.truncated {
+        ui::display_truncated_warning(completion.usage.completion_tokens);
+    }
+
+    Ok((completion.message, completion.usage))
+}
+
+/// Get several alternative replies and keep the one the user picks
+///
+/// Alternatives are not streamed or continued; one cut off by the token
+/// limit is kept as truncated. Usage covers every alternative.
+async fn choose_reply(
+    client: &dyn provider::Provider,
+    request: provider::ChatRequest,
+    choices: u32,
+) -> error::Result<(api::Message, usage::Usage)> {
+    let spinner = ui::create_spinner(&format!("Thinking ({choices} replies)..."));
+    let alternatives = client.complete_choices(request, choices).await;
+    spinner.finish_and_clear();
+
+    let alternatives = alternatives?;
+    ui::display_alternatives(&alternatives.choices, OutputFormat::Text);
+    let index = match alternatives.choices.len() {
+        0 | 1 => 0,
+        count => ui::select_alternative(count)?,
+    };
+
+    let provider::Alternative { message, finish_reason } = alternatives
+        .choices
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| error::AppError::ApiError {
+            message: "No response choices available".to_string(),
+        })?;
+    let mut message = message.with_estimated_tokens();
+    message.truncated = finish_reason.as_deref() == Some("length") && message.tool_calls.is_empty();
+    Ok((message, alternatives.usage))
+}
+
+/// Stream a reply to the terminal as it arrives
+///
+/// Continuations of a cut-off reply are shown as part of the same reply, and
+/// reasoning streamed before it is shown above it and kept apart from it.
+async fn stream_reply(
+    client: &dyn provider::Provider,
+    request: provider::ChatRequest,
+    continuation: &config::ContinuationConfig,
+    display: reasoning::ReasoningDisplay,
+) -> error::Result<(api::Message, usage::Usage)> {
+    use futures_util::StreamExt;
+    use crate::provider::StreamEvent;
+    // Streaming mode with table support
+    use crate::streaming_buffer::StreamingBuffer;
+
+    let stream = continuation::stream(client, request, continuation).await?;
+    let (mut reasoning, mut stream) = show_leading_reasoning(stream, display).await;
+
+    ui::display_streaming_header();
+
+    let mut full_response = String::new();
+    let mut tool_calls = Vec::new();
+    let mut usage = usage::Usage::default();
+    let mut truncated = false;
+    let mut buffer = StreamingBuffer::new();
+    let mut needs_indent = true;  // Start with indent for first line
+    let mut table_spinner: Option<indicatif::ProgressBar> = None;
+
+    while let Some(event) = stream.next().await {
+        let event = match event {
+            Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                if continuation.enabled {
+                    truncated = true;
+                    continue;
+                }
+                Err(error::AppError::TokenLimitExceeded)
+            }
+            event => event,
+        };
+
+        let chunk = match event {
+            Ok(StreamEvent::Text(chunk)) => chunk,
+            Ok(StreamEvent::ToolCalls(calls)) => {
+                tool_calls.extend(calls);
+                continue;
+            }
+            Ok(StreamEvent::Usage(reported)) => {
+                usage += reported;
+                continue;
+            }
+            // Reasoning after the answer has started is kept but not shown
+            Ok(StreamEvent::Reasoning(chunk)) => {
+                reasoning.push_str(&chunk);
+                continue;
+            }
+            Ok(StreamEvent::Finish(_)) => continue,
             Err(e) => {
-                spinner.finish_and_clear();
-                ui::display_error(&e.to_string());
+                // Clean up spinner if active
+                if let Some(spinner) = table_spinner.take() {
+                    spinner.finish_and_clear();
+                }
+                ui::finish_streaming_display();
+                return Err(e);
+            }
+        };
+
+        if !chunk.is_empty() {
+            full_response.push_str(&chunk);
+
+            // Process chunk through buffer for table/code block detection
+            let (text_output, special_output, is_buffering) = buffer.process_chunk(&chunk);
+
+            // Don't show spinner for code blocks, only for tables
+            if is_buffering && buffer.is_buffering_table() && table_spinner.is_none() {
+                // Start spinner for table buffering only
+                if needs_indent {
+                    println!(); // New line before spinner
+                    needs_indent = false;
+                }
+                let spinner = ui::create_spinner("  Buffering table...");
+                table_spinner = Some(spinner);
+            } else if !buffer.is_buffering_table() && table_spinner.is_some() {
+                // Stop spinner when table buffering is done
+                if let Some(spinner) = table_spinner.take() {
+                    spinner.finish_and_clear();
+                }
+            }
+
+            // Display any immediate text
+            if !text_output.is_empty() {
+                ui::display_streaming_chunk_smart(&text_output, needs_indent);
+                // Only reset needs_indent if we're at the start of a new line
+                needs_indent = false;  // We've printed something, no more indent until newline
+            }
 
-                // Remove the user message if the request failed
-                session.messages.pop();
+            // Display any completed special content (table or code block)
+            if let Some(special) = special_output {
+                if needs_indent {
+                    println!(); // New line before special content
+                    needs_indent = false;
+                }
+                // Just print the formatted content directly
+                print!("{}", special);
+                std::io::Write::flush(&mut std::io::stdout()).unwrap();
             }
         }
     }
 
-    Ok(())
+    // Clean up any remaining spinner
+    if let Some(spinner) = table_spinner.take() {
+        spinner.finish_and_clear();
+    }
+
+    // Flush any remaining content (could be formatted code block or table)
+    if let Some(remaining) = buffer.flush() {
+        // The flush might return formatted content (code blocks/tables)
+        // so we print it directly instead of passing through chunk display
+        print!("{}", remaining);
+        io::stdout().flush().unwrap();
+    }
+
+    ui::finish_streaming_display();
+    if truncated {
+        ui::display_truncated_warning(usage.completion_tokens);
+    }
+
+    let mut reply = api::Message::assistant_with_tool_calls(full_response, tool_calls);
+    reply.truncated = truncated;
+    reply.reasoning_content = Some(reasoning).filter(|r| !r.is_empty());
+    Ok((reply, usage))
+}
+
+/// Show the reasoning at the start of a stream while it arrives
+///
+/// Returns the reasoning and the stream from the first other event on.
+async fn show_leading_reasoning<'a>(
+    mut events: futures_util::stream::BoxStream<'a, error::Result<provider::StreamEvent>>,
+    display: reasoning::ReasoningDisplay,
+) -> (String, futures_util::stream::BoxStream<'a, error::Result<provider::StreamEvent>>) {
+    use futures_util::StreamExt;
+
+    let mut view = ui::ReasoningView::new(display);
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(provider::StreamEvent::Reasoning(chunk)) => view.push(&chunk),
+            event => {
+                let rest = futures_util::stream::once(async { event }).chain(events);
+                return (view.finish(), Box::pin(rest));
+            }
+        }
+    }
+    (view.finish(), events)
 }
 
 /// Run single query mode
 async fn run_query_mode(
     config: Config,
     message: String,
+    images: &[std::path::PathBuf],
     format: OutputFormat,
     stream: bool,
+    choices: u32,
 ) -> anyhow::Result<()> {
     use futures_util::StreamExt;
     
-    let client = api::OpenAIClient::new(config.clone())?;
+    let client = provider::from_config(config.clone())?;
+    let messages = vec![
+        api::Message::system(&config.system_prompt),
+        api::Message::user_with_images(message, vision::load_images(images, config.max_image_bytes).await?),
+    ];
+    warn_on_overflow(context_budget(&config).as_ref(), &provider::ChatRequest::new(messages.clone()));
 
-    if stream {
+    if choices > 1 {
+        // Alternatives are printed together once all have arrived
+        let spinner = ui::create_spinner(&format!("Processing query ({choices} replies)..."));
+        let result = client.complete_choices(provider::ChatRequest::new(messages), choices).await;
+        spinner.finish_and_clear();
+
+        match result {
+            Ok(alternatives) => ui::display_alternatives(&alternatives.choices, format),
+            Err(e) => ui::display_error(&e.to_string()),
+        }
+    } else if stream {
         // Streaming mode with table support
+        use crate::provider::StreamEvent;
         use crate::streaming_buffer::StreamingBuffer;
         
-        let messages = vec![
-            api::Message::system(&config.system_prompt),
-            api::Message::user(&message),
-        ];
-        
-        match client.complete_stream(messages).await {
+        let events = continuation::stream(
+            client.as_ref(),
+            provider::ChatRequest::new(messages),
+            &config.continuation,
+        )
+        .await;
+        let events = match events {
+            Ok(events) if matches!(format, OutputFormat::Text) => {
+                Ok(show_leading_reasoning(events, config.reasoning.display).await.1)
+            }
+            events => events,
+        };
+        match events {
             Ok(mut stream) => {
+                // A reply still cut off after continuing is kept and marked truncated
+                let mut usage = usage::Usage::default();
+                let mut truncated = false;
+
                 if matches!(format, OutputFormat::Text) {
                     ui::display_streaming_header();
                     
@@ -341,7 +978,23 @@ async fn run_query_mode(
                     let mut needs_indent = true;  // Start with indent for first line
                     let mut table_spinner: Option<indicatif::ProgressBar> = None;
                     
-                    while let Some(chunk_result) = stream.next().await {
+                    while let Some(event) = stream.next().await {
+                        let chunk_result = match event {
+                            Ok(StreamEvent::Text(chunk)) => Ok(chunk),
+                            Ok(StreamEvent::Usage(reported)) => {
+                                usage += reported;
+                                continue;
+                            }
+                            Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                                if config.continuation.enabled {
+                                    truncated = true;
+                                    continue;
+                                }
+                                Err(error::AppError::TokenLimitExceeded)
+                            }
+                            Ok(_) => continue,
+                            Err(e) => Err(e),
+                        };
                         match chunk_result {
                             Ok(chunk) => {
                                 if !chunk.is_empty() {
@@ -404,15 +1057,27 @@ async fn run_query_mode(
                     }
                     
                     ui::finish_streaming_display();
+                    if truncated {
+                        ui::display_truncated_warning(usage.completion_tokens);
+                    }
                 } else {
                     // For non-text formats, collect the full response first
                     let mut full_response = String::new();
                     
-                    while let Some(chunk_result) = stream.next().await {
-                        match chunk_result {
-                            Ok(chunk) => {
+                    while let Some(event) = stream.next().await {
+                        match event {
+                            Ok(StreamEvent::Text(chunk)) => {
                                 full_response.push_str(&chunk);
                             }
+                            Ok(StreamEvent::Usage(reported)) => usage += reported,
+                            Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                                if !config.continuation.enabled {
+                                    ui::display_error(&error::AppError::TokenLimitExceeded.to_string());
+                                    return Ok(());
+                                }
+                                truncated = true;
+                            }
+                            Ok(_) => {}
                             Err(e) => {
                                 ui::display_error(&e.to_string());
                                 return Ok(());
@@ -421,6 +1086,9 @@ async fn run_query_mode(
                     }
                     
                     ui::display_response(&full_response, format);
+                    if truncated {
+                        ui::display_truncated_warning(usage.completion_tokens);
+                    }
                 }
             }
             Err(e) => {
@@ -431,10 +1099,19 @@ async fn run_query_mode(
         // Non-streaming mode
         let spinner = ui::create_spinner("Processing query...");
 
-        match client.chat(&message).await {
-            Ok(response) => {
+        let request = provider::ChatRequest::new(messages);
+        match continuation::complete(client.as_ref(), request, &config.continuation).await {
+            Ok(completion) => {
                 spinner.finish_and_clear();
-                ui::display_response(&response, format);
+                if let Some(reasoning) = completion.message.reasoning_content.as_deref() {
+                    if !matches!(format, OutputFormat::Json) {
+                        ui::display_reasoning(reasoning, config.reasoning.display);
+                    }
+                }
+                ui::display_response(&completion.message.content, format);
+                if completion.message.truncated {
+                    ui::display_truncated_warning(completion.usage.completion_tokens);
+                }
             }
             Err(e) => {
                 spinner.finish_and_clear();
@@ -446,16 +1123,381 @@ async fn run_query_mode(
     Ok(())
 }
 
-/// Run configuration command
-async fn run_config_command(
-    mut config: Config,
-    show: bool,
-    api_key: Option<String>,
-    model: Option<String>,
-    system_prompt: Option<String>,
-    base_url: Option<String>,
-    api_path: Option<String>,
+/// Run a single query whose reply must match a JSON schema, printing the bare JSON
+async fn run_structured_query(
+    config: Config,
+    message: String,
+    images: &[std::path::PathBuf],
+    schema_path: &std::path::Path,
+    max_repairs: usize,
+) -> anyhow::Result<()> {
+    let schema = structured::Schema::load(schema_path)
+        .await
+        .with_context(|| format!("Failed to load schema {}", schema_path.display()))?;
+
+    let client = provider::from_config(config.clone())?;
+    let messages = vec![
+        api::Message::system(&config.system_prompt),
+        api::Message::user_with_images(message, vision::load_images(images, config.max_image_bytes).await?),
+    ];
+
+    let spinner = ui::create_spinner("Processing query...");
+    let result = structured::complete_json(client.as_ref(), messages, &schema, max_repairs).await;
+    spinner.finish_and_clear();
+
+    // Errors go to stderr with a failing exit code so pipelines notice
+    println!("{}", serde_json::to_string_pretty(&result?)?);
+    Ok(())
+}
+
+/// Run a single query and show how confident the model was in each token
+///
+/// The reply is not streamed or continued.
+async fn run_logprobs_query(
+    config: Config,
+    message: String,
+    images: &[std::path::PathBuf],
+    format: OutputFormat,
+    top_logprobs: u8,
+) -> anyhow::Result<()> {
+    let client = openai_client(config.clone(), "Logprobs")?;
+    let messages = vec![
+        api::Message::system(&config.system_prompt),
+        api::Message::user_with_images(message, vision::load_images(images, config.max_image_bytes).await?),
+    ];
+    let request = provider::ChatRequest::new(messages);
+    warn_on_overflow(context_budget(&config).as_ref(), &request);
+
+    let spinner = ui::create_spinner("Processing query...");
+    let result = client.complete_with_logprobs(request, top_logprobs).await;
+    spinner.finish_and_clear();
+
+    let (completion, tokens) = result?;
+    if tokens.is_empty() {
+        ui::display_error("The server sent no logprobs; showing the reply as is");
+        ui::display_response(&completion.message.content, format);
+    } else {
+        ui::display_logprobs(&completion.message.content, &tokens, format);
+    }
+    if completion.finish_reason.as_deref() == Some("length") {
+        ui::display_error("The reply was cut off by the token limit");
+    }
+    Ok(())
+}
+
+/// Create a client for the OpenAI-only endpoints (audio, images, embeddings)
+fn openai_client(config: Config, feature: &str) -> anyhow::Result<api::OpenAIClient> {
+    if !matches!(config.provider, config::ProviderKind::OpenAI | config::ProviderKind::Azure) {
+        anyhow::bail!(
+            "{feature} needs an OpenAI-compatible provider, but the configured provider is {:?}",
+            config.provider
+        );
+    }
+
+    Ok(api::OpenAIClient::new(config)?)
+}
+
+/// Transcribe an audio file and print the transcript
+async fn run_transcribe(
+    config: Config,
+    file: &std::path::Path,
+    options: &audio::TranscriptionOptions,
 ) -> anyhow::Result<()> {
+    let client = openai_client(config, "Transcription")?;
+
+    let spinner = ui::create_spinner("Transcribing...");
+    let result = audio::transcribe(&client, file, options).await;
+    spinner.finish_and_clear();
+
+    println!("{}", result?);
+    Ok(())
+}
+
+/// Speak text (or standard input when `text` is `None`) into an audio file
+async fn run_speak(
+    config: Config,
+    text: Option<String>,
+    output: &std::path::Path,
+    options: &audio::SpeechOptions,
+) -> anyhow::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let text = match text {
+        Some(text) => text,
+        None => {
+            let mut text = String::new();
+            tokio::io::stdin().read_to_string(&mut text).await?;
+            text
+        }
+    };
+    if text.trim().is_empty() {
+        anyhow::bail!("Nothing to speak: the text is empty");
+    }
+
+    let client = openai_client(config, "Text-to-speech")?;
+
+    let progress = ui::create_progress_bar("Speaking");
+    let result = audio::speak(&client, &text, output, options, &progress).await;
+    progress.finish_and_clear();
+
+    let bytes = result?;
+    println!("Saved {} ({})", output.display(), indicatif::HumanBytes(bytes));
+    Ok(())
+}
+
+/// Generate images and print where they were saved
+async fn run_image_generation(
+    config: Config,
+    prompt: &str,
+    options: &images::ImageOptions,
+    output_dir: &std::path::Path,
+) -> anyhow::Result<()> {
+    let client = openai_client(config, "Image generation")?;
+
+    let spinner = ui::create_spinner("Generating...");
+    let result = images::generate(&client, prompt, options, output_dir).await;
+    spinner.finish_and_clear();
+
+    for image in result? {
+        println!("Saved {}", image.path.display());
+        if let Some(revised_prompt) = image.revised_prompt {
+            println!("  {} {}", "Revised prompt:".dimmed(), revised_prompt);
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a client for the embeddings endpoint, which Ollama also serves
+fn embeddings_client(config: Config) -> anyhow::Result<api::OpenAIClient> {
+    if matches!(config.provider, config::ProviderKind::Ollama) {
+        return Ok(api::OpenAIClient::new(config)?);
+    }
+
+    openai_client(config, "Embeddings")
+}
+
+/// Collect the inputs to embed as (source, text) pairs
+async fn read_embed_inputs(input: &cli::EmbedInput) -> anyhow::Result<Vec<(String, String)>> {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut inputs: Vec<(String, String)> = input
+        .texts
+        .iter()
+        .map(|text| ("argument".to_string(), text.clone()))
+        .collect();
+
+    for path in &input.files {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        inputs.push((path.display().to_string(), text));
+    }
+
+    if input.stdin {
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if !line.trim().is_empty() {
+                inputs.push(("stdin".to_string(), line));
+            }
+        }
+    }
+
+    inputs.retain(|(_, text)| !text.trim().is_empty());
+    if inputs.is_empty() {
+        anyhow::bail!("Nothing to embed: the inputs are empty");
+    }
+
+    Ok(inputs)
+}
+
+/// Embed inputs and print the vectors
+async fn run_embed(
+    config: Config,
+    input: &cli::EmbedInput,
+    format: cli::EmbeddingFormat,
+    model: &str,
+) -> anyhow::Result<()> {
+    let texts: Vec<String> = read_embed_inputs(input)
+        .await?
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect();
+    let batch_size = config.embeddings.batch_size;
+    let client = embeddings_client(config)?;
+
+    let spinner = ui::create_spinner("Embedding...");
+    let result = embeddings::embed(&client, model, &texts, batch_size).await;
+    spinner.finish_and_clear();
+    let vectors = result?;
+
+    match format {
+        cli::EmbeddingFormat::Json => println!("{}", serde_json::to_string(&vectors)?),
+        cli::EmbeddingFormat::Raw => {
+            let mut stdout = io::stdout().lock();
+            for value in vectors.iter().flatten() {
+                stdout.write_all(&value.to_le_bytes())?;
+            }
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Embed inputs and add them to a named index
+async fn run_index_add(config: Config, input: &cli::EmbedInput, name: &str) -> anyhow::Result<()> {
+    let path = vector_index::VectorIndex::default_path(name);
+    let mut index = vector_index::VectorIndex::load(&path)
+        .await?
+        .unwrap_or_else(|| vector_index::VectorIndex::new(config.embeddings.model.clone()));
+
+    // Texts already in the index are not embedded again
+    let inputs: Vec<(String, String)> = read_embed_inputs(input)
+        .await?
+        .into_iter()
+        .filter(|(source, text)| !index.contains(source, text))
+        .collect();
+    if inputs.is_empty() {
+        println!("Index '{name}' already contains every input");
+        return Ok(());
+    }
+
+    let texts: Vec<String> = inputs.iter().map(|(_, text)| text.clone()).collect();
+    let batch_size = config.embeddings.batch_size;
+    let client = embeddings_client(config)?;
+
+    let spinner = ui::create_spinner("Embedding...");
+    let result = embeddings::embed(&client, &index.model, &texts, batch_size).await;
+    spinner.finish_and_clear();
+
+    let mut added = 0;
+    for ((source, text), embedding) in inputs.into_iter().zip(result?) {
+        if index.add(vector_index::IndexEntry {
+            source,
+            text,
+            embedding,
+        })? {
+            added += 1;
+        }
+    }
+    index.save(&path).await?;
+
+    println!(
+        "Added {added} entries to '{name}' ({} total, {})",
+        index.entries.len(),
+        index.model
+    );
+    Ok(())
+}
+
+/// Print the entries of a named index most similar to a query
+async fn run_index_search(config: Config, query: &str, k: usize, name: &str) -> anyhow::Result<()> {
+    let path = vector_index::VectorIndex::default_path(name);
+    let Some(index) = vector_index::VectorIndex::load(&path).await? else {
+        anyhow::bail!("Index '{name}' does not exist; create it with `llm-cli index add`");
+    };
+
+    let client = embeddings_client(config)?;
+
+    let spinner = ui::create_spinner("Searching...");
+    let result = embeddings::embed(&client, &index.model, &[query.to_string()], 1).await;
+    spinner.finish_and_clear();
+    let query_embedding = result?.pop().unwrap_or_default();
+
+    for hit in index.search(&query_embedding, k)? {
+        let snippet: String = hit
+            .entry
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .take(160)
+            .collect();
+        println!("{:.3}  {}", hit.score, hit.entry.source.bold());
+        println!("       {}", snippet.dimmed());
+    }
+
+    Ok(())
+}
+
+/// Count the tokens in texts, files or standard input
+///
+/// A single input prints the bare count; several print one line each and a total.
+async fn run_tokens(
+    config: &Config,
+    texts: Vec<String>,
+    files: &[std::path::PathBuf],
+    stdin: bool,
+    encoding: Option<tokenizer::Encoding>,
+    vocab: Option<std::path::PathBuf>,
+) -> anyhow::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let tokenizer = tokenizer::Tokenizer::for_model(
+        &config.model,
+        encoding.or(config.tokenizer.encoding),
+        vocab.as_deref().or(config.tokenizer.vocab.as_deref()),
+    )?;
+
+    let mut inputs: Vec<(String, String)> = texts
+        .into_iter()
+        .map(|text| ("argument".to_string(), text))
+        .collect();
+
+    for path in files {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        inputs.push((path.display().to_string(), text));
+    }
+
+    if stdin {
+        let mut text = String::new();
+        tokio::io::stdin().read_to_string(&mut text).await?;
+        inputs.push(("stdin".to_string(), text));
+    }
+
+    let counts: Vec<usize> = inputs.iter().map(|(_, text)| tokenizer.count(text)).collect();
+    let total: usize = counts.iter().sum();
+
+    if let [count] = counts[..] {
+        println!("{count}");
+    } else {
+        for ((source, _), count) in inputs.iter().zip(&counts) {
+            println!("{count:>10}  {source}");
+        }
+        println!("{total:>10}  {}", "total".bold());
+    }
+
+    let window = config
+        .context_window
+        .or_else(|| context::context_window(&config.model))
+        .map_or(String::new(), |window| {
+            let share = total as f64 * 100.0 / f64::from(window);
+            format!(" ({share:.1}% of the {window}-token context window of {})", config.model)
+        });
+    eprintln!("{}", format!("Counted with {}{}", tokenizer.name(), window).dimmed());
+
+    Ok(())
+}
+
+/// Run configuration command
+async fn run_config_command(mut config: Config, args: cli::ConfigArgs) -> anyhow::Result<()> {
+    let cli::ConfigArgs {
+        show,
+        api_key,
+        model,
+        system_prompt,
+        base_url,
+        api_path,
+        provider,
+        azure_resource,
+        azure_api_version,
+        azure_deployment,
+    } = args;
+
     if show {
         println!("{:#?}", config);
         println!("Full API URL: {}", config.api_url());
@@ -494,6 +1536,30 @@ async fn run_config_command(
         println!("API path updated");
     }
 
+    if let Some(provider) = provider {
+        config.provider = provider;
+        modified = true;
+        println!("Provider set to {}", provider);
+    }
+
+    if let Some(resource) = azure_resource {
+        config.azure.resource = Some(resource);
+        modified = true;
+        println!("Azure resource updated");
+    }
+
+    if let Some(version) = azure_api_version {
+        config.azure.api_version = version;
+        modified = true;
+        println!("Azure API version updated");
+    }
+
+    for (model, deployment) in azure_deployment {
+        println!("Azure deployment for {} set to {}", model, deployment);
+        config.azure.deployments.insert(model, deployment);
+        modified = true;
+    }
+
     if modified {
         config.save().await?;
         println!("Configuration saved");
@@ -506,9 +1572,10 @@ async fn run_config_command(
 
 /// List available models
 async fn list_models(config: Config) -> anyhow::Result<()> {
-    println!("Fetching available models from {}...\n", config.base_url);
-    
-    let client = api::OpenAIClient::new(config)?;
+    let base_url = config.base_url.clone();
+    let client = provider::from_config(config)?;
+
+    println!("Fetching available models from {} ({})...\n", base_url, client.name());
     
     match client.list_models().await {
         Ok(models) => {
@@ -537,10 +1604,57 @@ async fn list_models(config: Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Connect to each configured MCP server and list its tools
+async fn list_mcp_servers(config: Config) -> anyhow::Result<()> {
+    if config.mcp_servers.is_empty() {
+        println!("No MCP servers configured. Add them under [mcp_servers.<name>] in the config file.");
+        return Ok(());
+    }
+
+    let timeout = std::time::Duration::from_secs(config.timeout_seconds);
+    for (name, server) in &config.mcp_servers {
+        println!("{} ({})", name.bold(), server.command);
+
+        let tools = match mcp::McpClient::connect(name, server, timeout).await {
+            Ok(client) => client.list_tools().await,
+            Err(e) => Err(e),
+        };
+        match tools {
+            Ok(tools) if tools.is_empty() => println!("  No tools"),
+            Ok(tools) => {
+                for tool in tools {
+                    println!("  - {}: {}", tool.name, tool.description);
+                }
+            }
+            Err(e) => println!("  {} {}", "Error:".red(), e),
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
 /// Show usage statistics
-async fn show_stats() -> anyhow::Result<()> {
-    println!("Token usage statistics are tracked per session in chat mode.");
-    println!("Use the 'save' command in chat mode to persist session data.");
+async fn show_stats(
+    config: &Config,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let path = ledger::ledger_path(&config.usage);
+    let entries = ledger::read(&path).await?;
+    let report = ledger::UsageReport::new(&entries, since, &config.usage);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.total.requests == 0 {
+        println!("No requests recorded in {}", path.display());
+        if !config.usage.ledger {
+            println!("{}", "The usage ledger is disabled (usage.ledger = false).".dimmed());
+        }
+    } else {
+        ui::display_usage_report(&report);
+    }
+
     Ok(())
 }
 
@@ -551,19 +1665,35 @@ fn display_history(session: &session::Session) {
 
     for message in session.history() {
         let role = match message.role {
-            api::Role::System => continue, // Skip system messages in display
+            api::Role::System | api::Role::Developer => continue, // Skip system messages in display
             api::Role::User => "You".green(),
             api::Role::Assistant => "Assistant".blue(),
+            api::Role::Tool => "Tool".yellow(),
         };
 
-        println!("\n{}:", role.bold());
+        let mut notes: Vec<String> = message.tokens.map(|tokens| format!("{tokens} tokens")).into_iter().collect();
+        if message.truncated {
+            notes.push("truncated".to_string());
+        }
+        if message.reasoning_content.is_some() {
+            notes.push("with reasoning".to_string());
+        }
+        if notes.is_empty() {
+            println!("\n{}:", role.bold());
+        } else {
+            println!("\n{}: {}", role.bold(), format!("({})", notes.join(", ")).dimmed());
+        }
         println!(); // Add space between role and content
         // Use the new markdown processing for better table display
         let processed = ui::process_markdown_content(&message.content);
         println!("{}", processed);
+        for image in &message.images {
+            println!("  {}", format!("[image: {}]", image.path).dimmed());
+        }
     }
 
     println!("\n{}", "─".repeat(60));
+    println!("{}", format!("Total tokens used: {}", session.total_tokens).dimmed());
 }
 
 /// Save the current session
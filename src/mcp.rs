@@ -0,0 +1,336 @@
+//! Model Context Protocol (MCP) client for tool servers spoken to over stdio
+//!
+//! Each configured server is spawned as a child process. Messages are
+//! newline-delimited JSON-RPC 2.0 on the child's stdin and stdout.
+
+use crate::api::ToolDefinition;
+use crate::config::McpServerConfig;
+use crate::error::{AppError, Result};
+use crate::tools::{Tool, ToolRegistry};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Protocol revision sent in `initialize`
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// A tool advertised by an MCP server
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpToolInfo {
+    /// Name the tool is called by
+    pub name: String,
+    /// What the tool does, shown to the model
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the tool arguments
+    #[serde(rename = "inputSchema", default = "empty_schema")]
+    pub input_schema: serde_json::Value,
+}
+
+fn empty_schema() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+/// The child's pipes, used by one request at a time
+struct Connection {
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+/// A running MCP server
+pub struct McpClient {
+    name: String,
+    connection: Mutex<Connection>,
+    timeout: Duration,
+    /// Kept so the server is killed when the client is dropped
+    _child: Child,
+}
+
+impl McpClient {
+    /// Spawn a server and perform the `initialize` handshake
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server cannot be spawned or the handshake fails.
+    pub async fn connect(name: &str, config: &McpServerConfig, timeout: Duration) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                AppError::ToolError(format!(
+                    "Failed to start MCP server {name} ({}): {e}",
+                    config.command
+                ))
+            })?;
+
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(AppError::ToolError(format!(
+                "MCP server {name} has no stdio"
+            )));
+        };
+
+        let client = Self {
+            name: name.to_string(),
+            connection: Mutex::new(Connection {
+                stdin,
+                stdout: BufReader::new(stdout),
+                next_id: 1,
+            }),
+            timeout,
+            _child: child,
+        };
+
+        client
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "llm-cli", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await?;
+        client.notify("notifications/initialized").await?;
+
+        Ok(client)
+    }
+
+    /// List every tool the server offers, following pagination cursors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails or a page cannot be parsed.
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        #[derive(Deserialize)]
+        struct ToolsPage {
+            tools: Vec<McpToolInfo>,
+            #[serde(rename = "nextCursor")]
+            next_cursor: Option<String>,
+        }
+
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.as_ref().map_or_else(
+                || serde_json::json!({}),
+                |cursor| serde_json::json!({"cursor": cursor}),
+            );
+            let page: ToolsPage =
+                serde_json::from_value(self.request("tools/list", params).await?)?;
+
+            tools.extend(page.tools);
+            match page.next_cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    /// Call a tool and return its text output
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the call fails or the tool reports an error.
+    pub async fn call_tool(&self, name: &str, arguments: serde_json::Value) -> Result<String> {
+        #[derive(Deserialize)]
+        struct CallResult {
+            #[serde(default)]
+            content: Vec<ContentItem>,
+            #[serde(rename = "isError", default)]
+            is_error: bool,
+        }
+
+        #[derive(Deserialize)]
+        struct ContentItem {
+            #[serde(rename = "type")]
+            kind: String,
+            text: Option<String>,
+        }
+
+        let result: CallResult = serde_json::from_value(
+            self.request(
+                "tools/call",
+                serde_json::json!({"name": name, "arguments": arguments}),
+            )
+            .await?,
+        )?;
+
+        let text = result
+            .content
+            .into_iter()
+            .map(|item| {
+                item.text
+                    .unwrap_or_else(|| format!("[{} content]", item.kind))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if result.is_error {
+            return Err(AppError::ToolError(text));
+        }
+
+        Ok(text)
+    }
+
+    /// Send a request and wait for the response with the same ID
+    async fn request(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        let mut connection = self.connection.lock().await;
+        let id = connection.next_id;
+        connection.next_id += 1;
+
+        debug!("MCP {} -> {} (id {})", self.name, method, id);
+
+        let message =
+            serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        connection.send(&message).await?;
+
+        let response = tokio::time::timeout(self.timeout, connection.wait_for(id))
+            .await
+            .map_err(|_| {
+                AppError::ToolError(format!("MCP server {} timed out on {method}", self.name))
+            })??;
+        drop(connection);
+
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(AppError::ToolError(format!(
+                "MCP server {} failed {method}: {message}",
+                self.name
+            )));
+        }
+
+        Ok(response.get("result").cloned().unwrap_or_default())
+    }
+
+    /// Send a notification, which has no response
+    async fn notify(&self, method: &str) -> Result<()> {
+        let message = serde_json::json!({"jsonrpc": "2.0", "method": method});
+        self.connection.lock().await.send(&message).await
+    }
+}
+
+impl Connection {
+    /// Write one JSON-RPC message as a line
+    async fn send(&mut self, message: &serde_json::Value) -> Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.stdin.write_all(&line).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Read messages until the response to `id` arrives
+    ///
+    /// Notifications are skipped. Requests from the server are answered so it
+    /// is never left waiting: `ping` succeeds, anything else is unsupported.
+    async fn wait_for(&mut self, id: u64) -> Result<serde_json::Value> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line).await? == 0 {
+                return Err(AppError::ToolError(
+                    "MCP server closed the connection".to_string(),
+                ));
+            }
+
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+                warn!("Ignoring non-JSON output from MCP server: {}", line.trim());
+                continue;
+            };
+
+            let is_response = message.get("result").is_some() || message.get("error").is_some();
+            match (message.get("id"), message.get("method")) {
+                (Some(message_id), None) if is_response && message_id.as_u64() == Some(id) => {
+                    return Ok(message);
+                }
+                (Some(request_id), Some(method)) => {
+                    let reply = if method == "ping" {
+                        serde_json::json!({"jsonrpc": "2.0", "id": request_id, "result": {}})
+                    } else {
+                        serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": {"code": -32601, "message": "Method not found"}
+                        })
+                    };
+                    self.send(&reply).await?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A tool provided by an MCP server
+pub struct McpTool {
+    client: Arc<McpClient>,
+    info: McpToolInfo,
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            &self.info.name,
+            &self.info.description,
+            self.info.input_schema.clone(),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> Result<String> {
+        self.client.call_tool(&self.info.name, arguments).await
+    }
+}
+
+/// Connect to a server and register its tools
+///
+/// Tools whose names are already taken (by built-ins or an earlier server)
+/// are skipped. Returns the number of tools registered.
+///
+/// # Errors
+///
+/// Returns an error if the server cannot be started or listed.
+pub async fn register_server(
+    registry: &mut ToolRegistry,
+    name: &str,
+    config: &McpServerConfig,
+    timeout: Duration,
+) -> Result<usize> {
+    let client = Arc::new(McpClient::connect(name, config, timeout).await?);
+    let mut registered = 0;
+
+    for info in client.list_tools().await? {
+        if registry.contains(&info.name) {
+            warn!(
+                "Skipping MCP tool {} from {}: name already in use",
+                info.name, name
+            );
+            continue;
+        }
+
+        registry.register(McpTool {
+            client: Arc::clone(&client),
+            info,
+        });
+        registered += 1;
+    }
+
+    Ok(registered)
+}
@@ -0,0 +1,491 @@
+//! Native Ollama API client implementation
+
+use crate::api::{assemble_events, Message, Role, ToolCall, ToolDefinition};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::ledger::Ledger;
+use crate::provider::{self, Completion, EventStream, Provider, StreamEvent};
+use crate::retry::{RateLimitInfo, RateLimitTracker, RetryPolicy};
+use crate::sampling::SamplingParams;
+use crate::usage::{self, Usage};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument};
+
+/// A message in the Ollama chat format
+#[derive(Debug, Serialize)]
+struct OllamaMessage {
+    role: &'static str,
+    content: String,
+    /// Base64-encoded images, without a data URL prefix
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+/// A tool call in the Ollama format, with arguments as a JSON object and no ID
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+/// Ollama `/api/chat` request
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolDefinition>,
+    /// JSON schema the reply must follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    options: serde_json::Map<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<serde_json::Value>,
+}
+
+/// Ollama `/api/generate` request, used in raw mode
+#[derive(Debug, Serialize)]
+struct GenerateRequest {
+    model: String,
+    prompt: String,
+    raw: bool,
+    stream: bool,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    options: serde_json::Map<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<serde_json::Value>,
+}
+
+/// A single response object, either the whole reply or one NDJSON line of a stream
+///
+/// `/api/chat` puts text in `message.content`, `/api/generate` in `response`.
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    message: Option<ResponseMessage>,
+    response: Option<String>,
+    #[serde(default)]
+    done: bool,
+    done_reason: Option<String>,
+    error: Option<String>,
+    /// Tokens in the prompt, sent with the final object
+    prompt_eval_count: Option<u32>,
+    /// Tokens generated, sent with the final object
+    eval_count: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+impl ChatResponse {
+    /// Token counts of a final response object
+    fn usage(&self) -> Option<Usage> {
+        if !self.done || (self.prompt_eval_count.is_none() && self.eval_count.is_none()) {
+            return None;
+        }
+
+        Some(Usage::new(
+            self.prompt_eval_count.unwrap_or(0),
+            self.eval_count.unwrap_or(0),
+        ))
+    }
+
+    /// Split a response object into its text and tool calls, surfacing errors
+    fn into_parts(self) -> Result<(String, Vec<ToolCall>, Option<String>)> {
+        if let Some(message) = self.error {
+            return Err(AppError::ApiError { message });
+        }
+
+        let (content, tool_calls) = match self.message {
+            Some(message) => (message.content, message.tool_calls),
+            None => (self.response.unwrap_or_default(), Vec::new()),
+        };
+
+        // Ollama does not assign tool call IDs, and numbering them per line
+        // would repeat IDs across stream chunks and tool rounds
+        let tool_calls = tool_calls
+            .into_iter()
+            .map(|call| {
+                ToolCall::function(
+                    format!("call_{}", uuid::Uuid::new_v4().simple()),
+                    call.function.name,
+                    call.function.arguments.to_string(),
+                )
+            })
+            .collect();
+
+        let finish_reason = self
+            .done
+            .then(|| self.done_reason.unwrap_or_else(|| "stop".to_string()));
+
+        Ok((content, tool_calls, finish_reason))
+    }
+
+    /// Turn one NDJSON line of a stream into chat events
+    fn into_events(self) -> Result<Vec<StreamEvent>> {
+        let usage = self.usage();
+        let (content, tool_calls, finish_reason) = self.into_parts()?;
+        let mut events = Vec::new();
+
+        if !content.is_empty() {
+            events.push(StreamEvent::Text(content));
+        }
+        if !tool_calls.is_empty() {
+            events.push(StreamEvent::ToolCalls(tool_calls));
+        }
+        events.extend(finish_reason.map(StreamEvent::Finish));
+        events.extend(usage.map(StreamEvent::Usage));
+
+        Ok(events)
+    }
+}
+
+/// Ollama API error response
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Native Ollama API client
+pub struct OllamaClient {
+    client: Client,
+    config: Config,
+    retry: RetryPolicy,
+    rate_limits: RateLimitTracker,
+    ledger: Ledger,
+}
+
+impl OllamaClient {
+    /// Create a new Ollama client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be built.
+    pub fn new(config: Config) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+
+        Ok(Self {
+            client,
+            retry: RetryPolicy::from_config(&config.retry),
+            rate_limits: RateLimitTracker::default(),
+            ledger: Ledger::from_config(&config),
+            config,
+        })
+    }
+
+    /// Build a full URL for an API path
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.provider_base_url(), path)
+    }
+
+    /// Attach `ollama.api_key`, if set, for Ollama instances behind an auth proxy
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.config.ollama.api_key {
+            Some(key) => request.header("Authorization", format!("Bearer {key}")),
+            None => request,
+        }
+    }
+
+    /// Model options from the config overlaid with the sampling parameters,
+    /// with `num_predict` defaulting to `max_tokens`
+    ///
+    /// Ollama has no logit bias, so it is not sent.
+    fn options(&self, sampling: &SamplingParams) -> serde_json::Map<String, serde_json::Value> {
+        let mut options = self.config.ollama.options.clone();
+        let values = [
+            ("temperature", sampling.temperature.map(Into::into)),
+            ("top_p", sampling.top_p.map(Into::into)),
+            ("seed", sampling.seed.map(Into::into)),
+            (
+                "stop",
+                Some(sampling.stop.clone().into()).filter(|_| !sampling.stop.is_empty()),
+            ),
+            (
+                "presence_penalty",
+                sampling.presence_penalty.map(Into::into),
+            ),
+            (
+                "frequency_penalty",
+                sampling.frequency_penalty.map(Into::into),
+            ),
+            (
+                "num_predict",
+                sampling.max_completion_tokens.map(Into::into),
+            ),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                options.insert(name.to_string(), value);
+            }
+        }
+
+        options
+            .entry("num_predict")
+            .or_insert_with(|| self.config.max_tokens.into());
+        options
+    }
+
+    /// Send a chat (or raw generate) request, retrying transient failures
+    async fn send(
+        &self,
+        request: provider::ChatRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response> {
+        let options = self.options(&self.config.sampling.overridden_by(&request.sampling));
+        let (path, body) = if self.config.ollama.raw {
+            let prompt = request
+                .messages
+                .into_iter()
+                .map(|m| m.content)
+                .collect::<Vec<_>>()
+                .join("\n\n");
+
+            let request = GenerateRequest {
+                model: self.config.model.clone(),
+                prompt,
+                raw: true,
+                stream,
+                options,
+                keep_alive: self.config.ollama.keep_alive.clone(),
+            };
+            ("/api/generate", serde_json::to_value(request)?)
+        } else {
+            let messages = request
+                .messages
+                .into_iter()
+                .map(|m| OllamaMessage {
+                    role: match m.role {
+                        Role::System | Role::Developer => "system",
+                        Role::User => "user",
+                        Role::Assistant => "assistant",
+                        Role::Tool => "tool",
+                    },
+                    content: m.content,
+                    images: m.images.into_iter().map(|image| image.data).collect(),
+                    tool_calls: m
+                        .tool_calls
+                        .into_iter()
+                        .map(|call| OllamaToolCall {
+                            function: OllamaFunction {
+                                arguments: call
+                                    .function
+                                    .parse_arguments()
+                                    .unwrap_or_else(|_| serde_json::json!({})),
+                                name: call.function.name,
+                            },
+                        })
+                        .collect(),
+                })
+                .collect();
+
+            // Ollama has no tool_choice; the model always decides
+            let chat_request = ChatRequest {
+                model: self.config.model.clone(),
+                messages,
+                stream,
+                tools: request.tools,
+                format: request.response_format.map(|format| format.schema),
+                options,
+                keep_alive: self.config.ollama.keep_alive.clone(),
+            };
+            ("/api/chat", serde_json::to_value(chat_request)?)
+        };
+        let url = self.url(path);
+
+        self.retry
+            .send(
+                &self.rate_limits,
+                || self.authorize(self.client.post(&url).json(&body)),
+                |status, error_text| {
+                    if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(error_text) {
+                        return AppError::ApiError {
+                            message: error_response.error,
+                        };
+                    }
+
+                    AppError::ApiError {
+                        message: format!("API request failed with status {status}: {error_text}"),
+                    }
+                },
+            )
+            .await
+    }
+
+    /// Send a chat request and return the full reply
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the reply cannot be parsed.
+    #[instrument(skip(self, request))]
+    pub async fn complete_chat(&self, request: provider::ChatRequest) -> Result<Completion> {
+        debug!("Sending Ollama chat request");
+
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let started = Instant::now();
+        let response: ChatResponse = self.send(request, false).await?.json().await?;
+        let reported = response.usage();
+        let (content, tool_calls, finish_reason) = response.into_parts()?;
+        let message = Message::assistant_with_tool_calls(content, tool_calls);
+
+        let usage = reported.unwrap_or_else(|| Usage::estimate(prompt_tokens, &message));
+        self.ledger
+            .record("chat", &self.config.model, &usage, started.elapsed());
+
+        Ok(Completion {
+            message,
+            finish_reason,
+            usage,
+        })
+    }
+
+    /// Send a streaming chat request
+    ///
+    /// Ollama streams newline-delimited JSON objects rather than SSE, so bytes
+    /// are buffered until a full line is available before parsing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails before the stream starts.
+    #[instrument(skip(self, request))]
+    pub async fn stream_chat(&self, request: provider::ChatRequest) -> Result<EventStream> {
+        debug!("Sending streaming Ollama chat request");
+
+        let prompt_tokens = usage::estimate_prompt_tokens(&request.messages);
+        let started = Instant::now();
+        let response = self.send(request, true).await?;
+        let bytes = Box::pin(response.bytes_stream());
+
+        let lines = futures_util::stream::unfold(
+            (bytes, Vec::new(), false),
+            |(mut bytes, mut buffer, finished)| async move {
+                if finished {
+                    return None;
+                }
+
+                loop {
+                    if let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = buffer.drain(..=pos).collect();
+                        if let Some(item) = parse_line(&line) {
+                            return Some((item, (bytes, buffer, false)));
+                        }
+                        continue;
+                    }
+
+                    match bytes.next().await {
+                        Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                        Some(Err(e)) => {
+                            return Some((
+                                Err(AppError::Network(e.to_string())),
+                                (bytes, buffer, true),
+                            ));
+                        }
+                        None => {
+                            // Final line without a trailing newline
+                            let item = parse_line(&buffer)?;
+                            return Some((item, (bytes, Vec::new(), true)));
+                        }
+                    }
+                }
+            },
+        );
+
+        let events = assemble_events(lines, |line: ChatResponse, _| line.into_events());
+
+        let events = usage::with_estimated_usage(events, prompt_tokens);
+
+        Ok(self
+            .ledger
+            .track(events, "chat", self.config.model.clone(), started))
+    }
+
+    /// List locally available models via `/api/tags`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the reply cannot be parsed.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct TagsResponse {
+            models: Vec<ModelInfo>,
+        }
+
+        #[derive(Deserialize)]
+        struct ModelInfo {
+            name: String,
+        }
+
+        let url = self.url("/api/tags");
+
+        debug!("Fetching models from {}", url);
+
+        let response = self.authorize(self.client.get(&url)).send().await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(AppError::ApiError {
+                message: format!("Failed to fetch models: {error_text}"),
+            });
+        }
+
+        let tags: TagsResponse = response.json().await?;
+
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+}
+
+/// Parse one NDJSON line, skipping blank lines
+fn parse_line(line: &[u8]) -> Option<Result<ChatResponse>> {
+    let line = std::str::from_utf8(line).map_err(|e| AppError::ApiError {
+        message: format!("Invalid UTF-8 in stream: {e}"),
+    });
+
+    match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str(line.trim()).map_err(AppError::from)),
+        Err(e) => Some(Err(e)),
+    }
+}
+
+#[async_trait]
+impl Provider for OllamaClient {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    async fn complete_chat(&self, request: provider::ChatRequest) -> Result<Completion> {
+        Self::complete_chat(self, request).await
+    }
+
+    async fn stream_chat(&self, request: provider::ChatRequest) -> Result<EventStream> {
+        Self::stream_chat(self, request).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Self::list_models(self).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limits.latest()
+    }
+}
@@ -0,0 +1,216 @@
+//! Provider abstraction so the CLI can talk to any chat-completion backend
+
+use crate::anthropic::AnthropicClient;
+use crate::api::{Message, OpenAIClient, ResponseFormat, ToolCall, ToolChoice, ToolDefinition};
+use crate::config::{Config, ProviderKind};
+use crate::error::{AppError, Result};
+use crate::ollama::OllamaClient;
+use crate::retry::RateLimitInfo;
+use crate::sampling::SamplingParams;
+use crate::usage::Usage;
+use async_trait::async_trait;
+use futures_util::Stream;
+use std::pin::Pin;
+
+/// Stream of events produced by a streaming chat completion
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// A chat completion request
+#[derive(Debug, Clone, Default)]
+pub struct ChatRequest {
+    /// Conversation so far
+    pub messages: Vec<Message>,
+    /// Tools the model may call
+    pub tools: Vec<ToolDefinition>,
+    /// Whether and which tool the model should call (backend default when `None`)
+    pub tool_choice: Option<ToolChoice>,
+    /// Required shape of the reply (free text when `None`)
+    pub response_format: Option<ResponseFormat>,
+    /// Sampling parameters that override the configured ones for this request
+    pub sampling: SamplingParams,
+}
+
+impl ChatRequest {
+    /// Create a request for the given conversation without tools
+    #[must_use]
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            ..Self::default()
+        }
+    }
+
+    /// Offer tools to the model
+    #[must_use]
+    pub fn with_tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Set the tool choice
+    #[must_use]
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Require the reply to match a JSON schema
+    #[must_use]
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    /// Override the configured sampling parameters
+    #[must_use]
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+}
+
+/// A finished chat completion
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Assistant reply, possibly with tool calls
+    pub message: Message,
+    /// Why generation stopped, normalized to OpenAI values (`stop`, `length`, `tool_calls`)
+    pub finish_reason: Option<String>,
+    /// Tokens used by the request, estimated when the backend reports none
+    pub usage: Usage,
+}
+
+impl Completion {
+    /// Get the reply text, treating a truncated reply as an error
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::TokenLimitExceeded` if the reply was cut off.
+    pub fn into_text(self) -> Result<String> {
+        if self.finish_reason.as_deref() == Some("length") {
+            return Err(AppError::TokenLimitExceeded);
+        }
+
+        Ok(self.message.content)
+    }
+}
+
+/// One of several alternative replies to a chat request
+#[derive(Debug, Clone)]
+pub struct Alternative {
+    /// Assistant reply, possibly with tool calls
+    pub message: Message,
+    /// Why generation stopped, normalized to OpenAI values
+    pub finish_reason: Option<String>,
+}
+
+/// Alternative replies to one chat request
+#[derive(Debug, Clone)]
+pub struct Alternatives {
+    /// Replies in choice order
+    pub choices: Vec<Alternative>,
+    /// Tokens used by every request made for them
+    pub usage: Usage,
+}
+
+impl Alternatives {
+    /// Add the replies of another request
+    pub fn extend(&mut self, other: Self) {
+        self.choices.extend(other.choices);
+        self.usage += other.usage;
+    }
+}
+
+impl From<Completion> for Alternatives {
+    fn from(completion: Completion) -> Self {
+        Self {
+            choices: vec![Alternative {
+                message: completion.message,
+                finish_reason: completion.finish_reason,
+            }],
+            usage: completion.usage,
+        }
+    }
+}
+
+/// Ask for `n` replies with one request each, for backends that cannot
+/// return several choices; the requests are sent at once
+///
+/// # Errors
+///
+/// Returns an error if any of the requests fails.
+pub async fn fan_out<P: Provider + ?Sized>(
+    client: &P,
+    request: &ChatRequest,
+    n: usize,
+) -> Result<Alternatives> {
+    let completions = futures_util::future::try_join_all(
+        (0..n.max(1)).map(|_| client.complete_chat(request.clone())),
+    )
+    .await?;
+
+    let mut completions = completions.into_iter().map(Alternatives::from);
+    let mut alternatives = completions.next().ok_or_else(|| AppError::ApiError {
+        message: "No response choices available".to_string(),
+    })?;
+    for other in completions {
+        alternatives.extend(other);
+    }
+    Ok(alternatives)
+}
+
+/// An event in a streaming chat completion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A piece of reply text
+    Text(String),
+    /// A piece of the reasoning a model streams before its reply
+    Reasoning(String),
+    /// Tool calls, emitted once all of their fragments have arrived
+    ToolCalls(Vec<ToolCall>),
+    /// Generation stopped, with the normalized finish reason
+    Finish(String),
+    /// Tokens used by the request, sent once at the end of the stream
+    Usage(Usage),
+}
+
+/// A chat-completion backend (OpenAI, Anthropic, Ollama, ...)
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Short name of the backend, used in status output
+    fn name(&self) -> &'static str;
+
+    /// Send a chat request and return the full reply
+    async fn complete_chat(&self, request: ChatRequest) -> Result<Completion>;
+
+    /// Send a streaming chat request and return a stream of events
+    async fn stream_chat(&self, request: ChatRequest) -> Result<EventStream>;
+
+    /// List the models the backend can serve
+    async fn list_models(&self) -> Result<Vec<String>>;
+
+    /// Send a chat request for `n` alternative replies
+    ///
+    /// By default this sends `n` separate requests.
+    async fn complete_choices(&self, request: ChatRequest, n: u32) -> Result<Alternatives> {
+        fan_out(self, &request, n as usize).await
+    }
+
+    /// Rate-limit quota reported with the most recent response, if the backend sends it
+    fn rate_limit(&self) -> Option<RateLimitInfo> {
+        None
+    }
+}
+
+/// Create the provider selected by `config.provider`
+///
+/// # Errors
+///
+/// Returns an error if the client cannot be built.
+pub fn from_config(config: Config) -> Result<Box<dyn Provider>> {
+    match config.provider {
+        ProviderKind::OpenAI | ProviderKind::Azure => Ok(Box::new(OpenAIClient::new(config)?)),
+        ProviderKind::Anthropic => Ok(Box::new(AnthropicClient::new(config)?)),
+        ProviderKind::Ollama => Ok(Box::new(OllamaClient::new(config)?)),
+    }
+}
@@ -0,0 +1,520 @@
+//! Retrieval-augmented chat over a local directory
+//!
+//! Text files under a root are split into overlapping chunks of lines and
+//! embedded. The index lives in the data directory and is brought up to date
+//! incrementally: files whose modification time is unchanged are skipped,
+//! and files whose content hash is unchanged are not embedded again.
+
+use crate::api::{Message, OpenAIClient};
+use crate::embeddings;
+use crate::error::{AppError, Result};
+use crate::fs_tools::SKIPPED_DIRS;
+use crate::vector_index::{cosine_similarity, load_json, save_json};
+use chrono::{DateTime, Utc};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Settings for indexing and retrieval
+#[derive(Debug, Clone)]
+pub struct RagOptions {
+    /// Embedding model
+    pub model: String,
+    /// Number of chunks sent per embeddings request
+    pub batch_size: usize,
+    /// Largest chunk, in characters
+    pub chunk_chars: usize,
+    /// Characters of trailing lines repeated at the start of the next chunk
+    pub overlap_chars: usize,
+    /// Files larger than this are not indexed
+    pub max_file_bytes: u64,
+    /// Number of chunks retrieved per question
+    pub top_k: usize,
+}
+
+/// A span of lines from a file and its embedding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// First line, counting from 1
+    pub start_line: usize,
+    /// Last line, inclusive
+    pub end_line: usize,
+    /// The lines joined with `\n`
+    pub text: String,
+    /// Embedding of `text`; empty until the chunk has been embedded
+    #[serde(default)]
+    pub embedding: Vec<f32>,
+}
+
+/// An indexed file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// Modification time when the file was last read
+    pub modified: DateTime<Utc>,
+    /// SHA-256 of the content, hex encoded
+    pub hash: String,
+    /// The file's chunks in order
+    pub chunks: Vec<Chunk>,
+}
+
+/// Chunks of every text file under a root, keyed by path relative to the root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentIndex {
+    /// Model the embeddings were made with
+    pub model: String,
+    /// Indexed files by relative path, using `/` as separator
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+/// What a sync changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Files indexed for the first time
+    pub added: usize,
+    /// Files whose content changed and were embedded again
+    pub updated: usize,
+    /// Files that no longer exist or are no longer indexable
+    pub removed: usize,
+    /// Files left as they were
+    pub unchanged: usize,
+    /// Chunks embedded during the sync
+    pub embedded_chunks: usize,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} removed, {} unchanged",
+            self.added, self.updated, self.removed, self.unchanged
+        )
+    }
+}
+
+/// Where a retrieved chunk came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    /// Path relative to the indexed root
+    pub path: String,
+    /// First line of the chunk
+    pub start_line: usize,
+    /// Last line of the chunk
+    pub end_line: usize,
+    /// Cosine similarity to the question
+    pub score: f32,
+}
+
+impl fmt::Display for Citation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.path, self.start_line, self.end_line)
+    }
+}
+
+/// Chunks retrieved for one question
+#[derive(Debug, Clone, Default)]
+pub struct Retrieval {
+    /// Sources of the excerpts, numbered from 1 in this order
+    pub citations: Vec<Citation>,
+    /// The excerpts' text, parallel to `citations`
+    pub excerpts: Vec<String>,
+}
+
+impl Retrieval {
+    /// Whether nothing was retrieved
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.citations.is_empty()
+    }
+
+    /// A system message holding the numbered excerpts
+    #[must_use]
+    pub fn context_message(&self) -> Message {
+        let mut content = String::from(
+            "The excerpts below come from the user's documents. Use them to answer when they \
+             are relevant and cite them by number, like [1]. If they do not contain the answer, \
+             say so rather than guessing.",
+        );
+
+        for (number, (citation, excerpt)) in self.citations.iter().zip(&self.excerpts).enumerate() {
+            let _ = write!(content, "\n\n[{}] {}\n{}", number + 1, citation, excerpt);
+        }
+
+        Message::system(content)
+    }
+}
+
+/// Path of the index for a root directory under the data directory
+#[must_use]
+pub fn index_path(root: &Path) -> PathBuf {
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("llm-cli");
+    path.push("rag");
+    path.push(format!("{}.json", &hex(&digest)[..16]));
+    path
+}
+
+/// Split text into chunks of whole lines
+///
+/// Each chunk holds at most `max_chars` characters, except that a single
+/// longer line is cut to `max_chars`. Consecutive chunks share trailing lines
+/// of up to `overlap_chars` characters so that passages spanning a boundary
+/// are still found. Chunks of only whitespace are dropped.
+#[must_use]
+pub fn chunk_text(text: &str, max_chars: usize, overlap_chars: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let width = |line: &str| line.chars().count() + 1;
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < lines.len() {
+        let mut end = start + 1;
+        let mut size = width(lines[start]);
+        while end < lines.len() && size + width(lines[end]) <= max_chars {
+            size += width(lines[end]);
+            end += 1;
+        }
+
+        let text: String = lines[start..end]
+            .join("\n")
+            .chars()
+            .take(max_chars)
+            .collect();
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                start_line: start + 1,
+                end_line: end,
+                text,
+                embedding: Vec::new(),
+            });
+        }
+
+        if end == lines.len() {
+            break;
+        }
+
+        // Step back over the overlap, but always make progress
+        let mut next = end;
+        let mut overlap = 0;
+        while next > start + 1 && overlap + width(lines[next - 1]) <= overlap_chars {
+            next -= 1;
+            overlap += width(lines[next]);
+        }
+        start = next;
+    }
+
+    chunks
+}
+
+impl DocumentIndex {
+    /// Create an empty index for a model
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Load an index, or `None` if the file does not exist yet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        load_json(path).await
+    }
+
+    /// Save the index, creating parent directories as needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path).await
+    }
+
+    /// Total number of chunks
+    #[must_use]
+    pub fn chunk_count(&self) -> usize {
+        self.files.values().map(|file| file.chunks.len()).sum()
+    }
+
+    /// Bring the index up to date with the files under `root`
+    ///
+    /// `progress` advances once per file found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read or an embedding request fails.
+    pub async fn sync(
+        &mut self,
+        client: &OpenAIClient,
+        root: &Path,
+        options: &RagOptions,
+        progress: &ProgressBar,
+    ) -> Result<SyncReport> {
+        let mut found = Vec::new();
+        collect_files(root, root, options.max_file_bytes, &mut found);
+        progress.set_length(found.len() as u64);
+
+        let mut report = SyncReport::default();
+        let mut fresh = BTreeMap::new();
+        // Files whose chunks still need embeddings, by relative path
+        let mut pending = Vec::new();
+
+        for (relative, path, modified) in found {
+            progress.inc(1);
+            let previous = self.files.remove(&relative);
+
+            if let Some(previous) = previous.as_ref().filter(|file| file.modified == modified) {
+                fresh.insert(relative, previous.clone());
+                report.unchanged += 1;
+                continue;
+            }
+
+            // Binary and non-UTF-8 files are skipped
+            let Ok(bytes) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            let Ok(text) = String::from_utf8(bytes) else {
+                continue;
+            };
+            if text.contains('\0') {
+                continue;
+            }
+
+            let hash = hex(&Sha256::digest(text.as_bytes()));
+            match previous {
+                Some(mut previous) if previous.hash == hash => {
+                    previous.modified = modified;
+                    fresh.insert(relative, previous);
+                    report.unchanged += 1;
+                }
+                previous => {
+                    if previous.is_some() {
+                        report.updated += 1;
+                    } else {
+                        report.added += 1;
+                    }
+
+                    let chunks = chunk_text(&text, options.chunk_chars, options.overlap_chars);
+                    pending.push(relative.clone());
+                    fresh.insert(
+                        relative,
+                        IndexedFile {
+                            modified,
+                            hash,
+                            chunks,
+                        },
+                    );
+                }
+            }
+        }
+
+        report.removed = self.files.len();
+        self.files = fresh;
+
+        let texts: Vec<String> = pending
+            .iter()
+            .flat_map(|relative| self.files[relative].chunks.iter().map(|c| c.text.clone()))
+            .collect();
+        report.embedded_chunks = texts.len();
+
+        if !texts.is_empty() {
+            debug!(
+                "Embedding {} chunks from {} files",
+                texts.len(),
+                pending.len()
+            );
+            progress.set_message(format!("embedding {} chunks", texts.len()));
+
+            let result = embeddings::embed(client, &self.model, &texts, options.batch_size).await;
+            let mut vectors = match result {
+                Ok(vectors) => vectors.into_iter(),
+                Err(e) => {
+                    // Drop the unembedded files so the next sync retries them
+                    for relative in &pending {
+                        self.files.remove(relative);
+                    }
+                    return Err(e);
+                }
+            };
+
+            for relative in &pending {
+                if let Some(file) = self.files.get_mut(relative) {
+                    for chunk in &mut file.chunks {
+                        chunk.embedding = vectors.next().unwrap_or_default();
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// The `k` chunks most similar to a query embedding, best first
+    #[must_use]
+    pub fn search(&self, query: &[f32], k: usize) -> Retrieval {
+        let mut hits: Vec<(f32, &str, &Chunk)> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().map(move |chunk| {
+                    (
+                        cosine_similarity(query, &chunk.embedding),
+                        path.as_str(),
+                        chunk,
+                    )
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        hits.truncate(k);
+
+        let mut retrieval = Retrieval::default();
+        for (score, path, chunk) in hits {
+            retrieval.citations.push(Citation {
+                path: path.to_string(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                score,
+            });
+            retrieval.excerpts.push(chunk.text.clone());
+        }
+        retrieval
+    }
+}
+
+/// Answers retrieval queries against an indexed directory
+pub struct Retriever {
+    client: OpenAIClient,
+    options: RagOptions,
+    index: DocumentIndex,
+}
+
+impl Retriever {
+    /// Load the index stored at `index_path`, sync it with `root` and save it
+    ///
+    /// An index built with a different model is rebuilt from scratch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index cannot be loaded, synced or saved.
+    pub async fn open(
+        client: OpenAIClient,
+        root: &Path,
+        index_path: &Path,
+        options: RagOptions,
+        progress: &ProgressBar,
+    ) -> Result<(Self, SyncReport)> {
+        if !root.is_dir() {
+            return Err(AppError::IndexError(format!(
+                "{} is not a directory",
+                root.display()
+            )));
+        }
+
+        let mut index = DocumentIndex::load(index_path)
+            .await?
+            .filter(|index| index.model == options.model)
+            .unwrap_or_else(|| DocumentIndex::new(options.model.clone()));
+
+        let report = index.sync(&client, root, &options, progress).await?;
+        index.save(index_path).await?;
+
+        Ok((
+            Self {
+                client,
+                options,
+                index,
+            },
+            report,
+        ))
+    }
+
+    /// The synced index
+    pub const fn index(&self) -> &DocumentIndex {
+        &self.index
+    }
+
+    /// Retrieve the chunks most relevant to a question
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query cannot be embedded.
+    pub async fn retrieve(&self, query: &str) -> Result<Retrieval> {
+        if self.index.files.is_empty() {
+            return Ok(Retrieval::default());
+        }
+
+        let embedding = embeddings::embed(&self.client, &self.index.model, &[query.to_string()], 1)
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        Ok(self.index.search(&embedding, self.options.top_k))
+    }
+}
+
+/// Collect (relative path, path, modification time) for indexable files, in name order
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    max_bytes: u64,
+    files: &mut Vec<(String, PathBuf, DateTime<Utc>)>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(std::result::Result::ok).collect();
+    entries.sort_by_key(std::fs::DirEntry::file_name);
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(root, &path, max_bytes, files);
+            }
+        } else if file_type.is_file() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let Ok(modified) = metadata.modified() else {
+                continue;
+            };
+            if metadata.len() > max_bytes {
+                continue;
+            }
+
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((relative, path, modified.into()));
+        }
+    }
+}
+
+/// Lowercase hex encoding
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
@@ -0,0 +1,89 @@
+//! Reasoning models and the reasoning they produce before answering
+//!
+//! OpenAI's o-series and GPT-5 models need `max_completion_tokens` and a
+//! `developer` message instead of `max_tokens` and a `system` one.
+//! They reject `temperature`, `top_p`, the penalties and `logit_bias`, so
+//! those are left out. Servers such as LM Studio and DeepSeek send the
+//! model's reasoning as `reasoning_content`. It is shown apart from the
+//! answer and never sent back.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Model name prefixes of reasoning models
+const REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4", "gpt-5"];
+
+/// Whether a model is known to need reasoning-model requests
+///
+/// A provider prefix such as `openai/` is ignored. GPT-5 chat variants take
+/// ordinary requests.
+#[must_use]
+pub fn is_reasoning_model(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    REASONING_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        && !name.contains("-chat")
+}
+
+/// How much a reasoning model should think before answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// As little reasoning as possible, for the fastest answers
+    Minimal,
+    /// Brief reasoning
+    Low,
+    /// The model's default
+    Medium,
+    /// Thorough reasoning, for the hardest questions
+    High,
+}
+
+impl fmt::Display for ReasoningEffort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Minimal => write!(f, "minimal"),
+            Self::Low => write!(f, "low"),
+            Self::Medium => write!(f, "medium"),
+            Self::High => write!(f, "high"),
+        }
+    }
+}
+
+impl FromStr for ReasoningEffort {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        <Self as clap::ValueEnum>::from_str(value, true).map_err(|_| {
+            AppError::ConfigError(format!(
+                "reasoning_effort must be minimal, low, medium or high, got '{value}'"
+            ))
+        })
+    }
+}
+
+/// How a model's reasoning is shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningDisplay {
+    /// A one-line summary, expanded in chat with `/thinking`
+    #[default]
+    Collapsed,
+    /// The full text, dimmed, above the answer
+    Expanded,
+    /// Nothing
+    Hidden,
+}
+
+impl fmt::Display for ReasoningDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Collapsed => write!(f, "collapsed"),
+            Self::Expanded => write!(f, "expanded"),
+            Self::Hidden => write!(f, "hidden"),
+        }
+    }
+}
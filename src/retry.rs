@@ -0,0 +1,464 @@
+//! Retry policy with exponential backoff and rate-limit header handling
+
+use crate::config::RetryConfig;
+use crate::error::{AppError, Result};
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// Remaining rate-limit quota reported by the server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Requests allowed in the current window
+    pub limit_requests: Option<u64>,
+    /// Requests left in the current window
+    pub remaining_requests: Option<u64>,
+    /// Time until the request window resets
+    pub reset_requests: Option<Duration>,
+    /// Tokens allowed in the current window
+    pub limit_tokens: Option<u64>,
+    /// Tokens left in the current window
+    pub remaining_tokens: Option<u64>,
+    /// Time until the token window resets
+    pub reset_tokens: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Parse OpenAI-style `x-ratelimit-*` or Anthropic `anthropic-ratelimit-*` headers
+    ///
+    /// Returns `None` when the response carries no rate-limit headers at all.
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let number = |openai: &str, anthropic: &str| {
+            text(openai)
+                .or_else(|| text(anthropic))
+                .and_then(|v| v.trim().parse().ok())
+        };
+        let reset = |openai: &str, anthropic: &str| {
+            text(openai)
+                .and_then(parse_duration)
+                .or_else(|| text(anthropic).and_then(parse_reset_timestamp))
+        };
+
+        let info = Self {
+            limit_requests: number(
+                "x-ratelimit-limit-requests",
+                "anthropic-ratelimit-requests-limit",
+            ),
+            remaining_requests: number(
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+            ),
+            reset_requests: reset(
+                "x-ratelimit-reset-requests",
+                "anthropic-ratelimit-requests-reset",
+            ),
+            limit_tokens: number(
+                "x-ratelimit-limit-tokens",
+                "anthropic-ratelimit-tokens-limit",
+            ),
+            remaining_tokens: number(
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+            ),
+            reset_tokens: reset(
+                "x-ratelimit-reset-tokens",
+                "anthropic-ratelimit-tokens-reset",
+            ),
+        };
+
+        (info != Self::default()).then_some(info)
+    }
+
+    /// Whether either quota has dropped below 10% of its limit
+    #[must_use]
+    pub fn is_low(&self) -> bool {
+        let low = |remaining: Option<u64>, limit: Option<u64>| match (remaining, limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => remaining * 10 < limit,
+            _ => false,
+        };
+
+        low(self.remaining_requests, self.limit_requests)
+            || low(self.remaining_tokens, self.limit_tokens)
+    }
+
+    /// Time until an exhausted quota resets, if any quota is at zero
+    fn exhausted_reset(&self) -> Option<Duration> {
+        let requests = (self.remaining_requests == Some(0))
+            .then_some(self.reset_requests)
+            .flatten();
+        let tokens = (self.remaining_tokens == Some(0))
+            .then_some(self.reset_tokens)
+            .flatten();
+
+        requests.max(tokens)
+    }
+}
+
+/// Keeps the most recent rate-limit headers seen by a client
+#[derive(Debug, Default)]
+pub struct RateLimitTracker {
+    latest: Mutex<Option<RateLimitInfo>>,
+}
+
+impl RateLimitTracker {
+    /// Record rate-limit headers from a response, if present
+    pub fn record(&self, headers: &HeaderMap) {
+        if let Some(info) = RateLimitInfo::from_headers(headers) {
+            if let Ok(mut latest) = self.latest.lock() {
+                *latest = Some(info);
+            }
+        }
+    }
+
+    /// Get the most recently recorded quota
+    pub fn latest(&self) -> Option<RateLimitInfo> {
+        self.latest.lock().ok().and_then(|latest| latest.clone())
+    }
+}
+
+/// Retry policy for API requests
+///
+/// Only the request itself is retried: once a response with a success status
+/// is returned its body belongs to the caller, so a stream that has started
+/// emitting tokens is never replayed.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Create a retry policy from configuration
+    #[must_use]
+    pub fn from_config(config: &RetryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_millis(config.base_delay_ms),
+            max_delay: Duration::from_millis(config.max_delay_ms),
+            jitter: config.jitter.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Send a request, retrying connect errors, 429 and 5xx responses
+    ///
+    /// `build` is called once per attempt. Non-success responses that are not
+    /// retried (or that exhaust the attempts) are turned into errors by `map_error`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails for good or `map_error` rejects the response.
+    pub async fn send(
+        &self,
+        tracker: &RateLimitTracker,
+        build: impl Fn() -> RequestBuilder,
+        map_error: impl Fn(StatusCode, &str) -> AppError,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+
+        loop {
+            let response = match build().send().await {
+                Ok(response) => response,
+                Err(e) if e.is_connect() && attempt < self.max_attempts => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        "Connection failed ({}), retrying in {:.1}s (attempt {}/{})",
+                        e,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            tracker.record(response.headers());
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+
+            let delay = self.retry_delay(attempt, status, response.headers());
+            let error_text = response.text().await?;
+
+            match delay {
+                Some(delay) if !is_permanent_rate_limit(&error_text) => {
+                    warn!(
+                        "Request failed with status {}, retrying in {:.1}s (attempt {}/{})",
+                        status,
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return Err(map_error(status, &error_text)),
+            }
+        }
+    }
+
+    /// How long to wait before retrying a failed response, or `None` to give up
+    fn retry_delay(
+        &self,
+        attempt: u32,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<Duration> {
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retryable || attempt >= self.max_attempts {
+            return None;
+        }
+
+        match server_delay(headers) {
+            // Retrying before the server says we may would only fail again
+            Some(delay) if delay > self.max_delay => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Exponential backoff with jitter for the given attempt number
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let factor = self.jitter.mul_add(fastrand::f64().mul_add(2.0, -1.0), 1.0);
+
+        exponential.mul_f64(factor)
+    }
+}
+
+/// 429 responses that retrying cannot fix, such as an exhausted billing quota
+fn is_permanent_rate_limit(body: &str) -> bool {
+    body.contains("insufficient_quota")
+}
+
+/// Delay requested by the server through `Retry-After` or rate-limit reset headers
+fn server_delay(headers: &HeaderMap) -> Option<Duration> {
+    let text = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(ms) = text("retry-after-ms").and_then(|v| v.trim().parse::<f64>().ok()) {
+        return Some(saturating_secs(ms / 1000.0));
+    }
+
+    if let Some(value) = text("retry-after") {
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+            let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+            return Some(wait.to_std().unwrap_or_default());
+        }
+    }
+
+    RateLimitInfo::from_headers(headers).and_then(|info| info.exhausted_reset())
+}
+
+/// Parse a Go-style duration such as `"1s"`, `"6m0s"`, `"20ms"` or `"1h2m3.5s"`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let mut rest = value.trim();
+    let mut total = 0.0;
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds_per_unit = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" | "" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+
+        total += number * seconds_per_unit;
+    }
+
+    Some(saturating_secs(total))
+}
+
+/// Convert seconds sent by a server into a `Duration`
+///
+/// Negative and NaN values become zero; values too large for a `Duration`,
+/// infinity included, become `Duration::MAX` and so exceed any `max_delay`.
+fn saturating_secs(seconds: f64) -> Duration {
+    if seconds.is_nan() || seconds <= 0.0 {
+        return Duration::ZERO;
+    }
+    Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+}
+
+/// Parse an RFC 3339 reset timestamp into the time remaining until it
+fn parse_reset_timestamp(value: &str) -> Option<Duration> {
+    let reset = chrono::DateTime::parse_from_rfc3339(value.trim()).ok()?;
+    let wait = reset.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::from_config(&RetryConfig {
+            max_attempts: 4,
+            base_delay_ms: 100,
+            max_delay_ms: 10_000,
+            jitter: 0.0,
+        })
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_duration("1h2m3.5s"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(
+            parse_duration(&format!("{}s", "9".repeat(40))),
+            Some(Duration::MAX)
+        );
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(30), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_retry_delay_honours_headers() {
+        let policy = policy();
+
+        let delay = policy.retry_delay(
+            1,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "3")]),
+        );
+        assert_eq!(delay, Some(Duration::from_secs(3)));
+
+        let delay = policy.retry_delay(
+            1,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[
+                ("x-ratelimit-remaining-requests", "0"),
+                ("x-ratelimit-reset-requests", "1.5s"),
+                ("x-ratelimit-remaining-tokens", "5000"),
+                ("x-ratelimit-reset-tokens", "30s"),
+            ]),
+        );
+        assert_eq!(delay, Some(Duration::from_millis(1500)));
+
+        // Waiting longer than max_delay is not worth it
+        let delay = policy.retry_delay(
+            1,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "600")]),
+        );
+        assert_eq!(delay, None);
+
+        // Values too large for a Duration are waits too long, not panics
+        for value in ["inf", "1e30"] {
+            let delay = policy.retry_delay(
+                1,
+                StatusCode::TOO_MANY_REQUESTS,
+                &headers(&[("retry-after-ms", value)]),
+            );
+            assert_eq!(delay, None);
+        }
+        let delay = policy.retry_delay(
+            1,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after-ms", "-5")]),
+        );
+        assert_eq!(delay, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_retry_delay_only_for_retryable_statuses() {
+        let policy = policy();
+        let none = HeaderMap::new();
+
+        assert!(policy
+            .retry_delay(1, StatusCode::BAD_GATEWAY, &none)
+            .is_some());
+        assert!(policy
+            .retry_delay(1, StatusCode::UNAUTHORIZED, &none)
+            .is_none());
+        assert!(policy
+            .retry_delay(1, StatusCode::BAD_REQUEST, &none)
+            .is_none());
+        assert!(policy
+            .retry_delay(4, StatusCode::SERVICE_UNAVAILABLE, &none)
+            .is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_info_from_headers() {
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("x-ratelimit-limit-requests", "500"),
+            ("x-ratelimit-remaining-requests", "20"),
+            ("x-ratelimit-reset-requests", "12s"),
+            ("x-ratelimit-limit-tokens", "30000"),
+            ("x-ratelimit-remaining-tokens", "29000"),
+        ]))
+        .unwrap();
+
+        assert_eq!(info.remaining_requests, Some(20));
+        assert_eq!(info.reset_requests, Some(Duration::from_secs(12)));
+        assert!(info.is_low());
+
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "49"),
+        ]))
+        .unwrap();
+        assert_eq!(info.limit_requests, Some(50));
+        assert!(!info.is_low());
+
+        assert!(RateLimitInfo::from_headers(&HeaderMap::new()).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+//! Sampling parameters sent with chat requests
+//!
+//! Every parameter is optional and omitted from requests when unset, so
+//! servers that reject unknown fields keep working with the defaults.
+
+use crate::error::{AppError, Result};
+use crate::reasoning::ReasoningEffort;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Names accepted by [`SamplingParams::set`] and [`SamplingParams::unset`]
+pub const PARAMETER_NAMES: &[&str] = &[
+    "temperature",
+    "top_p",
+    "seed",
+    "stop",
+    "presence_penalty",
+    "frequency_penalty",
+    "logit_bias",
+    "max_completion_tokens",
+    "reasoning_effort",
+];
+
+/// Sampling parameters, each left to the server when unset
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SamplingParams {
+    /// Randomness from 0 to 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling probability mass from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    /// Seed for best-effort deterministic sampling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Sequences that end generation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+
+    /// Penalty from -2 to 2 for tokens that already appeared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+
+    /// Penalty from -2 to 2 scaled by how often tokens appeared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+
+    /// Bias from -100 to 100 added to tokens, keyed by token ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub logit_bias: BTreeMap<String, i32>,
+
+    /// Upper bound on generated tokens, replacing `max_tokens` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+
+    /// How much a reasoning model thinks before answering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl SamplingParams {
+    /// Whether no parameter is set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// These parameters with every parameter set in `overrides` replaced
+    ///
+    /// `stop` and `logit_bias` are replaced as a whole when set.
+    #[must_use]
+    pub fn overridden_by(&self, overrides: &Self) -> Self {
+        Self {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            seed: overrides.seed.or(self.seed),
+            stop: if overrides.stop.is_empty() {
+                self.stop.clone()
+            } else {
+                overrides.stop.clone()
+            },
+            presence_penalty: overrides.presence_penalty.or(self.presence_penalty),
+            frequency_penalty: overrides.frequency_penalty.or(self.frequency_penalty),
+            logit_bias: if overrides.logit_bias.is_empty() {
+                self.logit_bias.clone()
+            } else {
+                overrides.logit_bias.clone()
+            },
+            max_completion_tokens: overrides
+                .max_completion_tokens
+                .or(self.max_completion_tokens),
+            reasoning_effort: overrides.reasoning_effort.or(self.reasoning_effort),
+        }
+    }
+
+    /// Check that every set parameter is in range
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parameter is out of range.
+    pub fn validate(&self) -> Result<()> {
+        check_range("temperature", self.temperature, 0.0, 2.0)?;
+        check_range("top_p", self.top_p, 0.0, 1.0)?;
+        check_range("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        check_range("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+
+        for (token, bias) in &self.logit_bias {
+            if token.parse::<u32>().is_err() {
+                return Err(invalid(format!(
+                    "logit_bias key must be a token ID, got '{token}'"
+                )));
+            }
+            if !(-100..=100).contains(bias) {
+                return Err(invalid(format!(
+                    "logit_bias for token {token} must be between -100 and 100, got {bias}"
+                )));
+            }
+        }
+
+        if self.max_completion_tokens == Some(0) {
+            return Err(invalid(
+                "max_completion_tokens must be at least 1".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Set a parameter from text, as in `/set temperature 0.2`
+    ///
+    /// `stop` replaces the stop sequences with one sequence, and `logit_bias`
+    /// takes `TOKEN=BIAS` and adds to the existing biases.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is unknown or the value is invalid.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let mut updated = self.clone();
+        match name {
+            "temperature" => updated.temperature = Some(parse(name, value)?),
+            "top_p" => updated.top_p = Some(parse(name, value)?),
+            "seed" => updated.seed = Some(parse(name, value)?),
+            "stop" => updated.stop = vec![value.to_string()],
+            "presence_penalty" => updated.presence_penalty = Some(parse(name, value)?),
+            "frequency_penalty" => updated.frequency_penalty = Some(parse(name, value)?),
+            "logit_bias" => {
+                let (token, bias) = value
+                    .split_once('=')
+                    .ok_or_else(|| invalid(format!("expected TOKEN=BIAS, got '{value}'")))?;
+                updated
+                    .logit_bias
+                    .insert(token.trim().to_string(), parse(name, bias.trim())?);
+            }
+            "max_completion_tokens" => updated.max_completion_tokens = Some(parse(name, value)?),
+            "reasoning_effort" => updated.reasoning_effort = Some(value.trim().parse()?),
+            _ => return Err(unknown(name)),
+        }
+
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// Clear the parameters reasoning models reject, returning the names of
+    /// those that were set
+    pub fn clear_for_reasoning(&mut self) -> Vec<&'static str> {
+        [
+            ("temperature", self.temperature.take().is_some()),
+            ("top_p", self.top_p.take().is_some()),
+            ("presence_penalty", self.presence_penalty.take().is_some()),
+            ("frequency_penalty", self.frequency_penalty.take().is_some()),
+            (
+                "logit_bias",
+                !std::mem::take(&mut self.logit_bias).is_empty(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, was_set)| was_set.then_some(name))
+        .collect()
+    }
+
+    /// Clear a parameter
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is unknown.
+    pub fn unset(&mut self, name: &str) -> Result<()> {
+        match name {
+            "temperature" => self.temperature = None,
+            "top_p" => self.top_p = None,
+            "seed" => self.seed = None,
+            "stop" => self.stop.clear(),
+            "presence_penalty" => self.presence_penalty = None,
+            "frequency_penalty" => self.frequency_penalty = None,
+            "logit_bias" => self.logit_bias.clear(),
+            "max_completion_tokens" => self.max_completion_tokens = None,
+            "reasoning_effort" => self.reasoning_effort = None,
+            _ => return Err(unknown(name)),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SamplingParams {
+    /// `name = value` pairs of the set parameters, separated by commas
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) if !map.is_empty() => {
+                let pairs: Vec<String> = map
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect();
+                write!(f, "{}", pairs.join(", "))
+            }
+            _ => write!(f, "server defaults"),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid(format!("invalid value for {name}: '{value}'")))
+}
+
+fn check_range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<()> {
+    match value {
+        Some(value) if !(min..=max).contains(&value) => Err(invalid(format!(
+            "{name} must be between {min} and {max}, got {value}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn unknown(name: &str) -> AppError {
+    invalid(format!(
+        "unknown parameter '{name}' (expected one of {})",
+        PARAMETER_NAMES.join(", ")
+    ))
+}
+
+const fn invalid(message: String) -> AppError {
+    AppError::ConfigError(message)
+}
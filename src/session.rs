@@ -1,7 +1,10 @@
 //! Session management for maintaining conversation history
 
-use crate::api::Message;
+use crate::api::{Message, Role};
+use crate::context::ContextStrategy;
 use crate::error::Result;
+use crate::sampling::SamplingParams;
+use crate::usage::Usage;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -14,6 +17,12 @@ pub struct Session {
     pub messages: Vec<Message>,
     pub model: String,
     pub total_tokens: u32,
+    /// Sampling parameters changed with `/set`, overriding the configured ones
+    #[serde(default, skip_serializing_if = "SamplingParams::is_empty")]
+    pub sampling: SamplingParams,
+    /// Context strategy chosen with `/context`, overriding the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_strategy: Option<ContextStrategy>,
 }
 
 impl Session {
@@ -25,6 +34,8 @@ impl Session {
             messages: Vec::new(),
             model,
             total_tokens: 0,
+            sampling: SamplingParams::default(),
+            context_strategy: None,
         }
     }
 
@@ -33,6 +44,24 @@ impl Session {
         self.messages.push(message);
     }
 
+    /// Add the tokens used by a request to the session total
+    pub fn record_usage(&mut self, usage: &Usage) {
+        self.total_tokens = self.total_tokens.saturating_add(usage.total_tokens);
+    }
+
+    /// Pin the latest turn (the last user message and the replies to it) so
+    /// it survives compaction; returns how many messages were pinned
+    pub fn pin_last_turn(&mut self) -> usize {
+        let Some(start) = self.messages.iter().rposition(|m| m.role == Role::User) else {
+            return 0;
+        };
+
+        for message in &mut self.messages[start..] {
+            message.pinned = true;
+        }
+        self.messages.len() - start
+    }
+
     /// Get the conversation history
     pub fn history(&self) -> &[Message] {
         &self.messages
@@ -77,12 +106,35 @@ impl Session {
 
         for message in &self.messages {
             let role = match message.role {
-                crate::api::Role::System => "System",
+                crate::api::Role::System | crate::api::Role::Developer => "System",
                 crate::api::Role::User => "User",
                 crate::api::Role::Assistant => "Assistant",
+                crate::api::Role::Tool => "Tool",
             };
 
-            output.push_str(&format!("## {}\n\n{}\n\n", role, message.content));
+            output.push_str(&format!("## {}\n\n", role));
+
+            if let Some(id) = &message.tool_call_id {
+                output.push_str(&format!("*Result of `{}`*\n\n", id));
+            }
+
+            if !message.content.is_empty() {
+                output.push_str(&format!("{}\n\n", message.content));
+            }
+
+            for image in &message.images {
+                output.push_str(&format!(
+                    "*Attached image* `{}` ({})\n\n",
+                    image.path, image.mime_type
+                ));
+            }
+
+            for call in &message.tool_calls {
+                output.push_str(&format!(
+                    "**Tool call** `{}` (`{}`): `{}`\n\n",
+                    call.function.name, call.id, call.function.arguments
+                ));
+            }
         }
 
         output
@@ -0,0 +1,289 @@
+//! Incremental decoder for server-sent event (SSE) streams
+//!
+//! Network chunks can end anywhere: in the middle of a line, an event, or a
+//! multi-byte UTF-8 character. The decoder keeps undecoded bytes until a full
+//! line is available, so nothing is dropped or garbled at chunk boundaries.
+
+use crate::error::{AppError, Result};
+use futures_util::{Stream, StreamExt};
+use std::collections::VecDeque;
+
+/// A single dispatched server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// Event type from the `event:` field (`"message"` when absent)
+    pub event: String,
+    /// Event payload; multiple `data:` lines are joined with `\n`
+    pub data: String,
+    /// Last event ID seen on the stream, if any
+    pub id: Option<String>,
+    /// Reconnection time requested by the server, in milliseconds
+    pub retry: Option<u64>,
+}
+
+/// Stateful SSE decoder following the WHATWG event-stream parsing rules
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    /// Bytes of the current incomplete line
+    line: Vec<u8>,
+    /// Whether the previous chunk ended with `\r` (a following `\n` is part of the same line break)
+    pending_cr: bool,
+    /// Whether the stream start (and a possible BOM) has been handled
+    started: bool,
+    /// Data lines collected for the event being built
+    data: String,
+    /// Whether any `data:` field was seen for the event being built
+    has_data: bool,
+    /// Event type for the event being built
+    event: Option<String>,
+    /// Last event ID, which persists across events
+    last_id: Option<String>,
+    /// Last retry value, which persists across events
+    retry: Option<u64>,
+}
+
+impl SseDecoder {
+    /// Create a new decoder
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of bytes and return any events completed by it
+    pub fn push(&mut self, mut bytes: &[u8]) -> Vec<SseEvent> {
+        let events = Vec::new();
+
+        if !self.started && !bytes.is_empty() {
+            // Strip a UTF-8 byte order mark, even if it arrives split
+            self.line.extend_from_slice(bytes);
+            if self.line.len() < 3 && b"\xEF\xBB\xBF".starts_with(&self.line) {
+                return events;
+            }
+            self.started = true;
+            let buffered = std::mem::take(&mut self.line);
+            let buffered = buffered.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&buffered);
+            return self.push_lines(buffered, events);
+        }
+
+        if self.pending_cr && !bytes.is_empty() {
+            self.pending_cr = false;
+            if let Some(rest) = bytes.strip_prefix(b"\n") {
+                bytes = rest;
+            }
+        }
+
+        self.push_lines(bytes, events)
+    }
+
+    /// Split bytes into lines and process each complete one
+    fn push_lines(&mut self, mut bytes: &[u8], mut events: Vec<SseEvent>) -> Vec<SseEvent> {
+        while let Some(pos) = bytes.iter().position(|&b| b == b'\n' || b == b'\r') {
+            self.line.extend_from_slice(&bytes[..pos]);
+            let line = std::mem::take(&mut self.line);
+
+            let skip = match bytes.get(pos + 1) {
+                Some(b'\n') if bytes[pos] == b'\r' => 2,
+                None if bytes[pos] == b'\r' => {
+                    self.pending_cr = true;
+                    1
+                }
+                _ => 1,
+            };
+            bytes = &bytes[pos + skip..];
+
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
+            }
+        }
+
+        self.line.extend_from_slice(bytes);
+        events
+    }
+
+    /// Signal end of stream, dispatching a final event that lacked a trailing blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                return Some(event);
+            }
+        }
+
+        self.dispatch()
+    }
+
+    /// Interpret one complete line
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        if line.starts_with(':') {
+            // Comment, commonly used as a keep-alive
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Emit the event being built, if it has any data
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+
+        if !self.has_data {
+            self.data.clear();
+            return None;
+        }
+
+        self.has_data = false;
+
+        Some(SseEvent {
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data: std::mem::take(&mut self.data),
+            id: self.last_id.clone(),
+            retry: self.retry,
+        })
+    }
+}
+
+/// Decode a stream of byte chunks into a stream of SSE events
+pub fn events<S, B, E>(bytes: S) -> impl Stream<Item = Result<SseEvent>> + Send
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    futures_util::stream::unfold(
+        (Box::pin(bytes), SseDecoder::new(), VecDeque::new(), false),
+        |(mut bytes, mut decoder, mut pending, mut finished)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((Ok(event), (bytes, decoder, pending, finished)));
+                }
+
+                if finished {
+                    return None;
+                }
+
+                match bytes.next().await {
+                    Some(Ok(chunk)) => pending.extend(decoder.push(chunk.as_ref())),
+                    Some(Err(e)) => {
+                        return Some((
+                            Err(AppError::Network(e.to_string())),
+                            (bytes, decoder, pending, true),
+                        ));
+                    }
+                    None => {
+                        finished = true;
+                        pending.extend(decoder.finish());
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(decoder.push(chunk));
+        }
+        events.extend(decoder.finish());
+        events
+    }
+
+    #[test]
+    fn test_event_split_across_chunks() {
+        let events = decode_all(&[b"da", b"ta: {\"a\":", b" 1}\n", b"\ndata: two\n\n"]);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data, "{\"a\": 1}");
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[1].data, "two");
+    }
+
+    #[test]
+    fn test_multibyte_utf8_split_across_chunks() {
+        let bytes = "data: 你好 👋\n\n".as_bytes();
+        // Split inside both the CJK character and the emoji
+        let events = decode_all(&[&bytes[..8], &bytes[8..14], &bytes[14..]]);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "你好 👋");
+    }
+
+    #[test]
+    fn test_multiline_data_and_fields() {
+        let events = decode_all(&[
+            b": keep-alive\n",
+            b"event: content_block_delta\nid: 42\nretry: 3000\n",
+            b"data: line one\ndata:line two\n\n",
+        ]);
+
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "content_block_delta".to_string(),
+                data: "line one\nline two".to_string(),
+                id: Some("42".to_string()),
+                retry: Some(3000),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_crlf_split_between_chunks() {
+        let events = decode_all(&[b"data: a\r", b"\n\r\n", b"data: b\r\r"]);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data, "a");
+        assert_eq!(events[1].data, "b");
+    }
+
+    #[test]
+    fn test_bom_and_unterminated_final_event() {
+        let events = decode_all(&[b"\xEF\xBB", b"\xBFdata: first\n\ndata: [DONE]"]);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].data, "first");
+        assert_eq!(events[1].data, "[DONE]");
+    }
+
+    #[test]
+    fn test_events_without_data_are_skipped() {
+        let events = decode_all(&[b"event: ping\n\n", b"id: 7\n\n", b"data:\n\n"]);
+
+        // Only the event with an (empty) data field is dispatched
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "");
+        assert_eq!(events[0].id, Some("7".to_string()));
+    }
+}
@@ -0,0 +1,201 @@
+//! Structured output: JSON replies validated against a JSON Schema
+
+use crate::api::{Message, ResponseFormat, Role};
+use crate::error::{AppError, Result};
+use crate::provider::{ChatRequest, Provider};
+use std::path::Path;
+use tracing::warn;
+
+/// A JSON schema the reply must conform to
+pub struct Schema {
+    format: ResponseFormat,
+    validator: jsonschema::Validator,
+}
+
+impl Schema {
+    /// Compile a schema, rejecting it if it is not itself valid
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema is invalid.
+    pub fn new(name: impl Into<String>, schema: serde_json::Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| AppError::SchemaError(format!("invalid schema: {e}")))?;
+
+        Ok(Self {
+            format: ResponseFormat::json_schema(name, schema),
+            validator,
+        })
+    }
+
+    /// Load a schema from a JSON file, named after the file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not hold a valid schema.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let schema = serde_json::from_str(&contents)?;
+
+        // Backends only accept letters, digits, `_` and `-` in schema names
+        let name: String = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        Self::new(
+            if name.is_empty() {
+                "response".to_string()
+            } else {
+                name
+            },
+            schema,
+        )
+    }
+
+    /// The response format to send with requests
+    #[must_use]
+    pub const fn response_format(&self) -> &ResponseFormat {
+        &self.format
+    }
+
+    /// Validate a value, returning one message per violation
+    #[must_use]
+    pub fn validate(&self, value: &serde_json::Value) -> Vec<String> {
+        self.validator
+            .iter_errors(value)
+            .map(|error| {
+                let location = error.instance_path.to_string();
+                if location.is_empty() {
+                    error.to_string()
+                } else {
+                    format!("{location}: {error}")
+                }
+            })
+            .collect()
+    }
+}
+
+/// Pull a JSON value out of a reply, tolerating code fences and surrounding prose
+#[must_use]
+pub fn extract_json(text: &str) -> Option<serde_json::Value> {
+    let trimmed = text.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some(value);
+    }
+
+    // A fenced block, with or without a language tag
+    if let Some(start) = trimmed.find("```") {
+        let after = &trimmed[start + 3..];
+        let body = after
+            .find('\n')
+            .map_or(after, |newline| &after[newline + 1..]);
+        if let Some(end) = body.find("```") {
+            if let Ok(value) = serde_json::from_str(body[..end].trim()) {
+                return Some(value);
+            }
+        }
+    }
+
+    // The outermost object or array
+    let start = trimmed.find(['{', '['])?;
+    let close = if trimmed[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = trimmed.rfind(close)?;
+    serde_json::from_str(trimmed.get(start..=end)?).ok()
+}
+
+/// Whether an error is the server refusing `response_format`
+///
+/// Auth, rate-limit and server errors do not name the field, so they are not
+/// retried in prompt-only mode.
+fn rejects_response_format(error: &AppError) -> bool {
+    matches!(error, AppError::ApiError { message }
+        if message.contains("response_format") || message.contains("json_schema"))
+}
+
+/// Ask for a reply matching `schema`, re-asking with the errors up to `max_repairs` times
+///
+/// The schema is sent as a `response_format` first. If the server rejects
+/// that field, the request is repeated with the schema in a system message
+/// instead; any other error is returned as is.
+///
+/// # Errors
+///
+/// Returns an error if a request fails or the reply still does not match after the repairs.
+pub async fn complete_json(
+    provider: &dyn Provider,
+    mut messages: Vec<Message>,
+    schema: &Schema,
+    max_repairs: usize,
+) -> Result<serde_json::Value> {
+    let mut native = true;
+    let mut repairs = 0;
+
+    loop {
+        let mut request = ChatRequest::new(messages.clone());
+        if native {
+            request = request.with_response_format(schema.response_format().clone());
+        }
+
+        let reply = match provider.complete_chat(request).await {
+            Err(e) if native && rejects_response_format(&e) => {
+                warn!(
+                    "Server rejected response_format ({e}); asking for JSON in the prompt instead"
+                );
+                native = false;
+
+                let after_system = messages
+                    .iter()
+                    .take_while(|m| m.role == Role::System)
+                    .count();
+                messages.insert(
+                    after_system,
+                    Message::system(schema.response_format().instruction()),
+                );
+                continue;
+            }
+            result => result?.into_text()?,
+        };
+
+        let errors = match extract_json(&reply) {
+            Some(value) => {
+                let errors = schema.validate(&value);
+                if errors.is_empty() {
+                    return Ok(value);
+                }
+                errors
+            }
+            None => vec!["the reply is not valid JSON".to_string()],
+        };
+
+        if repairs == max_repairs {
+            return Err(AppError::SchemaError(format!(
+                "reply still invalid after {} attempts: {}",
+                repairs + 1,
+                errors.join("; ")
+            )));
+        }
+        repairs += 1;
+        warn!("Reply does not match the schema, asking for a fix ({repairs}/{max_repairs})");
+
+        messages.push(Message::assistant(reply));
+        messages.push(Message::user(format!(
+            "That reply does not conform to the JSON Schema:\n- {}\n\n\
+             Respond again with only the corrected JSON.",
+            errors.join("\n- ")
+        )));
+    }
+}
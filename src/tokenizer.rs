@@ -0,0 +1,231 @@
+//! Offline BPE tokenizer for counting tokens before a request is sent
+//!
+//! The `cl100k_base` and `o200k_base` vocabularies are bundled. Other
+//! vocabularies can be loaded from a `.tiktoken` file (one base64 token and
+//! its rank per line), split with the pattern of one of the bundled encodings.
+
+use crate::api::Message;
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use tiktoken_rs::{CoreBPE, Rank};
+
+/// Tokens added per message for the role and separators in OpenAI's chat format
+const TOKENS_PER_MESSAGE: usize = 3;
+
+/// Tokens that prime the assistant's reply
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Pre-tokenization pattern of `cl100k_base`
+const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// Pre-tokenization pattern of `o200k_base`
+const O200K_PATTERN: &str = concat!(
+    r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+(?!\S)|\s+",
+);
+
+/// A bundled BPE vocabulary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Used by GPT-4, GPT-3.5 Turbo and the embedding models
+    #[value(name = "cl100k_base")]
+    Cl100kBase,
+    /// Used by GPT-4o, GPT-4.1 and the o-series models
+    #[value(name = "o200k_base")]
+    O200kBase,
+}
+
+impl Encoding {
+    /// Encoding used by a model, falling back to `cl100k_base` for unknown models
+    ///
+    /// Counts for models from other vendors are approximate.
+    #[must_use]
+    pub fn for_model(model: &str) -> Self {
+        const O200K_PREFIXES: &[&str] = &[
+            "gpt-4o",
+            "gpt-4.1",
+            "gpt-4.5",
+            "gpt-5",
+            "chatgpt-4o",
+            "o1",
+            "o3",
+            "o4",
+        ];
+
+        if O200K_PREFIXES
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            Self::O200kBase
+        } else {
+            Self::Cl100kBase
+        }
+    }
+
+    const fn pattern(self) -> &'static str {
+        match self {
+            Self::Cl100kBase => CL100K_PATTERN,
+            Self::O200kBase => O200K_PATTERN,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cl100kBase => write!(f, "cl100k_base"),
+            Self::O200kBase => write!(f, "o200k_base"),
+        }
+    }
+}
+
+/// A BPE tokenizer, bundled or loaded from a vocabulary file
+#[derive(Clone)]
+pub struct Tokenizer {
+    bpe: Cow<'static, CoreBPE>,
+    name: String,
+}
+
+impl fmt::Debug for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tokenizer")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tokenizer {
+    /// Tokenizer for a bundled encoding
+    ///
+    /// The vocabulary is parsed on first use and shared afterwards.
+    #[must_use]
+    pub fn new(encoding: Encoding) -> Self {
+        let bpe = match encoding {
+            Encoding::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+            Encoding::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        };
+
+        Self {
+            bpe: Cow::Borrowed(bpe),
+            name: encoding.to_string(),
+        }
+    }
+
+    /// Tokenizer for the vocabulary in a `.tiktoken` file
+    ///
+    /// Text is split into pieces with the pattern of `pattern_of` before the
+    /// pieces are merged with the file's ranks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn from_file(path: &Path, pattern_of: Encoding) -> Result<Self> {
+        use base64::Engine;
+
+        let invalid =
+            |message: String| AppError::TokenizerError(format!("{}: {}", path.display(), message));
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| invalid(format!("cannot read vocabulary ({e})")))?;
+
+        let mut encoder = HashMap::default();
+        let mut ranks = HashSet::new();
+        for (number, line) in contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+        {
+            let bad_line = || {
+                invalid(format!(
+                    "line {} is not '<base64 token> <rank>'",
+                    number + 1
+                ))
+            };
+            let (token, rank) = line.trim().split_once(' ').ok_or_else(bad_line)?;
+            let token = base64::engine::general_purpose::STANDARD
+                .decode(token)
+                .map_err(|_| bad_line())?;
+            let rank: Rank = rank.trim().parse().map_err(|_| bad_line())?;
+            if !ranks.insert(rank) {
+                return Err(invalid(format!("rank {rank} appears more than once")));
+            }
+            encoder.insert(token, rank);
+        }
+        if encoder.is_empty() {
+            return Err(invalid("the vocabulary is empty".to_string()));
+        }
+
+        let bpe = CoreBPE::new(encoder, HashMap::default(), pattern_of.pattern())
+            .map_err(|e| invalid(e.to_string()))?;
+
+        Ok(Self {
+            bpe: Cow::Owned(bpe),
+            name: path.display().to_string(),
+        })
+    }
+
+    /// Tokenizer for `model`, or the vocabulary file if one is given
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vocabulary file cannot be loaded.
+    pub fn for_model(
+        model: &str,
+        encoding: Option<Encoding>,
+        vocab: Option<&Path>,
+    ) -> Result<Self> {
+        let encoding = encoding.unwrap_or_else(|| Encoding::for_model(model));
+        vocab.map_or_else(
+            || Ok(Self::new(encoding)),
+            |path| Self::from_file(path, encoding),
+        )
+    }
+
+    /// Encoding name, or the vocabulary file it was loaded from
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Token IDs of a text, with special tokens treated as plain text
+    #[must_use]
+    pub fn encode(&self, text: &str) -> Vec<Rank> {
+        self.bpe.encode_ordinary(text)
+    }
+
+    /// Number of tokens in a text
+    #[must_use]
+    pub fn count(&self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+
+    /// Tokens one message takes up in a chat request, including its framing
+    ///
+    /// Images are not counted.
+    #[must_use]
+    pub fn count_message(&self, message: &Message) -> usize {
+        let tool_calls: usize = message
+            .tool_calls
+            .iter()
+            .map(|call| self.count(&call.function.name) + self.count(&call.function.arguments))
+            .sum();
+
+        TOKENS_PER_MESSAGE + 1 + self.count(&message.content) + tool_calls
+    }
+
+    /// Tokens a conversation takes up in a chat request, including the
+    /// per-message overhead and the priming of the reply
+    #[must_use]
+    pub fn count_messages(&self, messages: &[Message]) -> usize {
+        messages
+            .iter()
+            .map(|message| self.count_message(message))
+            .sum::<usize>()
+            + REPLY_PRIMING_TOKENS
+    }
+}
@@ -0,0 +1,72 @@
+//! Tools the model can call during a chat
+
+use crate::api::{ToolCall, ToolDefinition};
+use crate::error::Result;
+use async_trait::async_trait;
+
+/// Maximum number of call→execute→respond rounds for a single user message
+pub const MAX_TOOL_ROUNDS: usize = 10;
+
+/// A function the model can call
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Name, description and parameter schema advertised to the model
+    fn definition(&self) -> ToolDefinition;
+
+    /// Run the tool with the parsed arguments and return its output
+    async fn call(&self, arguments: serde_json::Value) -> Result<String>;
+}
+
+/// The set of tools offered to the model
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tool
+    pub fn register(&mut self, tool: impl Tool + 'static) {
+        self.tools.push(Box::new(tool));
+    }
+
+    /// Whether a tool with this name is registered
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools
+            .iter()
+            .any(|tool| tool.definition().function.name == name)
+    }
+
+    /// Definitions of all registered tools
+    #[must_use]
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    /// Execute a tool call and return the text to send back to the model
+    ///
+    /// Failures are returned as text rather than errors so the model can see
+    /// what went wrong and try again.
+    pub async fn execute(&self, call: &ToolCall) -> String {
+        let Some(tool) = self
+            .tools
+            .iter()
+            .find(|tool| tool.definition().function.name == call.function.name)
+        else {
+            return format!("Error: unknown tool `{}`", call.function.name);
+        };
+
+        let result = match call.function.parse_arguments() {
+            Ok(arguments) => tool.call(arguments).await,
+            Err(e) => Err(e),
+        };
+
+        result.unwrap_or_else(|e| format!("Error: {e}"))
+    }
+}
@@ -393,6 +393,336 @@ pub fn display_error(error: &str) {
     eprintln!("{} {}", "Error:".red().bold(), error);
 }
 
+/// Show a tool call the assistant is making
+pub fn display_tool_call(call: &crate::api::ToolCall) {
+    println!(
+        "{} {}{}",
+        "⚙".yellow(),
+        call.function.name.yellow().bold(),
+        format!("({})", call.function.arguments).dimmed()
+    );
+}
+
+/// Display a reply with each token coloured by how confident the model was
+///
+/// Text and markdown show the reply followed by the least confident tokens
+/// and their alternatives; JSON lists every token.
+pub fn display_logprobs(
+    response: &str,
+    tokens: &[crate::logprobs::TokenLogprob],
+    format: crate::cli::OutputFormat,
+) {
+    use crate::logprobs;
+
+    if matches!(format, crate::cli::OutputFormat::Json) {
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                let top_logprobs: Vec<_> = token
+                    .top_logprobs
+                    .iter()
+                    .map(|top| {
+                        serde_json::json!({
+                            "token": top.token,
+                            "logprob": top.logprob,
+                            "probability": top.probability(),
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "token": token.token,
+                    "logprob": token.logprob,
+                    "probability": token.probability(),
+                    "top_logprobs": top_logprobs,
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "response": response,
+            "tokens": tokens,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+
+    println!();
+    println!("{}", "Assistant:".green().bold());
+    println!();
+    print!("  ");
+    for token in tokens {
+        let text = token.token.replace('\n', "\n  ");
+        print!("{}", colour_by_confidence(&text, token.confidence()));
+    }
+    println!();
+    println!();
+    println!(
+        "{} {} {} {}",
+        "Confidence:".dimmed(),
+        colour_by_confidence("50-90%", logprobs::Confidence::Medium),
+        colour_by_confidence("20-50%", logprobs::Confidence::Low),
+        colour_by_confidence("below 20%", logprobs::Confidence::VeryLow),
+    );
+
+    let lowest = logprobs::lowest(tokens, logprobs::LOWEST_SHOWN);
+    let guesses: Vec<_> = lowest
+        .into_iter()
+        .map(|position| &tokens[position])
+        .filter(|token| token.confidence() < logprobs::Confidence::High)
+        .collect();
+    if !guesses.is_empty() {
+        println!("{}", "Least confident tokens:".dimmed());
+        for token in guesses {
+            let alternatives: Vec<_> = token
+                .alternatives()
+                .map(|top| format!("{:?} {:.0}%", top.token, top.probability() * 100.0))
+                .collect();
+            let probability = format!("{:.0}%", token.probability() * 100.0);
+            print!(
+                "  {} {}",
+                colour_by_confidence(&format!("{:?}", token.token), token.confidence()),
+                probability.dimmed()
+            );
+            if alternatives.is_empty() {
+                println!();
+            } else {
+                println!("  {} {}", "instead:".dimmed(), alternatives.join(", "));
+            }
+        }
+    }
+    println!();
+}
+
+/// Colour text by a confidence band; confident text is left plain
+fn colour_by_confidence(text: &str, confidence: crate::logprobs::Confidence) -> colored::ColoredString {
+    use crate::logprobs::Confidence;
+
+    match confidence {
+        Confidence::High => text.normal(),
+        Confidence::Medium => text.yellow(),
+        Confidence::Low => text.red(),
+        Confidence::VeryLow => text.white().on_red(),
+    }
+}
+
+/// Display alternative replies, numbered from 1
+///
+/// Replies that call tools show their calls; replies cut off by the token
+/// limit are marked.
+pub fn display_alternatives(alternatives: &[crate::provider::Alternative], format: crate::cli::OutputFormat) {
+    if matches!(format, crate::cli::OutputFormat::Json) {
+        let responses: Vec<_> = alternatives
+            .iter()
+            .map(|alternative| {
+                serde_json::json!({
+                    "response": alternative.message.content,
+                    "finish_reason": alternative.finish_reason,
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "responses": responses,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+
+    for (number, alternative) in alternatives.iter().enumerate() {
+        println!();
+        println!("{}", format!("Reply {}:", number + 1).green().bold());
+        println!();
+        if !alternative.message.content.is_empty() {
+            println!("{}", process_markdown_content(&alternative.message.content));
+        }
+        for call in &alternative.message.tool_calls {
+            display_tool_call(call);
+        }
+        if alternative.finish_reason.as_deref() == Some("length") {
+            println!("{}", "(cut off by the token limit)".yellow());
+        }
+    }
+    println!();
+}
+
+/// Ask which of `count` alternative replies to keep, returning its index
+pub fn select_alternative(count: usize) -> io::Result<usize> {
+    Input::<usize>::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Keep reply [1-{count}]"))
+        .default(1)
+        .validate_with(|number: &usize| {
+            if (1..=count).contains(number) {
+                Ok(())
+            } else {
+                Err(format!("Pick a reply from 1 to {count}"))
+            }
+        })
+        .interact_text()
+        .map(|number| number - 1)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Display the sources of the excerpts a reply was given
+pub fn display_citations(citations: &[crate::rag::Citation]) {
+    println!("{}", "Sources:".dimmed());
+    for (number, citation) in citations.iter().enumerate() {
+        println!(
+            "  {} {} {}",
+            format!("[{}]", number + 1).cyan(),
+            citation,
+            format!("({:.2})", citation.score).dimmed()
+        );
+    }
+    println!();
+}
+
+/// Show the tokens used by a reply and the session so far
+///
+/// Counts estimated locally are marked with `~`.
+pub fn display_usage(usage: &crate::usage::Usage, session_tokens: u32) {
+    let marker = if usage.estimated { "~" } else { "" };
+    println!(
+        "{}",
+        format!(
+            "{marker}{} tokens ({} prompt + {} completion) · session {}",
+            usage.total_tokens, usage.prompt_tokens, usage.completion_tokens, session_tokens
+        )
+        .dimmed()
+    );
+}
+
+/// Show usage ledger totals by day, model and endpoint
+pub fn display_usage_report(report: &crate::ledger::UsageReport) {
+    let heading = report.since.map_or_else(
+        || "Usage".to_string(),
+        |since| format!("Usage since {}", since.format("%Y-%m-%d %H:%M UTC")),
+    );
+    println!("{}", heading.cyan().bold());
+    println!("  {}", usage_totals_line(&report.total));
+    if report.total.unpriced_requests > 0 {
+        println!(
+            "  {}",
+            format!(
+                "Not in the cost: {} requests to models without a price (see usage.prices)",
+                report.total.unpriced_requests
+            )
+            .dimmed()
+        );
+    }
+
+    let by_day = report.by_day.iter().map(|(day, totals)| (day.to_string(), totals));
+    display_usage_group("By day", by_day);
+    display_usage_group("By model", report.by_model.iter().map(|(k, v)| (k.clone(), v)));
+    display_usage_group("By endpoint", report.by_endpoint.iter().map(|(k, v)| (k.clone(), v)));
+}
+
+fn display_usage_group<'a>(
+    title: &str,
+    groups: impl Iterator<Item = (String, &'a crate::ledger::UsageTotals)>,
+) {
+    let groups: Vec<_> = groups.collect();
+    let width = groups.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+    println!("\n{}", title.bold());
+    for (name, totals) in groups {
+        println!("  {:<width$}  {}", name, usage_totals_line(totals));
+    }
+}
+
+fn usage_totals_line(totals: &crate::ledger::UsageTotals) -> String {
+    let cost = if totals.unpriced_requests == totals.requests {
+        "n/a".to_string()
+    } else {
+        format!("${:.4}", totals.cost)
+    };
+
+    format!(
+        "{:>6} requests {:>12} tokens ({} prompt + {} completion)  {:>9}  {}",
+        totals.requests,
+        totals.total_tokens(),
+        totals.prompt_tokens,
+        totals.completion_tokens,
+        cost,
+        format!("{} ms avg", totals.average_latency_ms).dimmed()
+    )
+}
+
+/// Warn that a request will not fit the model's context window
+pub fn display_context_warning(overflow: &crate::context::ContextOverflow) {
+    eprintln!(
+        "{} {}",
+        "Context window exceeded:".yellow().bold(),
+        format!("{overflow}; the server will likely reject this request").dimmed()
+    );
+}
+
+/// Warn that a reply is still cut off after continuing it
+pub fn display_truncated_warning(completion_tokens: u32) {
+    eprintln!(
+        "{} {}",
+        "Reply truncated:".yellow().bold(),
+        format!(
+            "still cut off after {completion_tokens} completion tokens; \
+             raise continuation.max_total_tokens for longer replies"
+        )
+        .dimmed()
+    );
+}
+
+/// Report old turns removed from the history
+pub fn display_compaction(compaction: &crate::context::Compaction) {
+    let message = match compaction.summary_usage {
+        Some(_) => format!("Summarized {} old messages into a note", compaction.removed),
+        None => format!("Dropped {} old messages to stay within the context window", compaction.removed),
+    };
+    println!("{}", message.dimmed());
+}
+
+/// Show how much of the context window the history fills
+pub fn display_context_status(
+    budget: Option<&crate::context::ContextBudget>,
+    messages: &[crate::api::Message],
+    strategy: crate::context::ContextStrategy,
+) {
+    let fill = budget.map_or_else(
+        || "context window unknown (set context_window)".to_string(),
+        |budget| {
+            let tokens = budget.tokenizer().count_messages(messages);
+            let share = tokens as f64 * 100.0 / f64::from(budget.window());
+            format!("{tokens} of {} tokens ({share:.1}%)", budget.window())
+        },
+    );
+    println!(
+        "{} {} · {} messages · strategy {}",
+        "Context:".dimmed(),
+        fill,
+        messages.len(),
+        strategy
+    );
+}
+
+/// Warn when the remaining rate-limit quota is running low
+pub fn display_rate_limit_warning(info: &crate::retry::RateLimitInfo) {
+    let quota = |remaining: Option<u64>, limit: Option<u64>, reset: Option<std::time::Duration>, unit: &str| {
+        let reset = reset.map_or(String::new(), |r| format!(", resets in {}s", r.as_secs()));
+        Some(format!("{}/{} {}{}", remaining?, limit?, unit, reset))
+    };
+
+    let parts: Vec<String> = [
+        quota(info.remaining_requests, info.limit_requests, info.reset_requests, "requests"),
+        quota(info.remaining_tokens, info.limit_tokens, info.reset_tokens, "tokens"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    eprintln!(
+        "{} {}",
+        "Rate limit low:".yellow().bold(),
+        parts.join("; ").dimmed()
+    );
+}
+
 /// Create a spinner for loading states
 pub fn create_spinner(message: &str) -> ProgressBar {
     let pb = ProgressBar::new_spinner();
@@ -407,6 +737,20 @@ pub fn create_spinner(message: &str) -> ProgressBar {
     pb
 }
 
+/// Create a progress bar for a task with a known number of steps
+pub fn create_progress_bar(message: &str) -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.cyan} {prefix} [{bar:30.cyan/blue}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    pb.set_prefix(message.to_string());
+    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    pb
+}
+
 /// Clear the terminal screen
 pub fn clear_screen() {
     print!("\x1B[2J\x1B[1;1H");
@@ -422,7 +766,113 @@ pub fn show_help() {
     println!("  {}    - Show current session history", "history".cyan());
     println!("  {}      - Save conversation to file", "save".cyan());
     println!("  {}   - Change the model", "model <name>".cyan());
+    println!("  {} - Attach an image to the next message", "/image <path>".cyan());
+    println!("  {} - Set a sampling parameter, e.g. /set temperature 0.2", "/set <name> <value>".cyan());
+    println!("  {}    - Reset a sampling parameter to the configured value", "/unset <name>".cyan());
+    println!("  {}          - Keep the latest exchange when the history is compacted", "/pin".cyan());
+    println!("  {}      - Show how much of the context window the history fills", "/context".cyan());
+    println!("  {} - Choose what to do with old turns: drop, summarize or off", "/context <strategy>".cyan());
+    println!("  {}  - Compact the history now, optionally with another strategy", "/compact [strategy]".cyan());
+    println!("  {}     - Ask for n alternative replies and pick one (1 turns it off)", "/choices <n>".cyan());
+    println!("  {}      - Show the reasoning behind the last reply", "/thinking".cyan());
+    println!();
+}
+
+/// Shows the reasoning a model streams before its answer, apart from it
+///
+/// Collapsed, a spinner counts the reasoning as it arrives and a one-line
+/// summary is left in its place; expanded, the text is printed dimmed under
+/// a `Thinking:` heading. A spinner runs until the first piece arrives.
+pub struct ReasoningView {
+    display: crate::reasoning::ReasoningDisplay,
+    spinner: Option<ProgressBar>,
+    started: std::time::Instant,
+    text: String,
+}
+
+impl ReasoningView {
+    /// Start waiting for a reply
+    pub fn new(display: crate::reasoning::ReasoningDisplay) -> Self {
+        Self {
+            display,
+            spinner: Some(create_spinner("Thinking...")),
+            started: std::time::Instant::now(),
+            text: String::new(),
+        }
+    }
+
+    /// Add a piece of reasoning
+    pub fn push(&mut self, chunk: &str) {
+        use crate::reasoning::ReasoningDisplay;
+
+        let first = self.text.is_empty();
+        self.text.push_str(chunk);
+        match self.display {
+            ReasoningDisplay::Collapsed => {
+                if let Some(spinner) = &self.spinner {
+                    spinner.set_message(format!("Thinking... ({} words)", self.text.split_whitespace().count()));
+                }
+            }
+            ReasoningDisplay::Expanded => {
+                if first {
+                    if let Some(spinner) = self.spinner.take() {
+                        spinner.finish_and_clear();
+                    }
+                    println!();
+                    println!("{}", "Thinking:".dimmed().bold());
+                    print!("  {} ", "│".dimmed());
+                }
+                print!("{}", chunk.replace('\n', "\n  │ ").dimmed());
+                io::stdout().flush().unwrap();
+            }
+            ReasoningDisplay::Hidden => {}
+        }
+    }
+
+    /// End of the reasoning; leave the summary and get the full text
+    pub fn finish(mut self) -> String {
+        use crate::reasoning::ReasoningDisplay;
+
+        if let Some(spinner) = self.spinner.take() {
+            spinner.finish_and_clear();
+        }
+        if !self.text.is_empty() {
+            match self.display {
+                ReasoningDisplay::Collapsed => display_reasoning_summary(&self.text, Some(self.started.elapsed())),
+                ReasoningDisplay::Expanded => println!(),
+                ReasoningDisplay::Hidden => {}
+            }
+        }
+        self.text
+    }
+}
+
+/// Display the reasoning behind a reply that was not streamed
+pub fn display_reasoning(reasoning: &str, display: crate::reasoning::ReasoningDisplay) {
+    use crate::reasoning::ReasoningDisplay;
+
+    match display {
+        ReasoningDisplay::Collapsed => display_reasoning_summary(reasoning, None),
+        ReasoningDisplay::Expanded => {
+            println!();
+            println!("{}", "Thinking:".dimmed().bold());
+            for line in reasoning.trim_end().lines() {
+                println!("  {} {}", "│".dimmed(), line.dimmed());
+            }
+        }
+        ReasoningDisplay::Hidden => {}
+    }
+}
+
+/// One line standing in for collapsed reasoning
+fn display_reasoning_summary(reasoning: &str, elapsed: Option<std::time::Duration>) {
+    let words = reasoning.split_whitespace().count();
+    let summary = match elapsed {
+        Some(elapsed) => format!("▸ Thought for {:.0}s ({words} words)", elapsed.as_secs_f64()),
+        None => format!("▸ Thought before answering ({words} words)"),
+    };
     println!();
+    println!("{}", summary.dimmed());
 }
 
 /// Display streaming response header
@@ -0,0 +1,137 @@
+//! Token usage reported by the backends, with a local estimate as fallback
+
+use crate::api::Message;
+use crate::provider::{EventStream, StreamEvent};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+/// Tokens added per message for role and separators, as in OpenAI's chat format
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// Token usage of one request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Tokens in the request
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    /// Tokens in the reply
+    #[serde(default)]
+    pub completion_tokens: u32,
+    /// Prompt and completion tokens together
+    #[serde(default)]
+    pub total_tokens: u32,
+    /// Whether the counts were estimated locally because the server sent none
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+impl Usage {
+    /// Usage from prompt and completion counts
+    #[must_use]
+    pub const fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens.saturating_add(completion_tokens),
+            estimated: false,
+        }
+    }
+
+    /// Estimated usage of a request and its reply
+    #[must_use]
+    pub fn estimate(prompt_tokens: u32, reply: &Message) -> Self {
+        Self {
+            estimated: true,
+            ..Self::new(prompt_tokens, estimate_message_tokens(reply))
+        }
+    }
+
+    /// Fill in `total_tokens` when a server left it out
+    pub(crate) const fn normalized(mut self) -> Self {
+        if self.total_tokens == 0 {
+            self.total_tokens = self.prompt_tokens.saturating_add(self.completion_tokens);
+        }
+        self
+    }
+}
+
+impl AddAssign for Usage {
+    /// Sum the usage of several requests, estimated if any part was
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens = self.prompt_tokens.saturating_add(other.prompt_tokens);
+        self.completion_tokens = self
+            .completion_tokens
+            .saturating_add(other.completion_tokens);
+        self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+        self.estimated = self.estimated || other.estimated;
+    }
+}
+
+/// Rough token count of a text, at about four characters per token
+#[must_use]
+pub fn estimate_tokens(text: &str) -> u32 {
+    let chars = text.chars().count();
+    u32::try_from(chars.div_ceil(4)).unwrap_or(u32::MAX)
+}
+
+/// Rough token count of a message, including tool calls and formatting overhead
+pub fn estimate_message_tokens(message: &Message) -> u32 {
+    let tool_calls: u32 = message
+        .tool_calls
+        .iter()
+        .map(|call| {
+            estimate_tokens(&call.function.name)
+                .saturating_add(estimate_tokens(&call.function.arguments))
+        })
+        .fold(0, u32::saturating_add);
+
+    MESSAGE_OVERHEAD_TOKENS
+        .saturating_add(estimate_tokens(&message.content))
+        .saturating_add(tool_calls)
+}
+
+/// Rough token count of a request's messages
+pub fn estimate_prompt_tokens(messages: &[Message]) -> u32 {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+/// Make sure a stream reports usage, estimating it when the server does not
+///
+/// If the stream ends without a [`StreamEvent::Usage`], one is estimated from
+/// `prompt_tokens` and the streamed text and tool calls and emitted last.
+#[must_use]
+pub fn with_estimated_usage(events: EventStream, prompt_tokens: u32) -> EventStream {
+    let state = (events, Message::assistant(""), false, false);
+
+    Box::pin(futures_util::stream::unfold(
+        state,
+        move |(mut events, mut reply, mut reported, finished)| async move {
+            if finished {
+                return None;
+            }
+
+            match events.next().await {
+                Some(event) => {
+                    match &event {
+                        // Reasoning is generated too, so it counts as completion
+                        Ok(StreamEvent::Text(text) | StreamEvent::Reasoning(text)) => {
+                            reply.content.push_str(text);
+                        }
+                        Ok(StreamEvent::ToolCalls(calls)) => {
+                            reply.tool_calls.extend(calls.iter().cloned());
+                        }
+                        Ok(StreamEvent::Usage(_)) => reported = true,
+                        _ => {}
+                    }
+                    Some((event, (events, reply, reported, false)))
+                }
+                None if reported => None,
+                None => {
+                    let usage = Usage::estimate(prompt_tokens, &reply);
+                    Some((Ok(StreamEvent::Usage(usage)), (events, reply, true, true)))
+                }
+            }
+        },
+    ))
+}
@@ -0,0 +1,184 @@
+//! On-disk vector index searched by cosine similarity
+
+use crate::error::{AppError, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A stored text and its embedding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Where the text came from (a file path, `stdin`, ...)
+    pub source: String,
+    /// The embedded text
+    pub text: String,
+    /// Embedding of `text`
+    pub embedding: Vec<f32>,
+}
+
+/// A search match
+#[derive(Debug, Clone, Copy)]
+pub struct SearchHit<'a> {
+    /// Cosine similarity to the query, from -1 to 1
+    pub score: f32,
+    /// The matching entry
+    pub entry: &'a IndexEntry,
+}
+
+/// A collection of embeddings made with one model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorIndex {
+    /// Model the embeddings were made with; queries must use the same one
+    pub model: String,
+    /// Stored entries in insertion order
+    pub entries: Vec<IndexEntry>,
+}
+
+impl VectorIndex {
+    /// Create an empty index for a model
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Path of a named index under the data directory
+    #[must_use]
+    pub fn default_path(name: &str) -> PathBuf {
+        let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("llm-cli");
+        path.push("indexes");
+        path.push(format!("{name}.json"));
+        path
+    }
+
+    /// Load an index, or `None` if the file does not exist yet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        load_json(path).await
+    }
+
+    /// Save the index, creating parent directories as needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        save_json(self, path).await
+    }
+
+    /// Number of dimensions of the stored embeddings
+    #[must_use]
+    pub fn dimensions(&self) -> Option<usize> {
+        self.entries.first().map(|entry| entry.embedding.len())
+    }
+
+    /// Whether the same text from the same source is already present
+    #[must_use]
+    pub fn contains(&self, source: &str, text: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.source == source && entry.text == text)
+    }
+
+    /// Add an entry, returning `false` if it is already present
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the embedding has the wrong number of dimensions.
+    pub fn add(&mut self, entry: IndexEntry) -> Result<bool> {
+        self.check_dimensions(&entry.embedding)?;
+
+        if self.contains(&entry.source, &entry.text) {
+            return Ok(false);
+        }
+
+        self.entries.push(entry);
+        Ok(true)
+    }
+
+    /// The `k` entries most similar to a query embedding, best first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query has the wrong number of dimensions.
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchHit<'_>>> {
+        self.check_dimensions(query)?;
+
+        let mut hits: Vec<SearchHit<'_>> = self
+            .entries
+            .iter()
+            .map(|entry| SearchHit {
+                score: cosine_similarity(query, &entry.embedding),
+                entry,
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    fn check_dimensions(&self, embedding: &[f32]) -> Result<()> {
+        match self.dimensions() {
+            Some(dimensions) if dimensions != embedding.len() => {
+                Err(AppError::IndexError(format!(
+                    "embedding has {} dimensions but the index uses {} (model {})",
+                    embedding.len(),
+                    dimensions,
+                    self.model
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Read a JSON index file, or `None` if it does not exist yet
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed.
+pub async fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write a JSON index file, creating parent directories as needed
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub async fn save_json<T: Serialize + Sync>(value: &T, path: &Path) -> Result<()> {
+    let json = serde_json::to_string(value)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    tokio::fs::write(path, json).await?;
+    Ok(())
+}
+
+/// Cosine similarity of two vectors, or 0 if either is all zeros
+#[must_use]
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
@@ -0,0 +1,68 @@
+//! Image attachments for vision models
+
+use crate::api::ImageAttachment;
+use crate::error::{AppError, Result};
+use base64::Engine;
+use std::path::Path;
+
+/// Detect the MIME type of an image from its leading bytes
+///
+/// Only the formats accepted by the OpenAI, Anthropic and Ollama vision
+/// APIs are recognised: PNG, JPEG, GIF and WebP.
+#[must_use]
+pub fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Read an image file and encode it for sending to the model
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, is too large or is not a supported image.
+pub async fn load_image(path: &Path, max_bytes: u64) -> Result<ImageAttachment> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if size > max_bytes {
+        return Err(AppError::ImageError(format!(
+            "{} is {} bytes, over the {} byte limit",
+            path.display(),
+            size,
+            max_bytes
+        )));
+    }
+
+    let bytes = tokio::fs::read(path).await?;
+    let mime_type = sniff_mime_type(&bytes).ok_or_else(|| {
+        AppError::ImageError(format!(
+            "{} is not a PNG, JPEG, GIF or WebP image",
+            path.display()
+        ))
+    })?;
+
+    Ok(ImageAttachment {
+        path: path.display().to_string(),
+        mime_type: mime_type.to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+    })
+}
+
+/// Load several images, stopping at the first that fails
+///
+/// # Errors
+///
+/// Returns an error if any of the images cannot be loaded.
+pub async fn load_images(
+    paths: &[impl AsRef<Path> + Sync],
+    max_bytes: u64,
+) -> Result<Vec<ImageAttachment>> {
+    let mut images = Vec::with_capacity(paths.len());
+    for path in paths {
+        images.push(load_image(path.as_ref(), max_bytes).await?);
+    }
+    Ok(images)
+}
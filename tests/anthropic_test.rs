@@ -0,0 +1,308 @@
+//! Tests for the Anthropic Messages API backend
+
+#![allow(clippy::unwrap_used)]
+
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::Message;
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::{AppError, Result};
+use llm_cli::provider::{ChatRequest, Completion, EventStream, StreamEvent};
+use llm_cli::session::Session;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.usage.ledger = false;
+    config.provider = ProviderKind::Anthropic;
+    config.api_key = Some("test-key".to_string());
+    config.base_url = mock_server.uri();
+    config.model = "claude-sonnet-4-5".to_string();
+    config.max_tokens = 256;
+    config.timeout_seconds = 5;
+    config
+}
+
+/// Helper function to build an SSE event in the Messages API format
+fn sse_event(event: &str, data: &serde_json::Value) -> String {
+    format!("event: {event}\ndata: {data}\n\n")
+}
+
+/// Helper function to create a streaming response body from text deltas
+fn create_streaming_response(deltas: &[&str], stop_reason: &str) -> String {
+    let mut body = sse_event(
+        "message_start",
+        &serde_json::json!({
+            "type": "message_start",
+            "message": {"id": "msg_1", "type": "message", "role": "assistant", "content": []}
+        }),
+    );
+    body.push_str(&sse_event(
+        "content_block_start",
+        &serde_json::json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": {"type": "text", "text": ""}
+        }),
+    ));
+    body.push_str(&sse_event("ping", &serde_json::json!({"type": "ping"})));
+
+    for delta in deltas {
+        body.push_str(&sse_event(
+            "content_block_delta",
+            &serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": delta}
+            }),
+        ));
+    }
+
+    body.push_str(&sse_event(
+        "content_block_stop",
+        &serde_json::json!({"type": "content_block_stop", "index": 0}),
+    ));
+    body.push_str(&sse_event(
+        "message_delta",
+        &serde_json::json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": stop_reason},
+            "usage": {"output_tokens": 12}
+        }),
+    ));
+    body.push_str(&sse_event(
+        "message_stop",
+        &serde_json::json!({"type": "message_stop"}),
+    ));
+    body
+}
+
+/// Helper function to reduce a stream of events to its text, reporting truncation as an error
+fn text_chunks(events: EventStream) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text(text)) => Some(Ok(text)),
+                Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                    Some(Err(AppError::TokenLimitExceeded))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .boxed()
+}
+
+#[tokio::test]
+async fn test_anthropic_complete_sends_system_field() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(header("x-api-key", "test-key"))
+        .and(header("anthropic-version", "2023-06-01"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "system": "You are terse.",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": false
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "Hi there!"}],
+            "model": "claude-sonnet-4-5",
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 10, "output_tokens": 3}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![
+            Message::system("You are terse."),
+            Message::user("Hello"),
+        ]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Hi there!");
+}
+
+#[tokio::test]
+async fn test_anthropic_max_tokens_stop_reason() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "Cut o"}],
+            "stop_reason": "max_tokens"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Write an essay")]))
+        .await
+        .and_then(Completion::into_text);
+
+    assert!(matches!(result, Err(AppError::TokenLimitExceeded)));
+}
+
+#[tokio::test]
+async fn test_anthropic_error_response() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(serde_json::json!({
+            "type": "error",
+            "error": {"type": "rate_limit_error", "message": "Slow down"}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text);
+
+    assert!(matches!(result, Err(AppError::RateLimitExceeded)));
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(body_partial_json(serde_json::json!({"stream": true})))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(create_streaming_response(
+                    &["Hello", " from", " Claude", " 👋"],
+                    "end_turn",
+                ))
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut collected = String::new();
+    while let Some(chunk) = stream.next().await {
+        collected.push_str(&chunk.unwrap());
+    }
+
+    assert_eq!(collected, "Hello from Claude 👋");
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming_max_tokens() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(create_streaming_response(&["Partial"], "max_tokens"))
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut saw_limit = false;
+    while let Some(chunk) = stream.next().await {
+        if matches!(chunk, Err(AppError::TokenLimitExceeded)) {
+            saw_limit = true;
+        }
+    }
+
+    assert!(saw_limit);
+}
+
+#[tokio::test]
+async fn test_anthropic_session_flow_through_provider() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(body_partial_json(serde_json::json!({
+            "system": "Be brief.",
+            "messages": [
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "Hello!"},
+                {"role": "user", "content": "What is Rust?"}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [{"type": "text", "text": "A systems language."}],
+            "stop_reason": "end_turn"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let provider = llm_cli::provider::from_config(create_test_config(&mock_server)).unwrap();
+    assert_eq!(provider.name(), "anthropic");
+
+    let mut session = Session::new("claude-sonnet-4-5".to_string());
+    session.add_message(Message::system("Be brief."));
+    session.add_message(Message::user("Hi"));
+    session.add_message(Message::assistant("Hello!"));
+    session.add_message(Message::user("What is Rust?"));
+
+    let response = provider
+        .complete_chat(ChatRequest::new(session.history().to_vec()))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+    assert_eq!(response, "A systems language.");
+}
+
+#[tokio::test]
+async fn test_anthropic_list_models() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(header("x-api-key", "test-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [
+                {"id": "claude-sonnet-4-5", "type": "model", "display_name": "Claude Sonnet 4.5"},
+                {"id": "claude-haiku-4-5", "type": "model", "display_name": "Claude Haiku 4.5"}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = AnthropicClient::new(create_test_config(&mock_server)).unwrap();
+    let models = client.list_models().await.unwrap();
+
+    assert_eq!(models, vec!["claude-sonnet-4-5", "claude-haiku-4-5"]);
+}
@@ -0,0 +1,321 @@
+//! Tests for the audio transcription and speech endpoints
+
+#![allow(clippy::unwrap_used)]
+
+use indicatif::ProgressBar;
+use llm_cli::api::OpenAIClient;
+use llm_cli::audio::{self, SpeechOptions, TranscriptionOptions, MAX_SPEECH_CHARS};
+use llm_cli::cli::{SpeechFormat, TranscriptFormat};
+use llm_cli::config::{Config, ProviderKind};
+use std::path::{Path, PathBuf};
+use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const WAV_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny.wav");
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create transcription options
+fn options(format: TranscriptFormat) -> TranscriptionOptions {
+    TranscriptionOptions {
+        model: "whisper-1".to_string(),
+        language: None,
+        prompt: None,
+        format,
+    }
+}
+
+#[tokio::test]
+async fn test_uploads_multipart_form() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/audio/transcriptions"))
+        .and(header("authorization", "Bearer test-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello from the fixture.\n"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let options = TranscriptionOptions {
+        language: Some("en".to_string()),
+        prompt: Some("Fixture".to_string()),
+        ..options(TranscriptFormat::Text)
+    };
+    let transcript = audio::transcribe(&client, Path::new(WAV_FIXTURE), &options)
+        .await
+        .unwrap();
+    assert_eq!(transcript, "Hello from the fixture.");
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let content_type = requests[0]
+        .headers
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(content_type.starts_with("multipart/form-data; boundary="));
+
+    let body = String::from_utf8_lossy(&requests[0].body);
+    for field in [
+        "name=\"model\"\r\n\r\nwhisper-1\r\n",
+        "name=\"response_format\"\r\n\r\ntext\r\n",
+        "name=\"language\"\r\n\r\nen\r\n",
+        "name=\"prompt\"\r\n\r\nFixture\r\n",
+        "name=\"file\"; filename=\"tiny.wav\"",
+    ] {
+        assert!(body.contains(field), "missing {field:?} in {body}");
+    }
+    assert!(body.contains("RIFF"));
+}
+
+#[tokio::test]
+async fn test_output_formats() {
+    let mock_server = MockServer::start().await;
+    let srt = "1\n00:00:00,000 --> 00:00:01,000\nHello\n\n";
+
+    Mock::given(method("POST"))
+        .and(path("/v1/audio/transcriptions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(srt))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/audio/transcriptions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"text": "Hello", "language": "english"})),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let file = Path::new(WAV_FIXTURE);
+
+    let output = audio::transcribe(&client, file, &options(TranscriptFormat::Srt))
+        .await
+        .unwrap();
+    assert_eq!(output, srt.trim_end());
+
+    let output = audio::transcribe(&client, file, &options(TranscriptFormat::Json))
+        .await
+        .unwrap();
+    let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value["language"], "english");
+
+    // A server that ignores response_format=text still yields plain text
+    let output = audio::transcribe(&client, file, &options(TranscriptFormat::Text))
+        .await
+        .unwrap();
+    assert_eq!(output, "Hello");
+}
+
+#[tokio::test]
+async fn test_azure_deployment_and_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/openai/deployments/my-whisper/audio/transcriptions"))
+        .and(query_param("api-version", "2024-10-21"))
+        .and(header("api-key", "test-key"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": {
+                "message": "Invalid file format.",
+                "type": "invalid_request_error",
+                "code": null
+            }
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Azure;
+    config.azure.api_version = "2024-10-21".to_string();
+    config
+        .azure
+        .deployments
+        .insert("whisper-1".to_string(), "my-whisper".to_string());
+
+    let client = OpenAIClient::new(config).unwrap();
+    let error = audio::transcribe(
+        &client,
+        Path::new(WAV_FIXTURE),
+        &options(TranscriptFormat::Text),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("Invalid file format."));
+}
+
+/// Helper function to create speech options
+fn speech_options(format: SpeechFormat) -> SpeechOptions {
+    SpeechOptions {
+        model: "tts-1".to_string(),
+        voice: "nova".to_string(),
+        format,
+        speed: None,
+    }
+}
+
+/// Helper function to pick a scratch output file
+fn output_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "llm-cli-speech-{}.{extension}",
+        uuid::Uuid::new_v4()
+    ))
+}
+
+/// Helper function to build a text that needs two speech requests
+fn two_part_text() -> (String, String, String) {
+    let first = format!("{}.", "a".repeat(3000));
+    let second = format!("{}!", "b ".repeat(1500).trim_end());
+    (format!("{first} {second}"), first, second)
+}
+
+/// Helper function to build a mono 16-bit WAV file holding `samples`
+fn wav(samples: &[u8]) -> Vec<u8> {
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend_from_slice(&u32::try_from(36 + samples.len()).unwrap().to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&[1, 0, 1, 0]);
+    bytes.extend_from_slice(&24_000u32.to_le_bytes());
+    bytes.extend_from_slice(&48_000u32.to_le_bytes());
+    bytes.extend_from_slice(&[2, 0, 16, 0]);
+    bytes.extend_from_slice(b"data");
+    // Streamed WAV from the API has an unknown data size
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    bytes.extend_from_slice(samples);
+    bytes
+}
+
+#[test]
+fn test_split_sentences() {
+    let text = "First sentence. Second one!\nThird line? \"Quoted.\" Last";
+    assert_eq!(
+        audio::split_sentences(text, 1000),
+        [text.replace('\n', " ")]
+    );
+    assert_eq!(
+        audio::split_sentences(text, 30),
+        [
+            "First sentence. Second one!",
+            "Third line? \"Quoted.\" Last"
+        ]
+    );
+
+    // A full stop without a following space does not end a sentence
+    assert_eq!(
+        audio::split_sentences("Pi is 3.14 roughly", 12),
+        ["Pi is 3.14", "roughly"]
+    );
+
+    // A word longer than the limit is cut
+    assert_eq!(audio::split_sentences("abcdefgh", 3), ["abc", "def", "gh"]);
+    assert!(audio::split_sentences("  \n ", 10).is_empty());
+
+    let (text, first, second) = two_part_text();
+    let chunks = audio::split_sentences(&text, MAX_SPEECH_CHARS);
+    assert_eq!(chunks, [first, second]);
+}
+
+#[tokio::test]
+async fn test_speak_writes_audio() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/audio/speech"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "tts-1",
+            "input": "Hello there.",
+            "voice": "nova",
+            "response_format": "mp3",
+            "speed": 1.5
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"ID3 fake mp3 frames".to_vec()))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let output = output_path("mp3");
+    let options = SpeechOptions {
+        speed: Some(1.5),
+        ..speech_options(SpeechFormat::Mp3)
+    };
+    let progress = ProgressBar::hidden();
+
+    let bytes = audio::speak(&client, "Hello there.", &output, &options, &progress)
+        .await
+        .unwrap();
+
+    assert_eq!(bytes, 19);
+    assert_eq!(std::fs::read(&output).unwrap(), b"ID3 fake mp3 frames");
+    assert_eq!(progress.position(), 1);
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[tokio::test]
+async fn test_speak_joins_wav_segments() {
+    let mock_server = MockServer::start().await;
+    let (text, first, second) = two_part_text();
+
+    for (input, samples) in [(first, [1u8, 0, 2, 0]), (second, [3u8, 0, 4, 0])] {
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/speech"))
+            .and(body_partial_json(
+                serde_json::json!({"input": input, "response_format": "wav"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(wav(&samples)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let output = output_path("wav");
+    let progress = ProgressBar::hidden();
+
+    audio::speak(
+        &client,
+        &text,
+        &output,
+        &speech_options(SpeechFormat::Wav),
+        &progress,
+    )
+    .await
+    .unwrap();
+
+    // One header, both segments' samples, and sizes that match the file
+    let mut expected = wav(&[1, 0, 2, 0, 3, 0, 4, 0]);
+    expected[4..8].copy_from_slice(&44u32.to_le_bytes());
+    expected[40..44].copy_from_slice(&8u32.to_le_bytes());
+    assert_eq!(std::fs::read(&output).unwrap(), expected);
+    assert_eq!(progress.position(), 2);
+
+    // FLAC streams cannot be concatenated
+    let error = audio::speak(
+        &client,
+        &text,
+        &output,
+        &speech_options(SpeechFormat::Flac),
+        &progress,
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("FLAC"));
+
+    std::fs::remove_file(&output).unwrap();
+}
@@ -0,0 +1,150 @@
+//! Tests for Azure OpenAI deployment support
+
+#![allow(clippy::unwrap_used)]
+
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::{AppError, Result};
+use llm_cli::provider::{ChatRequest, Completion, EventStream, StreamEvent};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create an Azure config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.usage.ledger = false;
+    config.provider = ProviderKind::Azure;
+    config.api_key = Some("azure-key".to_string());
+    config.base_url = mock_server.uri();
+    config.model = "gpt-4o".to_string();
+    config
+        .azure
+        .deployments
+        .insert("gpt-4o".to_string(), "prod-gpt4o".to_string());
+    config.azure.api_version = "2024-10-21".to_string();
+    config.timeout_seconds = 5;
+    config
+}
+
+/// Helper function to reduce a stream of events to its text, reporting truncation as an error
+fn text_chunks(events: EventStream) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text(text)) => Some(Ok(text)),
+                Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                    Some(Err(AppError::TokenLimitExceeded))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .boxed()
+}
+
+#[tokio::test]
+async fn test_azure_complete_uses_deployment_url() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/openai/deployments/prod-gpt4o/chat/completions"))
+        .and(query_param("api-version", "2024-10-21"))
+        .and(header("api-key", "azure-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_700_000_000,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hello from Azure"},
+                "finish_reason": "stop"
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hi")]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Hello from Azure");
+}
+
+#[tokio::test]
+async fn test_azure_streaming_uses_deployment_url() {
+    let mock_server = MockServer::start().await;
+
+    let body = [
+        r#"data: {"id":"1","object":"chat.completion.chunk","created":1,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":"Streamed"},"finish_reason":null}]}"#,
+        r#"data: {"id":"1","object":"chat.completion.chunk","created":1,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":" reply"},"finish_reason":"stop"}]}"#,
+        "data: [DONE]",
+    ]
+    .join("\n\n");
+
+    Mock::given(method("POST"))
+        .and(path("/openai/deployments/prod-gpt4o/chat/completions"))
+        .and(query_param("api-version", "2024-10-21"))
+        .and(header("api-key", "azure-key"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(format!("{body}\n\n"))
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hi")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut collected = String::new();
+    while let Some(chunk) = stream.next().await {
+        collected.push_str(&chunk.unwrap());
+    }
+
+    assert_eq!(collected, "Streamed reply");
+}
+
+#[test]
+fn test_azure_url_resolution() {
+    let mut config = Config {
+        provider: ProviderKind::Azure,
+        ..Config::default()
+    };
+    config.azure.resource = Some("contoso".to_string());
+    config
+        .azure
+        .deployments
+        .insert("gpt-4o".to_string(), "prod-gpt4o".to_string());
+
+    config.model = "gpt-4o".to_string();
+    assert_eq!(
+        config.api_url(),
+        "https://contoso.openai.azure.com/openai/deployments/prod-gpt4o/chat/completions?api-version=2024-10-21"
+    );
+
+    // Unmapped models use their own name as the deployment
+    config.model = "gpt-4o-mini".to_string();
+    assert_eq!(config.azure_deployment(), "gpt-4o-mini");
+    assert!(config.api_url().contains("/deployments/gpt-4o-mini/"));
+}
+
+#[test]
+fn test_azure_requires_resource_or_base_url() {
+    let mut config = Config {
+        provider: ProviderKind::Azure,
+        ..Config::default()
+    };
+    config.api_key = Some("azure-key".to_string());
+
+    assert!(OpenAIClient::new(config).is_err());
+}
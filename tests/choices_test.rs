@@ -0,0 +1,166 @@
+//! Tests for asking for several alternative replies at once
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::{ChatRequest, Provider};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to build a completion response with the given choices
+fn completion(choices: &[(u32, &str, &str)], completion_tokens: u32) -> ResponseTemplate {
+    let choices: Vec<_> = choices
+        .iter()
+        .map(|(index, content, finish_reason)| {
+            serde_json::json!({
+                "index": index,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": finish_reason
+            })
+        })
+        .collect();
+
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "gpt-4o",
+        "choices": choices,
+        "usage": {"prompt_tokens": 10, "completion_tokens": completion_tokens, "total_tokens": 10 + completion_tokens}
+    }))
+}
+
+#[tokio::test]
+async fn test_choices_come_from_one_request() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({"n": 3})))
+        .respond_with(completion(
+            &[
+                (2, "Third", "stop"),
+                (0, "First", "stop"),
+                (1, "Second", "length"),
+            ],
+            30,
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Name a colour")]);
+    let alternatives = client.complete_choices(request, 3).await.unwrap();
+
+    let contents: Vec<_> = alternatives
+        .choices
+        .iter()
+        .map(|c| c.message.content.as_str())
+        .collect();
+    assert_eq!(contents, ["First", "Second", "Third"]);
+    assert_eq!(
+        alternatives.choices[1].finish_reason.as_deref(),
+        Some("length")
+    );
+    assert_eq!(alternatives.usage.completion_tokens, 30);
+    assert_eq!(alternatives.usage.prompt_tokens, 10);
+}
+
+#[tokio::test]
+async fn test_single_choice_does_not_send_n() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion(&[(0, "Only", "stop")], 5))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Hello")]);
+    let alternatives = client.complete_choices(request, 1).await.unwrap();
+    assert_eq!(alternatives.choices.len(), 1);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert!(body.get("n").is_none());
+}
+
+#[tokio::test]
+async fn test_server_ignoring_n_gets_one_request_per_missing_choice() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion(&[(0, "Red", "stop")], 4))
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Name a colour")]);
+    let alternatives = client.complete_choices(request, 3).await.unwrap();
+
+    assert_eq!(alternatives.choices.len(), 3);
+    assert_eq!(alternatives.usage.completion_tokens, 12);
+    assert_eq!(alternatives.usage.prompt_tokens, 30);
+
+    // Only the first request asks for several choices
+    let requests = mock_server.received_requests().await.unwrap();
+    let asked: Vec<_> = requests
+        .iter()
+        .map(|request| {
+            serde_json::from_slice::<serde_json::Value>(&request.body).unwrap()["n"].clone()
+        })
+        .collect();
+    assert_eq!(asked.iter().filter(|n| **n == 3).count(), 1);
+    assert_eq!(asked.iter().filter(|n| n.is_null()).count(), 2);
+}
+
+#[tokio::test]
+async fn test_backends_without_n_fan_out() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {"role": "assistant", "content": "Blue"},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 8,
+            "eval_count": 2
+        })))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+    config.model = "llama3.2".to_string();
+    let client = OllamaClient::new(config).unwrap();
+
+    let request = ChatRequest::new(vec![Message::user("Name a colour")]);
+    let alternatives = client.complete_choices(request, 2).await.unwrap();
+
+    assert_eq!(alternatives.choices.len(), 2);
+    assert!(alternatives
+        .choices
+        .iter()
+        .all(|c| c.message.content == "Blue"));
+    assert_eq!(alternatives.usage.completion_tokens, 4);
+}
@@ -1,6 +1,7 @@
 //! Configuration tests
 
-use llm_cli::config::Config;
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::AppError;
 
 #[test]
 fn test_config_with_api_key() {
@@ -95,3 +96,81 @@ fn test_default_config_values() {
     assert!(!config.debug);
 }
 
+#[test]
+fn test_provider_defaults_to_openai() {
+    let config: Config = toml::from_str("model = \"gpt-4o\"").unwrap();
+    assert_eq!(config.provider, ProviderKind::OpenAI);
+
+    let config: Config = toml::from_str("provider = \"openai\"").unwrap();
+    assert_eq!(config.provider, ProviderKind::OpenAI);
+}
+
+#[test]
+fn test_provider_base_url_defaults() {
+    let mut config = Config::test_config();
+    assert_eq!(config.provider_base_url(), "https://api.openai.com");
+
+    config.provider = ProviderKind::Ollama;
+    assert_eq!(config.provider_base_url(), "http://localhost:11434");
+    assert!(config.is_local_service());
+    assert!(config.validate().is_ok());
+
+    config.provider = ProviderKind::Anthropic;
+    assert_eq!(config.provider_base_url(), "https://api.anthropic.com");
+    assert!(config.validate().is_err());
+
+    // An explicit base URL always wins
+    config.base_url = "http://127.0.0.1:8080/".to_string();
+    assert_eq!(config.provider_base_url(), "http://127.0.0.1:8080");
+}
+
+#[test]
+fn test_ollama_options_from_toml() {
+    let config: Config = toml::from_str(
+        r#"
+provider = "ollama"
+
+[ollama]
+keep_alive = -1
+raw = true
+
+[ollama.options]
+num_ctx = 16384
+temperature = 0.2
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.provider, ProviderKind::Ollama);
+    assert_eq!(config.ollama.options["num_ctx"], 16384);
+    assert_eq!(config.ollama.keep_alive, Some(serde_json::json!(-1)));
+    assert!(config.ollama.raw);
+}
+
+#[test]
+fn test_api_key_comes_from_the_provider_variable() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+    };
+
+    let config = Config::default()
+        .with_env(env(&[("OPENAI_API_KEY", "sk-openai")]))
+        .unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("sk-openai"));
+
+    // The OpenAI key is never sent to another vendor
+    let result = Config::default().with_env(env(&[
+        ("LLM_PROVIDER", "anthropic"),
+        ("OPENAI_API_KEY", "sk-openai"),
+    ]));
+    assert!(matches!(result, Err(AppError::ApiKeyNotFound)));
+
+    let config = Config::default()
+        .with_env(env(&[
+            ("LLM_PROVIDER", "anthropic"),
+            ("OPENAI_API_KEY", "sk-openai"),
+            ("ANTHROPIC_API_KEY", "sk-ant"),
+        ]))
+        .unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("sk-ant"));
+}
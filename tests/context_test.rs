@@ -0,0 +1,183 @@
+//! Tests for compacting chat history to fit the context window
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::{Message, OpenAIClient, Role, ToolCall};
+use llm_cli::config::{Config, ContextConfig};
+use llm_cli::context::{self, ContextBudget, ContextManager, ContextStrategy};
+use llm_cli::provider::ChatRequest;
+use llm_cli::session::Session;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(base_url: String) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        base_url,
+        "gpt-4".to_string(),
+        100,
+    )
+}
+
+/// Helper function to build a conversation of `turns` question and answer pairs
+fn conversation(turns: usize) -> Vec<Message> {
+    let mut messages = vec![Message::system("You are terse.")];
+    for turn in 0..turns {
+        messages.push(Message::user(format!("question {turn}")));
+        messages.push(Message::assistant(format!("answer {turn}")));
+    }
+    messages
+}
+
+/// Helper function to list the contents of a conversation
+fn contents(messages: &[Message]) -> Vec<&str> {
+    messages.iter().map(|m| m.content.as_str()).collect()
+}
+
+#[test]
+fn test_drop_keeps_system_prompt_pins_and_recent_turns() {
+    let mut session = Session::new("gpt-4".to_string());
+    session.messages = conversation(2);
+    assert_eq!(session.pin_last_turn(), 2);
+
+    session.add_message(Message::user("read the file"));
+    session.add_message(Message::assistant_with_tool_calls(
+        "",
+        vec![ToolCall::function(
+            "call_1",
+            "read_file",
+            r#"{"path":"a.txt"}"#,
+        )],
+    ));
+    session.add_message(Message::tool("call_1", "contents"));
+    session.add_message(Message::assistant("done"));
+    session.add_message(Message::user("question 3"));
+    session.add_message(Message::assistant("answer 3"));
+
+    let compaction = context::drop_oldest_turns(&mut session.messages, 1, |_| false).unwrap();
+    assert_eq!(compaction.removed, 6);
+    assert_eq!(compaction.summary_usage, None);
+    assert_eq!(
+        contents(&session.messages),
+        [
+            "You are terse.",
+            "question 1",
+            "answer 1",
+            "question 3",
+            "answer 3"
+        ]
+    );
+
+    // Pins are saved with the session but never sent
+    let saved = serde_json::to_value(&session).unwrap();
+    assert_eq!(saved["messages"][1]["pinned"], true);
+    assert!(saved["messages"][3].get("pinned").is_none());
+
+    // Nothing is left to drop
+    assert!(context::drop_oldest_turns(&mut session.messages, 1, |_| false).is_none());
+}
+
+#[test]
+fn test_drop_stops_once_enough_is_freed() {
+    let mut messages = conversation(4);
+
+    let mut dropped_turns = 0;
+    context::drop_oldest_turns(&mut messages, 1, |_| {
+        dropped_turns += 1;
+        dropped_turns == 2
+    });
+
+    assert_eq!(
+        contents(&messages),
+        [
+            "You are terse.",
+            "question 2",
+            "answer 2",
+            "question 3",
+            "answer 3"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_summarize_replaces_old_turns_with_a_note() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_string_contains("User: question 0"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "They asked two questions."},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 50, "completion_tokens": 6, "total_tokens": 56}
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(mock_server.uri())).unwrap();
+    let mut messages = conversation(3);
+
+    let compaction = context::summarize_turns(&client, &mut messages, 1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(compaction.removed, 4);
+    assert_eq!(compaction.summary_usage.unwrap().total_tokens, 56);
+
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[1].role, Role::System);
+    assert_eq!(
+        messages[1].content,
+        format!("{}\n\nThey asked two questions.", context::SUMMARY_HEADING)
+    );
+    assert_eq!(contents(&messages[2..]), ["question 2", "answer 2"]);
+}
+
+#[tokio::test]
+async fn test_fit_compacts_only_near_the_window() {
+    let mut config = create_test_config("http://localhost".to_string());
+    config.context_window = Some(300);
+    let settings = ContextConfig {
+        threshold: 0.5,
+        keep_turns: 1,
+        ..ContextConfig::default()
+    };
+    let budget = ContextBudget::from_config(&config).unwrap();
+    let manager = ContextManager::new(budget, settings);
+    let client = OpenAIClient::new(config).unwrap();
+
+    // 100 tokens are reserved for the reply, leaving 50 of the 150 allowed
+    let mut messages = conversation(1);
+    let request = ChatRequest::new(messages.clone());
+    assert!(manager
+        .fit(&client, &mut messages, &request, ContextStrategy::Drop)
+        .await
+        .is_none());
+
+    let mut messages = conversation(6);
+    let request = ChatRequest::new(messages.clone());
+    assert!(manager
+        .fit(&client, &mut messages, &request, ContextStrategy::Off)
+        .await
+        .is_none());
+
+    let compaction = manager
+        .fit(&client, &mut messages, &request, ContextStrategy::Drop)
+        .await
+        .unwrap();
+    assert!(compaction.removed > 0);
+
+    let tokenizer = manager.budget().unwrap().tokenizer();
+    assert!(tokenizer.count_messages(&messages) + 100 <= 150);
+    assert_eq!(messages[0].content, "You are terse.");
+    assert_eq!(messages.last().unwrap().content, "answer 5");
+}
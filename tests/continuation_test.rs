@@ -0,0 +1,287 @@
+//! Tests for continuing replies cut off by the token limit
+
+#![allow(clippy::unwrap_used)]
+
+use futures_util::StreamExt;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ContinuationConfig};
+use llm_cli::continuation::{self, Stitcher};
+use llm_cli::error::AppError;
+use llm_cli::provider::{ChatRequest, StreamEvent};
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create continuation settings
+const fn settings(max_total_tokens: u32) -> ContinuationConfig {
+    ContinuationConfig {
+        enabled: true,
+        max_total_tokens,
+    }
+}
+
+/// Helper function to build a completion response
+fn completion(content: &str, finish_reason: &str, completion_tokens: u32) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": finish_reason
+        }],
+        "usage": {"prompt_tokens": 10, "completion_tokens": completion_tokens, "total_tokens": 10 + completion_tokens}
+    }))
+}
+
+/// Helper function to build a streamed response
+fn stream(content: &str, finish_reason: &str) -> ResponseTemplate {
+    let body = [
+        serde_json::json!({"choices": [{"index": 0, "delta": {"content": content}, "finish_reason": null}]}).to_string(),
+        serde_json::json!({"choices": [{"index": 0, "delta": {}, "finish_reason": finish_reason}]}).to_string(),
+        "[DONE]".to_string(),
+    ]
+    .iter()
+    .map(|data| format!("data: {data}\n\n"))
+    .collect::<Vec<_>>()
+    .concat();
+    ResponseTemplate::new(200).set_body_string(body)
+}
+
+#[test]
+fn test_stitch_joins_pieces() {
+    assert_eq!(
+        continuation::stitch("The quick brown fo", "x jumps."),
+        "The quick brown fox jumps."
+    );
+
+    // A repeat of the end of the reply is dropped
+    let text = "First sentence. Then the second sentence starts";
+    assert_eq!(
+        continuation::stitch(text, "the second sentence starts here."),
+        "First sentence. Then the second sentence starts here."
+    );
+
+    // Short coincidental matches are kept
+    assert_eq!(continuation::stitch("one and", "and two"), "one andand two");
+}
+
+#[test]
+fn test_stitch_drops_reopened_code_fence() {
+    let text = "Here it is:\n\n```rust\nfn main() {\n";
+    let continued = continuation::stitch(text, "```rust\n    println!(\"hi\");\n}\n```\n");
+    assert_eq!(
+        continued,
+        "Here it is:\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n"
+    );
+
+    // Outside a code block a fence in the continuation is kept
+    let continued = continuation::stitch("Intro.\n", "```sh\nls\n```\n");
+    assert_eq!(continued, "Intro.\n```sh\nls\n```\n");
+}
+
+#[test]
+fn test_stitcher_holds_back_only_the_start() {
+    let mut stitcher = Stitcher::new("Some text\n```python\nx = 1\n");
+    assert_eq!(stitcher.push("```py"), "");
+    assert_eq!(stitcher.push("thon\n"), "");
+
+    let long_line = "y = 2  # ".to_string() + &"a".repeat(300) + "\n";
+    assert_eq!(stitcher.push(&long_line), long_line);
+    assert_eq!(stitcher.push("```\n"), "```\n");
+    assert_eq!(stitcher.finish(), "");
+}
+
+#[tokio::test]
+async fn test_complete_continues_until_finished() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion("Roses are red, violets", "length", 8))
+        .with_priority(5)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_string_contains("Roses are red, violets"))
+        .and(body_string_contains("Continue exactly where it stopped"))
+        .respond_with(completion(" are blue.", "stop", 4))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("A poem")]);
+    let completion = continuation::complete(&client, request, &settings(1000))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        completion.message.content,
+        "Roses are red, violets are blue."
+    );
+    assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+    assert!(!completion.message.truncated);
+    assert_eq!(completion.usage.completion_tokens, 12);
+    assert_eq!(completion.usage.prompt_tokens, 20);
+}
+
+#[tokio::test]
+async fn test_complete_marks_truncated_at_the_limit() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion("and on", "length", 50))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Go on forever")]);
+    let completion = continuation::complete(&client, request, &settings(100))
+        .await
+        .unwrap();
+
+    assert_eq!(completion.message.content, "and onand on");
+    assert_eq!(completion.finish_reason.as_deref(), Some("length"));
+    assert!(completion.message.truncated);
+
+    // The flag is saved with a session but never sent
+    let saved = serde_json::to_value(&completion.message).unwrap();
+    assert_eq!(saved["truncated"], true);
+}
+
+#[tokio::test]
+async fn test_disabled_continuation_fails_as_before() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion("Partial", "length", 100))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Hello")]);
+    let result = continuation::complete(&client, request, &ContinuationConfig::default()).await;
+
+    assert!(matches!(result, Err(AppError::TokenLimitExceeded)));
+}
+
+#[tokio::test]
+async fn test_stream_chains_continuations() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(stream("Once upon a ", "length"))
+        .with_priority(5)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_string_contains("Continue exactly where it stopped"))
+        .respond_with(stream("time.", "stop"))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("A story")]);
+    let mut events = continuation::stream(&client, request, &settings(1000))
+        .await
+        .unwrap();
+
+    let mut text = String::new();
+    let mut finishes = Vec::new();
+    let mut usages = 0;
+    while let Some(event) = events.next().await {
+        match event.unwrap() {
+            StreamEvent::Text(chunk) => text.push_str(&chunk),
+            StreamEvent::Finish(reason) => finishes.push(reason),
+            StreamEvent::Usage(_) => usages += 1,
+            StreamEvent::ToolCalls(_) | StreamEvent::Reasoning(_) => unreachable!(),
+        }
+    }
+
+    assert_eq!(text, "Once upon a time.");
+    assert_eq!(finishes, ["stop"]);
+    assert_eq!(usages, 2);
+}
+
+#[tokio::test]
+async fn test_streamed_query_keeps_a_reply_truncated_at_the_limit() {
+    let mock_server = MockServer::start().await;
+
+    let body = [
+        serde_json::json!({"choices": [{"index": 0, "delta": {"content": "and on "}, "finish_reason": null}]}),
+        serde_json::json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "length"}]}),
+        serde_json::json!({"choices": [], "usage": {"prompt_tokens": 10, "completion_tokens": 50, "total_tokens": 60}}),
+    ]
+    .iter()
+    .map(|chunk| format!("data: {chunk}\n\n"))
+    .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+    .collect::<String>();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let home = std::env::temp_dir().join(format!("llm-cli-continuation-{}", std::process::id()));
+    std::fs::create_dir_all(home.join("config/llm-cli")).unwrap();
+    std::fs::write(
+        home.join("config/llm-cli/config.toml"),
+        format!(
+            "base_url = \"{}\"\nmodel = \"gpt-4o\"\n\n[continuation]\nmax_total_tokens = 100\n\n[usage]\nledger = false\n",
+            mock_server.uri()
+        ),
+    )
+    .unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_llm-cli"))
+        .args([
+            "--auto-continue",
+            "query",
+            "--stream",
+            "--format",
+            "json",
+            "Go on forever",
+        ])
+        .env("OPENAI_API_KEY", "test-key")
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("HOME", &home)
+        .output()
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    let reply: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(reply["response"], "and on and on ");
+    assert!(stderr.contains("Reply truncated"), "{stderr}");
+    assert!(!stderr.contains("exceeded maximum token limit"), "{stderr}");
+}
@@ -0,0 +1,166 @@
+//! Tests for the embeddings endpoint and the local vector index
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::OpenAIClient;
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::embeddings;
+use llm_cli::error::AppError;
+use llm_cli::vector_index::{self, IndexEntry, VectorIndex};
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create an entry
+fn entry(source: &str, text: &str, embedding: &[f32]) -> IndexEntry {
+    IndexEntry {
+        source: source.to_string(),
+        text: text.to_string(),
+        embedding: embedding.to_vec(),
+    }
+}
+
+#[tokio::test]
+async fn test_embeds_in_batches_and_keeps_order() {
+    let mock_server = MockServer::start().await;
+
+    // The server may list embeddings out of order; `index` says where they belong
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .and(header("authorization", "Bearer test-key"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "text-embedding-3-small",
+            "input": ["one", "two"]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "object": "list",
+            "data": [
+                {"object": "embedding", "index": 1, "embedding": [0.0, 2.0]},
+                {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]}
+            ],
+            "model": "text-embedding-3-small",
+            "usage": {"prompt_tokens": 2, "total_tokens": 2}
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .and(body_partial_json(serde_json::json!({"input": ["three"]})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{"index": 0, "embedding": [3.0, 3.0]}]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let inputs = ["one", "two", "three"].map(String::from);
+    let vectors = embeddings::embed(&client, "text-embedding-3-small", &inputs, 2)
+        .await
+        .unwrap();
+
+    assert_eq!(vectors, [vec![1.0, 0.0], vec![0.0, 2.0], vec![3.0, 3.0]]);
+}
+
+#[tokio::test]
+async fn test_ollama_uses_openai_compatible_route() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .and(body_partial_json(
+            serde_json::json!({"model": "nomic-embed-text"}),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{"index": 0, "embedding": [0.5]}]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // A stock local Ollama has no API key
+    let mut config = Config::test_config_with(None, mock_server.uri(), "llama3.2".to_string(), 100);
+    config.provider = ProviderKind::Ollama;
+    let client = OpenAIClient::new(config).unwrap();
+    let vectors = embeddings::embed(&client, "nomic-embed-text", &["hi".to_string()], 64)
+        .await
+        .unwrap();
+
+    assert_eq!(vectors, [vec![0.5]]);
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests[0].headers.get("authorization").is_none());
+}
+
+#[tokio::test]
+async fn test_missing_embeddings_are_an_error() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": [{"index": 0, "embedding": [0.5]}]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let inputs = ["a", "b"].map(String::from);
+    let error = embeddings::embed(&client, "text-embedding-3-small", &inputs, 64)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("Expected 2 embeddings, got 1"));
+}
+
+#[test]
+fn test_cosine_similarity() {
+    assert!((vector_index::cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert!(vector_index::cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+    assert!((vector_index::cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-6);
+    assert!(vector_index::cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]).abs() < f32::EPSILON);
+}
+
+#[tokio::test]
+async fn test_index_add_search_and_persist() {
+    let mut index = VectorIndex::new("text-embedding-3-small");
+    assert!(index.add(entry("a.txt", "cats", &[1.0, 0.0])).unwrap());
+    assert!(index.add(entry("b.txt", "dogs", &[0.6, 0.8])).unwrap());
+    assert!(index.add(entry("c.txt", "cars", &[0.0, 1.0])).unwrap());
+
+    // The same text from the same source is stored once
+    assert!(!index.add(entry("a.txt", "cats", &[1.0, 0.0])).unwrap());
+    assert_eq!(index.entries.len(), 3);
+
+    let hits = index.search(&[0.9, 0.1], 2).unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].entry.source, "a.txt");
+    assert_eq!(hits[1].entry.source, "b.txt");
+    assert!(hits[0].score > hits[1].score);
+
+    let error = index.search(&[1.0, 0.0, 0.0], 2).unwrap_err();
+    assert!(matches!(error, AppError::IndexError(_)));
+    assert!(index.add(entry("d.txt", "boats", &[1.0])).is_err());
+
+    let path = std::env::temp_dir()
+        .join(format!("llm-cli-index-{}", uuid::Uuid::new_v4()))
+        .join("docs.json");
+    assert!(VectorIndex::load(&path).await.unwrap().is_none());
+
+    index.save(&path).await.unwrap();
+    let restored = VectorIndex::load(&path).await.unwrap().unwrap();
+    assert_eq!(restored.model, "text-embedding-3-small");
+    assert_eq!(restored.entries, index.entries);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,94 @@
+//! Minimal MCP server over stdio used by the MCP client tests
+//!
+//! Offers `echo`, which returns its `text` argument, and `fail`, which always
+//! reports a tool error. `tools/list` is split over two pages, and a log
+//! notification is sent ahead of some responses.
+
+#![allow(clippy::unwrap_used)]
+
+use std::io::{BufRead, Write};
+
+fn main() {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let Ok(request) = serde_json::from_str::<serde_json::Value>(&line) else {
+            continue;
+        };
+
+        // Notifications have no ID and get no response
+        let Some(id) = request.get("id").cloned() else {
+            continue;
+        };
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = &request["params"];
+
+        let result = match method {
+            "initialize" => serde_json::json!({
+                "protocolVersion": params["protocolVersion"],
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "echo", "version": "0.1.0"}
+            }),
+            "tools/list" if params.get("cursor").is_none() => serde_json::json!({
+                "tools": [{
+                    "name": "echo",
+                    "description": "Echo the given text",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"text": {"type": "string"}},
+                        "required": ["text"]
+                    }
+                }],
+                "nextCursor": "2"
+            }),
+            "tools/list" => serde_json::json!({
+                "tools": [{"name": "fail", "description": "Always fails"}]
+            }),
+            "tools/call" => {
+                writeln!(
+                    stdout,
+                    "{}",
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/message",
+                        "params": {"level": "info", "data": "calling"}
+                    })
+                )
+                .unwrap();
+
+                match params["name"].as_str() {
+                    Some("echo") => serde_json::json!({
+                        "content": [{
+                            "type": "text",
+                            "text": format!("echo: {}", params["arguments"]["text"].as_str().unwrap_or_default())
+                        }]
+                    }),
+                    _ => serde_json::json!({
+                        "content": [{"type": "text", "text": "this tool always fails"}],
+                        "isError": true
+                    }),
+                }
+            }
+            _ => {
+                let error = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": "Method not found"}
+                });
+                writeln!(stdout, "{error}").unwrap();
+                stdout.flush().unwrap();
+                continue;
+            }
+        };
+
+        writeln!(
+            stdout,
+            "{}",
+            serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result})
+        )
+        .unwrap();
+        stdout.flush().unwrap();
+    }
+}
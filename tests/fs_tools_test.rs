@@ -0,0 +1,167 @@
+//! Tests for the built-in sandboxed file-system tools
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::ToolCall;
+use llm_cli::fs_tools::{self, Workspace, WriteApproval};
+use llm_cli::tools::ToolRegistry;
+use std::path::PathBuf;
+
+/// Helper function to create a scratch workspace with a few files
+fn create_workspace() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("llm-cli-fs-tools-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("src/nested")).unwrap();
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::write(root.join("README.md"), "# Demo\nHello workspace\n").unwrap();
+    std::fs::write(
+        root.join("src/main.rs"),
+        "fn main() {\n    println!(\"Hello\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("src/nested/notes.txt"), "nothing to see\n").unwrap();
+    std::fs::write(root.join(".git/config"), "hello from git\n").unwrap();
+    root
+}
+
+/// Helper function to register the tools for a workspace
+fn registry(root: &PathBuf, max_read_bytes: usize, approval: WriteApproval) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    fs_tools::register(
+        &mut registry,
+        Workspace::new(root).unwrap(),
+        max_read_bytes,
+        approval,
+    );
+    registry
+}
+
+/// Helper function to call a tool with JSON arguments
+async fn call(registry: &ToolRegistry, name: &str, arguments: serde_json::Value) -> String {
+    registry
+        .execute(&ToolCall::function("call_1", name, arguments.to_string()))
+        .await
+}
+
+#[tokio::test]
+async fn test_workspace_rejects_paths_outside_root() {
+    let root = create_workspace();
+    let workspace = Workspace::new(&root).unwrap();
+
+    assert!(workspace.resolve("src/main.rs").is_ok());
+    assert!(workspace.resolve("src/new/file.txt").is_ok());
+    assert!(workspace.resolve("src/../README.md").is_ok());
+
+    assert!(workspace.resolve("../outside.txt").is_err());
+    assert!(workspace.resolve("src/../../outside.txt").is_err());
+    assert!(workspace.resolve("missing/../../outside.txt").is_err());
+    assert!(workspace.resolve("/etc/passwd").is_err());
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(std::env::temp_dir(), root.join("escape")).unwrap();
+        assert!(workspace.resolve("escape/anything").is_err());
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_read_file_and_list_directory() {
+    let root = create_workspace();
+    let tools = registry(&root, 10, WriteApproval::DenyAll);
+
+    let names: Vec<String> = tools
+        .definitions()
+        .into_iter()
+        .map(|d| d.function.name)
+        .collect();
+    assert_eq!(
+        names,
+        ["read_file", "list_directory", "search_text", "write_file"]
+    );
+
+    let output = call(
+        &tools,
+        "read_file",
+        serde_json::json!({"path": "README.md"}),
+    )
+    .await;
+    assert_eq!(output, "# Demo\nHel\n\n[truncated after 10 bytes]");
+
+    let output = call(
+        &tools,
+        "read_file",
+        serde_json::json!({"path": "../secret"}),
+    )
+    .await;
+    assert!(output.contains("outside the workspace"));
+
+    let output = call(&tools, "list_directory", serde_json::json!({})).await;
+    assert_eq!(output, ".git/\nREADME.md (23 bytes)\nsrc/");
+
+    let output = call(&tools, "list_directory", serde_json::json!({"path": "src"})).await;
+    assert_eq!(output, "main.rs (37 bytes)\nnested/");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_search_text() {
+    let root = create_workspace();
+    let tools = registry(&root, 100_000, WriteApproval::DenyAll);
+
+    // Hidden directories are skipped and matching is case-insensitive by default
+    let output = call(&tools, "search_text", serde_json::json!({"query": "hello"})).await;
+    assert_eq!(
+        output,
+        "README.md:2: Hello workspace\nsrc/main.rs:2: println!(\"Hello\");"
+    );
+
+    let output = call(
+        &tools,
+        "search_text",
+        serde_json::json!({"query": "hello", "path": "src", "case_sensitive": true}),
+    )
+    .await;
+    assert!(output.starts_with("No matches"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_write_file_requires_approval() {
+    let root = create_workspace();
+
+    let denied = registry(&root, 100_000, WriteApproval::DenyAll);
+    let output = call(
+        &denied,
+        "write_file",
+        serde_json::json!({"path": "out/new.txt", "content": "data"}),
+    )
+    .await;
+    assert!(output.contains("denied"));
+    assert!(!root.join("out/new.txt").exists());
+
+    let allowed = registry(&root, 100_000, WriteApproval::AllowAll);
+    let output = call(
+        &allowed,
+        "write_file",
+        serde_json::json!({"path": "out/new.txt", "content": "data"}),
+    )
+    .await;
+    assert_eq!(output, "Wrote 4 bytes to out/new.txt");
+    assert_eq!(
+        std::fs::read_to_string(root.join("out/new.txt")).unwrap(),
+        "data"
+    );
+
+    let output = call(
+        &allowed,
+        "write_file",
+        serde_json::json!({"path": "../escape.txt", "content": "data"}),
+    )
+    .await;
+    assert!(output.contains("outside the workspace"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,158 @@
+//! Tests for the image generation endpoint
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::OpenAIClient;
+use llm_cli::config::Config;
+use llm_cli::images::{self, ImageOptions};
+use std::path::PathBuf;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// PNG signature followed by a few bytes of an IHDR chunk
+const PNG_BYTES: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create image options
+fn options(count: u32) -> ImageOptions {
+    ImageOptions {
+        model: "dall-e-3".to_string(),
+        size: "1024x1024".to_string(),
+        count,
+        quality: None,
+    }
+}
+
+/// Helper function to create an empty scratch output directory
+fn output_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("llm-cli-images-{}", uuid::Uuid::new_v4()))
+}
+
+#[tokio::test]
+async fn test_decodes_b64_json_into_numbered_files() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/images/generations"))
+        .and(header("authorization", "Bearer test-key"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "dall-e-3",
+            "prompt": "A lighthouse at dusk",
+            "n": 2,
+            "size": "1024x1024",
+            "quality": "hd"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "created": 1_713_833_628,
+            "data": [
+                {"b64_json": "iVBORw0KGgoAAAAN", "revised_prompt": "A tall lighthouse at dusk"},
+                {"b64_json": "/9j/4AAQ"}
+            ]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let dir = output_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("image-1.png"), "existing").unwrap();
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let options = ImageOptions {
+        quality: Some("hd".to_string()),
+        ..options(2)
+    };
+    let generated = images::generate(&client, "A lighthouse at dusk", &options, &dir)
+        .await
+        .unwrap();
+
+    // Existing files are never overwritten, and JPEG data gets a .jpg name
+    assert_eq!(generated.len(), 2);
+    assert_eq!(generated[0].path, dir.join("image-2.png"));
+    assert_eq!(
+        generated[0].revised_prompt.as_deref(),
+        Some("A tall lighthouse at dusk")
+    );
+    assert_eq!(generated[1].path, dir.join("image-3.jpg"));
+    assert_eq!(generated[1].revised_prompt, None);
+
+    assert_eq!(std::fs::read(dir.join("image-1.png")).unwrap(), b"existing");
+    assert_eq!(std::fs::read(dir.join("image-2.png")).unwrap(), PNG_BYTES);
+    assert_eq!(
+        std::fs::read(dir.join("image-3.jpg")).unwrap(),
+        [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_downloads_urls() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/images/generations"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "created": 1_713_833_628,
+            "data": [{"url": format!("{}/files/generated.png", mock_server.uri())}]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/files/generated.png"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(PNG_BYTES.to_vec()))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let dir = output_dir();
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let generated = images::generate(&client, "A cat", &options(1), &dir)
+        .await
+        .unwrap();
+
+    assert_eq!(generated[0].path, dir.join("image-1.png"));
+    assert_eq!(std::fs::read(&generated[0].path).unwrap(), PNG_BYTES);
+
+    // The pre-signed download URL must not receive the API key
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests[1].headers.get("authorization").is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_api_error() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/images/generations"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": {
+                "message": "Your request was rejected by the safety system.",
+                "type": "invalid_request_error",
+                "code": "content_policy_violation"
+            }
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let dir = output_dir();
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let error = images::generate(&client, "Something", &options(1), &dir)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("safety system"));
+    assert!(!dir.exists());
+}
@@ -2,6 +2,7 @@
 
 use llm_cli::api::{Message, Role};
 use llm_cli::config::Config;
+use llm_cli::provider::{ChatRequest, Completion};
 use llm_cli::session::Session;
 use wiremock::matchers::{header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -97,12 +98,17 @@ async fn test_api_client_mock() {
         .await;
 
     let mut config = Config::default();
+    config.usage.ledger = false;
     config.api_key = Some("test-key".to_string());
     config.base_url = mock_server.uri();
     config.api_path = "/v1/chat/completions".to_string();
 
     let client = llm_cli::api::OpenAIClient::new(config).unwrap();
-    let response = client.chat("Hello").await.unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
 
     assert_eq!(response, "Hello! How can I help you today?");
 }
@@ -126,12 +132,13 @@ async fn test_error_handling() {
         .await;
 
     let mut config = Config::default();
+    config.usage.ledger = false;
     config.api_key = Some("invalid-key".to_string());
     config.base_url = mock_server.uri();
     config.api_path = "/v1/chat/completions".to_string();
 
     let client = llm_cli::api::OpenAIClient::new(config).unwrap();
-    let result = client.chat("Hello").await;
+    let result = client.complete_chat(ChatRequest::new(vec![Message::user("Hello")])).await;
 
     assert!(result.is_err());
     let error = result.unwrap_err();
@@ -157,12 +164,13 @@ async fn test_rate_limit_error() {
         .await;
 
     let mut config = Config::default();
+    config.usage.ledger = false;
     config.api_key = Some("test-key".to_string());
     config.base_url = mock_server.uri();
     config.api_path = "/v1/chat/completions".to_string();
 
     let client = llm_cli::api::OpenAIClient::new(config).unwrap();
-    let result = client.chat("Hello").await;
+    let result = client.complete_chat(ChatRequest::new(vec![Message::user("Hello")])).await;
 
     assert!(result.is_err());
     match result.unwrap_err() {
@@ -170,3 +178,44 @@ async fn test_rate_limit_error() {
         e => panic!("Expected RateLimitExceeded, got {:?}", e),
     }
 }
+
+#[tokio::test]
+async fn test_provider_from_config() {
+    let mock_server = MockServer::start().await;
+
+    let mock_response = r#"{
+        "id": "chatcmpl-456",
+        "object": "chat.completion",
+        "created": 1677652288,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": "Served through the provider trait"
+            },
+            "finish_reason": "stop"
+        }]
+    }"#;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(mock_response))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = Config::default();
+    config.usage.ledger = false;
+    config.api_key = Some("test-key".to_string());
+    config.base_url = mock_server.uri();
+
+    let provider = llm_cli::provider::from_config(config).unwrap();
+    assert_eq!(provider.name(), "openai");
+
+    let response = provider
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+    assert_eq!(response, "Served through the provider trait");
+}
@@ -0,0 +1,219 @@
+//! Tests for the usage ledger and the `stats` report
+
+#![allow(clippy::unwrap_used)]
+
+use chrono::{TimeZone, Utc};
+use futures_util::StreamExt;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ModelPrice, UsageConfig};
+use llm_cli::embeddings;
+use llm_cli::ledger::{self, LedgerEntry, UsageReport};
+use llm_cli::provider::ChatRequest;
+use std::path::PathBuf;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config that records to a scratch ledger
+fn create_test_config(mock_server: &MockServer) -> (Config, PathBuf) {
+    let ledger_path =
+        std::env::temp_dir().join(format!("llm-cli-ledger-{}.jsonl", uuid::Uuid::new_v4()));
+
+    let mut config = Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o-2024-08-06".to_string(),
+        100,
+    );
+    config.usage.ledger = true;
+    config.usage.ledger_path = Some(ledger_path.clone());
+    (config, ledger_path)
+}
+
+/// Helper function to create a ledger entry
+fn entry(
+    day: u32,
+    endpoint: &str,
+    model: &str,
+    prompt_tokens: u32,
+    cost: Option<f64>,
+) -> LedgerEntry {
+    LedgerEntry {
+        timestamp: Utc.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap(),
+        endpoint: endpoint.to_string(),
+        model: model.to_string(),
+        prompt_tokens,
+        completion_tokens: 100,
+        estimated: false,
+        latency_ms: 200 * u64::from(day),
+        cost,
+    }
+}
+
+#[tokio::test]
+async fn test_chat_and_embedding_requests_are_recorded() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi!"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 1000, "completion_tokens": 500, "total_tokens": 1500}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "object": "list",
+            "data": [{"object": "embedding", "index": 0, "embedding": [0.5, 0.5]}],
+            "model": "text-embedding-3-small",
+            "usage": {"prompt_tokens": 3, "total_tokens": 3}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let (config, ledger_path) = create_test_config(&mock_server);
+    let client = OpenAIClient::new(config).unwrap();
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+    embeddings::embed(
+        &client,
+        "text-embedding-3-small",
+        &["one two".to_string()],
+        8,
+    )
+    .await
+    .unwrap();
+
+    let entries = ledger::read(&ledger_path).await.unwrap();
+    std::fs::remove_file(&ledger_path).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].endpoint, "chat");
+    assert_eq!(entries[0].model, "gpt-4o-2024-08-06");
+    assert_eq!(
+        (entries[0].prompt_tokens, entries[0].completion_tokens),
+        (1000, 500)
+    );
+    // The dated snapshot is priced as gpt-4o: $2.50 and $10 per million tokens
+    assert!((entries[0].cost.unwrap() - 0.0075).abs() < 1e-12);
+
+    assert_eq!(entries[1].endpoint, "embeddings");
+    assert_eq!(
+        (entries[1].prompt_tokens, entries[1].completion_tokens),
+        (3, 0)
+    );
+}
+
+#[tokio::test]
+async fn test_streaming_request_recorded_once() {
+    let mock_server = MockServer::start().await;
+
+    let body = [
+        r#"{"choices":[{"index":0,"delta":{"content":"Hi"},"finish_reason":"stop"}]}"#,
+        "[DONE]",
+    ]
+    .iter()
+    .map(|data| format!("data: {data}\n\n"))
+    .collect::<Vec<_>>()
+    .concat();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&mock_server)
+        .await;
+
+    let (mut config, ledger_path) = create_test_config(&mock_server);
+    config.model = "local-model".to_string();
+    let client = OpenAIClient::new(config).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+    while stream.next().await.is_some() {}
+
+    let entries = ledger::read(&ledger_path).await.unwrap();
+    std::fs::remove_file(&ledger_path).unwrap();
+
+    // The server sent no usage, so the estimate is recorded, without a price
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].estimated);
+    assert_eq!(entries[0].cost, None);
+}
+
+#[tokio::test]
+async fn test_read_skips_malformed_lines() {
+    let path = std::env::temp_dir().join(format!("llm-cli-ledger-{}.jsonl", uuid::Uuid::new_v4()));
+    assert!(ledger::read(&path).await.unwrap().is_empty());
+
+    let line = serde_json::to_string(&entry(1, "chat", "gpt-4o", 10, None)).unwrap();
+    std::fs::write(&path, format!("{line}\n{{not json\n\n{line}\n")).unwrap();
+    let entries = ledger::read(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn test_report_groups_and_prices() {
+    let entries = [
+        entry(1, "chat", "gpt-4o", 1000, Some(0.5)),
+        entry(2, "chat", "local-model", 1000, None),
+        entry(2, "embeddings", "my-embedder", 4000, None),
+        entry(3, "chat", "gpt-4o", 2000, Some(1.0)),
+    ];
+
+    let mut usage = UsageConfig::default();
+    usage.prices.insert(
+        "my-embedder".to_string(),
+        ModelPrice {
+            prompt: 1.0,
+            completion: 0.0,
+        },
+    );
+
+    let report = UsageReport::new(&entries, None, &usage);
+    assert_eq!(report.total.requests, 4);
+    assert_eq!(report.total.prompt_tokens, 8000);
+    assert_eq!(report.total.total_tokens(), 8400);
+    // Recorded costs are kept, and a price added later covers older entries
+    assert!((report.total.cost - 1.504).abs() < 1e-9);
+    assert_eq!(report.total.unpriced_requests, 1);
+    assert_eq!(report.total.average_latency_ms, 400);
+
+    assert_eq!(report.by_day.len(), 3);
+    assert_eq!(report.by_model["gpt-4o"].requests, 2);
+    assert_eq!(report.by_endpoint["chat"].requests, 3);
+    assert_eq!(report.by_endpoint["embeddings"].prompt_tokens, 4000);
+
+    let since = Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap();
+    let report = UsageReport::new(&entries, Some(since), &usage);
+    assert_eq!(report.total.requests, 3);
+    assert_eq!(report.by_day.len(), 2);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["by_model"]["gpt-4o"]["requests"], 1);
+    assert_eq!(json["by_day"]["2026-10-03"]["prompt_tokens"], 2000);
+}
+
+#[test]
+fn test_price_matches_longest_prefix() {
+    let usage = UsageConfig::default();
+    let price = |model| usage.price(model).copied();
+
+    assert_eq!(price("gpt-4o-mini-2024-07-18"), price("gpt-4o-mini"));
+    assert_ne!(price("gpt-4o-mini"), price("gpt-4o"));
+    assert_eq!(price("llama3.2"), None);
+}
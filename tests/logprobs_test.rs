@@ -0,0 +1,138 @@
+//! Tests for token logprobs and confidence highlighting
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::Config;
+use llm_cli::logprobs::{self, Confidence, TokenLogprob};
+use llm_cli::provider::ChatRequest;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to build a completion response with optional logprobs
+fn completion(content: &str, logprobs: &serde_json::Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop",
+            "logprobs": logprobs
+        }],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 3, "total_tokens": 13}
+    }))
+}
+
+/// Helper function to build a token without alternatives
+fn token(text: &str, probability: f64) -> TokenLogprob {
+    TokenLogprob {
+        token: text.to_string(),
+        logprob: probability.ln(),
+        top_logprobs: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_logprobs_are_requested_and_parsed() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(
+            serde_json::json!({"logprobs": true, "top_logprobs": 2}),
+        ))
+        .respond_with(completion(
+            "Paris.",
+            &serde_json::json!({"content": [
+                {
+                    "token": "Paris",
+                    "logprob": -0.4,
+                    "bytes": [80, 97, 114, 105, 115],
+                    "top_logprobs": [
+                        {"token": "Paris", "logprob": -0.4, "bytes": null},
+                        {"token": "Lyon", "logprob": -1.2, "bytes": null}
+                    ]
+                },
+                {"token": ".", "logprob": 0.0, "bytes": [46], "top_logprobs": null}
+            ]}),
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Capital of France?")]);
+    let (completion, tokens) = client.complete_with_logprobs(request, 2).await.unwrap();
+
+    assert_eq!(completion.message.content, "Paris.");
+    assert_eq!(completion.usage.total_tokens, 13);
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].token, "Paris");
+    assert!((tokens[0].probability() - (-0.4f64).exp()).abs() < 1e-12);
+    assert_eq!(tokens[0].confidence(), Confidence::Medium);
+
+    // The chosen token is not listed as its own alternative
+    let alternatives: Vec<_> = tokens[0]
+        .alternatives()
+        .map(|top| top.token.as_str())
+        .collect();
+    assert_eq!(alternatives, ["Lyon"]);
+
+    assert!(tokens[1].top_logprobs.is_empty());
+    assert_eq!(tokens[1].confidence(), Confidence::High);
+}
+
+#[tokio::test]
+async fn test_server_without_logprobs_returns_no_tokens() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion("Paris.", &serde_json::Value::Null))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Capital of France?")]);
+    let (completion, tokens) = client.complete_with_logprobs(request, 0).await.unwrap();
+
+    assert_eq!(completion.message.content, "Paris.");
+    assert!(tokens.is_empty());
+}
+
+#[test]
+fn test_confidence_bands() {
+    assert_eq!(Confidence::of(1.0), Confidence::High);
+    assert_eq!(Confidence::of(0.9), Confidence::High);
+    assert_eq!(Confidence::of(0.6), Confidence::Medium);
+    assert_eq!(Confidence::of(0.3), Confidence::Low);
+    assert_eq!(Confidence::of(0.05), Confidence::VeryLow);
+    assert!(Confidence::VeryLow < Confidence::High);
+}
+
+#[test]
+fn test_lowest_skips_whitespace() {
+    let tokens = [
+        token("The", 0.99),
+        token(" year", 0.7),
+        token(" ", 0.01),
+        token(" 1913", 0.1),
+        token(".", 0.4),
+    ];
+
+    assert_eq!(logprobs::lowest(&tokens, 2), [3, 4]);
+    assert_eq!(logprobs::lowest(&tokens, 10), [3, 4, 1, 0]);
+}
@@ -0,0 +1,112 @@
+//! Tests for the MCP stdio client against a fixture server
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::api::ToolCall;
+use llm_cli::config::McpServerConfig;
+use llm_cli::mcp::{self, McpClient};
+use llm_cli::tools::ToolRegistry;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Helper function to point at the `echo_mcp_server` example built alongside the tests
+fn echo_server() -> McpServerConfig {
+    // Test binaries live in target/<profile>/deps, examples in target/<profile>/examples
+    let mut path: PathBuf = std::env::current_exe().unwrap();
+    path.pop();
+    path.pop();
+    path.push("examples");
+    path.push(format!("echo_mcp_server{}", std::env::consts::EXE_SUFFIX));
+
+    if !path.exists() {
+        let status = std::process::Command::new(env!("CARGO"))
+            .args(["build", "--quiet", "--example", "echo_mcp_server"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    McpServerConfig {
+        command: path.display().to_string(),
+        args: Vec::new(),
+        env: BTreeMap::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_list_and_call_tools() {
+    let client = McpClient::connect("echo", &echo_server(), TIMEOUT)
+        .await
+        .unwrap();
+
+    // Both pages of tools/list are collected
+    let tools = client.list_tools().await.unwrap();
+    let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["echo", "fail"]);
+    assert_eq!(tools[0].description, "Echo the given text");
+    assert_eq!(tools[0].input_schema["required"][0], "text");
+    assert_eq!(tools[1].input_schema["type"], "object");
+
+    // The notification sent before the response is skipped
+    let output = client
+        .call_tool("echo", serde_json::json!({"text": "hi"}))
+        .await
+        .unwrap();
+    assert_eq!(output, "echo: hi");
+
+    let error = client
+        .call_tool("fail", serde_json::json!({}))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("this tool always fails"));
+}
+
+#[tokio::test]
+async fn test_register_server_tools() {
+    let mut registry = ToolRegistry::new();
+    let count = mcp::register_server(&mut registry, "echo", &echo_server(), TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+    assert!(registry.contains("echo"));
+
+    let output = registry
+        .execute(&ToolCall::function(
+            "call_1",
+            "echo",
+            r#"{"text":"from chat"}"#,
+        ))
+        .await;
+    assert_eq!(output, "echo: from chat");
+
+    let output = registry
+        .execute(&ToolCall::function("call_2", "fail", "{}"))
+        .await;
+    assert!(output.starts_with("Error:"));
+
+    // A second server with the same tool names adds nothing
+    let count = mcp::register_server(&mut registry, "again", &echo_server(), TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+    assert_eq!(registry.definitions().len(), 2);
+}
+
+#[tokio::test]
+async fn test_connect_failure() {
+    let config = McpServerConfig {
+        command: "llm-cli-no-such-mcp-server".to_string(),
+        args: Vec::new(),
+        env: BTreeMap::new(),
+    };
+    let error = McpClient::connect("missing", &config, TIMEOUT)
+        .await
+        .err()
+        .unwrap();
+    assert!(error
+        .to_string()
+        .contains("Failed to start MCP server missing"));
+}
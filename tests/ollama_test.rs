@@ -0,0 +1,360 @@
+//! Tests for the native Ollama backend
+
+#![allow(clippy::unwrap_used, clippy::panic)]
+
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use llm_cli::api::Message;
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::{AppError, Result};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::{ChatRequest, Completion, EventStream, StreamEvent};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.usage.ledger = false;
+    config.provider = ProviderKind::Ollama;
+    config.base_url = mock_server.uri();
+    config.model = "llama3.2".to_string();
+    config.max_tokens = 128;
+    config.timeout_seconds = 5;
+    config
+}
+
+/// Helper function to create an NDJSON streaming body from content deltas
+fn create_ndjson_response(deltas: &[&str], done_reason: &str) -> String {
+    let mut body = String::new();
+    for delta in deltas {
+        body.push_str(
+            &serde_json::json!({
+                "model": "llama3.2",
+                "created_at": "2025-01-01T00:00:00Z",
+                "message": {"role": "assistant", "content": delta},
+                "done": false
+            })
+            .to_string(),
+        );
+        body.push('\n');
+    }
+    body.push_str(
+        &serde_json::json!({
+            "model": "llama3.2",
+            "created_at": "2025-01-01T00:00:01Z",
+            "message": {"role": "assistant", "content": ""},
+            "done": true,
+            "done_reason": done_reason,
+            "eval_count": 7
+        })
+        .to_string(),
+    );
+    body.push('\n');
+    body
+}
+
+/// Helper function to reduce a stream of events to its text, reporting truncation as an error
+fn text_chunks(events: EventStream) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text(text)) => Some(Ok(text)),
+                Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                    Some(Err(AppError::TokenLimitExceeded))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .boxed()
+}
+
+#[tokio::test]
+async fn test_ollama_complete_sends_options() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "llama3.2",
+            "stream": false,
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hello"}
+            ],
+            "options": {"num_ctx": 8192, "num_predict": 128},
+            "keep_alive": "10m"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {"role": "assistant", "content": "Hi!"},
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config
+        .ollama
+        .options
+        .insert("num_ctx".to_string(), 8192.into());
+    config.ollama.keep_alive = Some("10m".into());
+
+    let client = OllamaClient::new(config).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![
+            Message::system("Be brief."),
+            Message::user("Hello"),
+        ]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Hi!");
+}
+
+#[tokio::test]
+async fn test_ollama_streaming_ndjson() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(serde_json::json!({"stream": true})))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(create_ndjson_response(
+                    &["Hello", " from", " Ollama", " 你好"],
+                    "stop",
+                ))
+                .append_header("content-type", "application/x-ndjson"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OllamaClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut collected = String::new();
+    while let Some(chunk) = stream.next().await {
+        collected.push_str(&chunk.unwrap());
+    }
+
+    assert_eq!(collected, "Hello from Ollama 你好");
+}
+
+#[tokio::test]
+async fn test_ollama_streaming_length_and_error() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(create_ndjson_response(&["Partial"], "length")),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OllamaClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut results = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        results.push(chunk);
+    }
+
+    assert!(matches!(results.first(), Some(Ok(text)) if text == "Partial"));
+    assert!(matches!(
+        results.last(),
+        Some(Err(AppError::TokenLimitExceeded))
+    ));
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{\"error\":\"model crashed\"}\n"))
+        .mount(&mock_server)
+        .await;
+
+    let client = OllamaClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    match stream.next().await {
+        Some(Err(AppError::ApiError { message })) => assert_eq!(message, "model crashed"),
+        other => panic!("Expected ApiError, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_ollama_raw_mode_uses_generate() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(serde_json::json!({
+            "raw": true,
+            "prompt": "[INST] Hello [/INST]"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "response": "Raw reply",
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.ollama.raw = true;
+
+    let client = OllamaClient::new(config).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![Message::user(
+            "[INST] Hello [/INST]",
+        )]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Raw reply");
+}
+
+#[tokio::test]
+async fn test_ollama_list_models_and_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/tags"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "models": [
+                {"name": "llama3.2:latest", "model": "llama3.2:latest", "size": 2_019_393_189},
+                {"name": "qwen2.5-coder:7b", "model": "qwen2.5-coder:7b", "size": 4_683_087_332_u64}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+            "error": "model \"missing\" not found, try pulling it first"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OllamaClient::new(create_test_config(&mock_server)).unwrap();
+
+    let models = client.list_models().await.unwrap();
+    assert_eq!(models, vec!["llama3.2:latest", "qwen2.5-coder:7b"]);
+
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("try pulling it first"));
+}
+
+#[tokio::test]
+async fn test_ollama_sends_only_its_own_api_key() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/tags"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"models": []})))
+        .mount(&mock_server)
+        .await;
+
+    // The general API key is never sent to Ollama
+    let mut config = create_test_config(&mock_server);
+    config.api_key = Some("sk-openai".to_string());
+    OllamaClient::new(config.clone())
+        .unwrap()
+        .list_models()
+        .await
+        .unwrap();
+
+    config.ollama.api_key = Some("ollama-token".to_string());
+    OllamaClient::new(config)
+        .unwrap()
+        .list_models()
+        .await
+        .unwrap();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(requests[0].headers.get("authorization").is_none());
+    assert_eq!(
+        requests[1].headers.get("authorization").unwrap(),
+        "Bearer ollama-token"
+    );
+}
+
+#[tokio::test]
+async fn test_ollama_tool_call_ids_are_unique_across_chunks() {
+    let mock_server = MockServer::start().await;
+
+    let tool_call = |city: &str| {
+        serde_json::json!({
+            "model": "llama3.2",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "get_weather", "arguments": {"city": city}}}]
+            },
+            "done": false
+        })
+    };
+    let body = [
+        tool_call("Paris"),
+        tool_call("Lyon"),
+        serde_json::json!({"model": "llama3.2", "message": {"role": "assistant", "content": ""}, "done": true, "done_reason": "stop"}),
+    ]
+    .iter()
+    .map(|line| format!("{line}\n"))
+    .collect::<Vec<_>>()
+    .concat();
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let client = OllamaClient::new(create_test_config(&mock_server)).unwrap();
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let mut events = client
+            .stream_chat(ChatRequest::new(vec![Message::user("Weather?")]))
+            .await
+            .unwrap();
+        while let Some(event) = events.next().await {
+            if let StreamEvent::ToolCalls(calls) = event.unwrap() {
+                ids.extend(calls.into_iter().map(|call| call.id));
+            }
+        }
+    }
+
+    // Two chunks per stream and two streams, with no ID repeated
+    assert_eq!(ids.len(), 4);
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 4);
+}
@@ -0,0 +1,261 @@
+//! Tests for indexing and retrieval in `chat --rag`
+
+#![allow(clippy::unwrap_used)]
+
+use indicatif::ProgressBar;
+use llm_cli::api::{OpenAIClient, Role};
+use llm_cli::config::Config;
+use llm_cli::rag::{self, DocumentIndex, RagOptions, Retriever, SyncReport};
+use std::path::{Path, PathBuf};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create indexing options
+fn options() -> RagOptions {
+    RagOptions {
+        model: "text-embedding-3-small".to_string(),
+        batch_size: 16,
+        chunk_chars: 200,
+        overlap_chars: 0,
+        max_file_bytes: 10_000,
+        top_k: 2,
+    }
+}
+
+/// Helper function to serve embeddings that score words about cats and dogs
+async fn embedding_server() -> MockServer {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/embeddings"))
+        .respond_with(|request: &Request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            let data: Vec<_> = body["input"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    let text = text.as_str().unwrap();
+                    let cats: f32 = text.matches("cat").map(|_| 1.0).sum();
+                    let dogs: f32 = text.matches("dog").map(|_| 1.0).sum();
+                    serde_json::json!({"index": index, "embedding": [cats, dogs, 0.1]})
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({"data": data}))
+        })
+        .mount(&mock_server)
+        .await;
+
+    mock_server
+}
+
+/// Helper function to create a scratch directory of documents
+fn docs_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("llm-cli-rag-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("guides")).unwrap();
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    std::fs::create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(
+        dir.join("cats.md"),
+        "# Cats\nA cat sleeps.\nThe cat purrs.\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("guides/dogs.txt"), "Walk the dog daily.\n").unwrap();
+    std::fs::write(dir.join(".git/config"), "cat dog").unwrap();
+    std::fs::write(dir.join("target/build.log"), "cat dog").unwrap();
+    std::fs::write(dir.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 0, 0xFF]).unwrap();
+    dir
+}
+
+/// Helper function to count the chunks the server was asked to embed
+async fn embedded_inputs(mock_server: &MockServer) -> usize {
+    mock_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["input"].as_array().unwrap().len()
+        })
+        .sum()
+}
+
+/// Helper function to forget when a file was last read, forcing a content check
+fn forget_modified(index: &mut DocumentIndex, file: &str) {
+    index.files.get_mut(file).unwrap().modified = chrono::DateTime::UNIX_EPOCH;
+}
+
+#[test]
+fn test_chunk_text() {
+    let text = "one\ntwo\nthree\nfour\n\n\nfive";
+
+    let chunks = rag::chunk_text(text, 10, 0);
+    let spans: Vec<_> = chunks
+        .iter()
+        .map(|c| (c.start_line, c.end_line, c.text.as_str()))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (1, 2, "one\ntwo"),
+            (3, 3, "three"),
+            (4, 6, "four\n\n"),
+            (7, 7, "five")
+        ]
+    );
+
+    // Trailing lines are repeated at the start of the next chunk
+    let chunks = rag::chunk_text("aaaa\nbbbb\ncccc\ndddd", 10, 5);
+    let spans: Vec<_> = chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+    assert_eq!(spans, [(1, 2), (2, 3), (3, 4)]);
+
+    // A line longer than the limit is cut, and blank chunks are dropped
+    let chunks = rag::chunk_text("abcdefghij\n   \n   ", 4, 0);
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].text, "abcd");
+    assert!(rag::chunk_text("", 10, 0).is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_is_incremental() {
+    let mock_server = embedding_server().await;
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let dir = docs_dir();
+    let progress = ProgressBar::hidden();
+    let mut index = DocumentIndex::new("text-embedding-3-small");
+
+    // Hidden and build directories and binary files are skipped
+    let report = index
+        .sync(&client, &dir, &options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(
+        report,
+        SyncReport {
+            added: 2,
+            embedded_chunks: 2,
+            ..SyncReport::default()
+        }
+    );
+    assert_eq!(
+        index.files.keys().collect::<Vec<_>>(),
+        ["cats.md", "guides/dogs.txt"]
+    );
+    assert_eq!(index.files["cats.md"].chunks[0].embedding, [2.0, 0.0, 0.1]);
+
+    // Nothing changed
+    let report = index
+        .sync(&client, &dir, &options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(report.unchanged, 2);
+    assert_eq!(report.embedded_chunks, 0);
+
+    // A new modification time with the same content is not embedded again
+    forget_modified(&mut index, "cats.md");
+    let report = index
+        .sync(&client, &dir, &options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(report.unchanged, 2);
+    assert_ne!(
+        index.files["cats.md"].modified,
+        chrono::DateTime::UNIX_EPOCH
+    );
+
+    // Changed files are embedded again and deleted files are dropped
+    std::fs::write(dir.join("guides/dogs.txt"), "A dog and a cat.\n").unwrap();
+    forget_modified(&mut index, "guides/dogs.txt");
+    std::fs::remove_file(dir.join("cats.md")).unwrap();
+    let report = index
+        .sync(&client, &dir, &options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(
+        report,
+        SyncReport {
+            updated: 1,
+            removed: 1,
+            embedded_chunks: 1,
+            ..SyncReport::default()
+        }
+    );
+    assert_eq!(
+        index.files["guides/dogs.txt"].chunks[0].embedding,
+        [1.0, 1.0, 0.1]
+    );
+    assert_eq!(embedded_inputs(&mock_server).await, 3);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_retrieve_and_cite() {
+    let mock_server = embedding_server().await;
+    let dir = docs_dir();
+    let index_path = dir.join(".git/index.json");
+    let progress = ProgressBar::hidden();
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let (retriever, report) = Retriever::open(client, &dir, &index_path, options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(report.added, 2);
+    assert!(index_path.exists());
+
+    let retrieval = retriever.retrieve("Why does my cat purr?").await.unwrap();
+    assert_eq!(retrieval.citations.len(), 2);
+    assert_eq!(retrieval.citations[0].to_string(), "cats.md:1-3");
+    assert!(retrieval.citations[0].score > retrieval.citations[1].score);
+
+    let message = retrieval.context_message();
+    assert_eq!(message.role, Role::System);
+    assert!(message
+        .content
+        .contains("[1] cats.md:1-3\n# Cats\nA cat sleeps."));
+    assert!(message
+        .content
+        .contains("[2] guides/dogs.txt:1-1\nWalk the dog daily."));
+
+    // Reopening reuses the saved index
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let (_, report) = Retriever::open(client, &dir, &index_path, options(), &progress)
+        .await
+        .unwrap();
+    assert_eq!(report.unchanged, 2);
+
+    // Another model means a rebuild
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let other_model = RagOptions {
+        model: "nomic-embed-text".to_string(),
+        ..options()
+    };
+    let (retriever, report) = Retriever::open(client, &dir, &index_path, other_model, &progress)
+        .await
+        .unwrap();
+    assert_eq!(report.added, 2);
+    assert_eq!(retriever.index().model, "nomic-embed-text");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_index_path_depends_on_root() {
+    let docs = rag::index_path(Path::new("/srv/docs"));
+    assert_eq!(docs, rag::index_path(Path::new("/srv/docs")));
+    assert_ne!(docs, rag::index_path(Path::new("/srv/other")));
+    assert!(docs.parent().unwrap().ends_with("llm-cli/rag"));
+    assert_eq!(docs.extension().unwrap(), "json");
+}
@@ -0,0 +1,226 @@
+//! Tests for reasoning models and the reasoning they stream
+
+#![allow(clippy::unwrap_used)]
+
+use futures_util::StreamExt;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ReasoningConfig};
+use llm_cli::provider::{ChatRequest, StreamEvent};
+use llm_cli::reasoning::{self, ReasoningEffort};
+use llm_cli::sampling::SamplingParams;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config for a model pointing to mock server
+fn create_test_config(mock_server: &MockServer, model: &str) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        model.to_string(),
+        100,
+    )
+}
+
+/// Helper function to build a completion response
+fn completion(message: &serde_json::Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "o3-mini",
+        "choices": [{"index": 0, "message": message, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 40, "total_tokens": 50}
+    }))
+}
+
+/// Helper function to get the JSON body of each request the server received
+async fn request_bodies(mock_server: &MockServer) -> Vec<serde_json::Value> {
+    mock_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_reasoning_models_are_recognized() {
+    assert!(reasoning::is_reasoning_model("o1"));
+    assert!(reasoning::is_reasoning_model("o3-mini-2025-01-31"));
+    assert!(reasoning::is_reasoning_model("o4-mini"));
+    assert!(reasoning::is_reasoning_model("gpt-5-mini"));
+    assert!(reasoning::is_reasoning_model("openai/o3"));
+    assert!(!reasoning::is_reasoning_model("gpt-5-chat-latest"));
+    assert!(!reasoning::is_reasoning_model("gpt-4o"));
+    assert!(!reasoning::is_reasoning_model("deepseek-reasoner"));
+
+    let config = ReasoningConfig {
+        models: vec!["my-deployment".to_string()],
+        ..ReasoningConfig::default()
+    };
+    assert!(config.applies_to("my-deployment-eu"));
+    assert!(!config.applies_to("gpt-4o"));
+}
+
+#[test]
+fn test_reasoning_effort_parameter() {
+    let mut sampling = SamplingParams::default();
+    sampling.set("reasoning_effort", "High").unwrap();
+    assert_eq!(sampling.reasoning_effort, Some(ReasoningEffort::High));
+    assert_eq!(sampling.to_string(), "reasoning_effort = \"high\"");
+
+    assert!(sampling.set("reasoning_effort", "extreme").is_err());
+    assert_eq!(sampling.reasoning_effort, Some(ReasoningEffort::High));
+
+    sampling.unset("reasoning_effort").unwrap();
+    assert!(sampling.is_empty());
+}
+
+#[tokio::test]
+async fn test_reasoning_model_request_form() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion(
+            &serde_json::json!({"role": "assistant", "content": "4"}),
+        ))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let mut sampling = SamplingParams::default();
+    sampling.set("temperature", "0.2").unwrap();
+    sampling.set("top_p", "0.9").unwrap();
+    sampling.set("frequency_penalty", "0.5").unwrap();
+    sampling.set("logit_bias", "50256=-100").unwrap();
+    sampling.set("seed", "7").unwrap();
+
+    let mut config = create_test_config(&mock_server, "o3-mini");
+    config.sampling = sampling.clone();
+    config.sampling.reasoning_effort = Some(ReasoningEffort::Low);
+    let client = OpenAIClient::new(config).unwrap();
+    let messages = vec![Message::system("Be brief."), Message::user("2 + 2?")];
+    client
+        .complete_chat(ChatRequest::new(messages.clone()))
+        .await
+        .unwrap();
+
+    let mut config = create_test_config(&mock_server, "gpt-4o");
+    config.sampling = sampling;
+    let client = OpenAIClient::new(config).unwrap();
+    client
+        .complete_chat(ChatRequest::new(messages))
+        .await
+        .unwrap();
+
+    let bodies = request_bodies(&mock_server).await;
+    let reasoning = &bodies[0];
+    assert_eq!(reasoning["max_completion_tokens"], 100);
+    assert!(reasoning.get("max_tokens").is_none());
+    assert_eq!(reasoning["reasoning_effort"], "low");
+    assert_eq!(reasoning["messages"][0]["role"], "developer");
+    assert_eq!(reasoning["messages"][1]["role"], "user");
+    for unsupported in ["temperature", "top_p", "frequency_penalty", "logit_bias"] {
+        assert!(reasoning.get(unsupported).is_none(), "{unsupported} sent");
+    }
+    assert_eq!(reasoning["seed"], 7);
+
+    let ordinary = &bodies[1];
+    assert_eq!(ordinary["max_tokens"], 100);
+    assert!(ordinary.get("max_completion_tokens").is_none());
+    assert!(ordinary.get("reasoning_effort").is_none());
+    assert_eq!(ordinary["messages"][0]["role"], "system");
+    assert_eq!(ordinary["temperature"], 0.2);
+    assert_eq!(ordinary["logit_bias"]["50256"], -100);
+}
+
+#[tokio::test]
+async fn test_reasoning_is_kept_apart_and_never_sent() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(completion(&serde_json::json!({
+            "role": "assistant",
+            "content": "4",
+            "reasoning_content": "Two plus two is four."
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server, "deepseek-reasoner")).unwrap();
+    let mut messages = vec![Message::user("2 + 2?")];
+    let reply = client
+        .complete_chat(ChatRequest::new(messages.clone()))
+        .await
+        .unwrap()
+        .message;
+
+    assert_eq!(reply.content, "4");
+    assert_eq!(
+        reply.reasoning_content.as_deref(),
+        Some("Two plus two is four.")
+    );
+
+    // Saved with a session, left out of the next request
+    let saved = serde_json::to_value(&reply).unwrap();
+    assert_eq!(saved["reasoning_content"], "Two plus two is four.");
+
+    messages.push(reply);
+    messages.push(Message::user("And 3 + 3?"));
+    client
+        .complete_chat(ChatRequest::new(messages))
+        .await
+        .unwrap();
+
+    let bodies = request_bodies(&mock_server).await;
+    assert_eq!(
+        bodies[1]["messages"][1],
+        serde_json::json!({"role": "assistant", "content": "4"})
+    );
+    assert!(!bodies[1].to_string().contains("Two plus two"));
+}
+
+#[tokio::test]
+async fn test_streamed_reasoning_content() {
+    let mock_server = MockServer::start().await;
+
+    let body = [
+        serde_json::json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": null, "reasoning_content": "Add "}, "finish_reason": null}]}),
+        serde_json::json!({"choices": [{"index": 0, "delta": {"content": null, "reasoning_content": "them."}, "finish_reason": null}]}),
+        serde_json::json!({"choices": [{"index": 0, "delta": {"content": "4", "reasoning_content": null}, "finish_reason": null}]}),
+        serde_json::json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+    ]
+    .iter()
+    .map(|chunk| format!("data: {chunk}\n\n"))
+    .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+    .collect::<String>();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server, "qwen3-8b")).unwrap();
+    let events: Vec<_> = client
+        .stream_chat(ChatRequest::new(vec![Message::user("2 + 2?")]))
+        .await
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|event| std::future::ready(!matches!(event, StreamEvent::Usage(_))))
+        .collect()
+        .await;
+
+    assert_eq!(
+        events,
+        [
+            StreamEvent::Reasoning("Add ".to_string()),
+            StreamEvent::Reasoning("them.".to_string()),
+            StreamEvent::Text("4".to_string()),
+            StreamEvent::Finish("stop".to_string()),
+        ]
+    );
+}
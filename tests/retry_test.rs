@@ -0,0 +1,218 @@
+//! Tests for request retries and rate-limit tracking
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::AppError;
+use llm_cli::provider::{ChatRequest, Completion, Provider};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config with fast retries
+fn create_test_config(mock_server: &MockServer) -> Config {
+    let mut config = Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    );
+    config.retry.base_delay_ms = 10;
+    config.retry.max_delay_ms = 1_000;
+    config
+}
+
+/// Helper function to create a successful completion body
+fn completion_body(content: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": "chatcmpl-123",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }]
+    })
+}
+
+#[tokio::test]
+async fn test_retries_server_error_then_succeeds() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion_body("Recovered")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Recovered");
+}
+
+#[tokio::test]
+async fn test_retry_after_header_and_exhausted_attempts() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "0")
+                .set_body_json(serde_json::json!({
+                    "error": {
+                        "message": "Rate limit reached",
+                        "type": "requests",
+                        "code": "rate_limit_exceeded"
+                    }
+                })),
+        )
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text);
+
+    assert!(matches!(result, Err(AppError::RateLimitExceeded)));
+}
+
+#[tokio::test]
+async fn test_no_retry_on_client_error_or_insufficient_quota() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("Incorrect API key"));
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(serde_json::json!({
+            "error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let result = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("exceeded your current quota"));
+}
+
+#[tokio::test]
+async fn test_rate_limit_headers_are_tracked() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("x-ratelimit-limit-requests", "500")
+                .insert_header("x-ratelimit-remaining-requests", "3")
+                .insert_header("x-ratelimit-reset-requests", "1m30s")
+                .set_body_json(completion_body("Hi")),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    assert!(Provider::rate_limit(&client).is_none());
+
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    let info = Provider::rate_limit(&client).unwrap();
+    assert_eq!(info.limit_requests, Some(500));
+    assert_eq!(info.remaining_requests, Some(3));
+    assert_eq!(
+        info.reset_requests,
+        Some(std::time::Duration::from_secs(90))
+    );
+    assert!(info.is_low());
+}
+
+#[tokio::test]
+async fn test_anthropic_retries_overloaded() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(529).set_body_json(serde_json::json!({
+            "type": "error",
+            "error": {"type": "overloaded_error", "message": "Overloaded"}
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "Back online"}],
+            "stop_reason": "end_turn"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+
+    let client = AnthropicClient::new(config).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "Back online");
+}
@@ -0,0 +1,248 @@
+//! Tests for configurable sampling parameters
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::ChatRequest;
+use llm_cli::sampling::SamplingParams;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to mount a successful completion
+async fn mount_completion(mock_server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop"
+            }]
+        })))
+        .mount(mock_server)
+        .await;
+}
+
+/// Helper function to read the JSON body of the only request the server received
+async fn request_body(mock_server: &MockServer) -> serde_json::Value {
+    let requests = mock_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    serde_json::from_slice(&requests[0].body).unwrap()
+}
+
+#[test]
+fn test_set_unset_and_validate() {
+    let mut params = SamplingParams::default();
+    assert!(params.is_empty());
+    assert_eq!(params.to_string(), "server defaults");
+
+    params.set("temperature", "0.2").unwrap();
+    params.set("stop", "END").unwrap();
+    params.set("logit_bias", "50256=-100").unwrap();
+    params.set("logit_bias", "1734 = 5").unwrap();
+    assert_eq!(params.temperature, Some(0.2));
+    assert_eq!(params.stop, ["END"]);
+    assert_eq!(params.logit_bias.len(), 2);
+    assert_eq!(
+        params.to_string(),
+        r#"logit_bias = {"1734":5,"50256":-100}, stop = ["END"], temperature = 0.2"#
+    );
+
+    // Rejected values leave the parameters unchanged
+    let error = params.set("temperature", "3").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("temperature must be between 0 and 2"));
+    assert!(params.set("top_p", "high").is_err());
+    assert!(params.set("logit_bias", "hello=1").is_err());
+    assert!(params.set("max_completion_tokens", "0").is_err());
+    assert!(params
+        .set("top_k", "40")
+        .unwrap_err()
+        .to_string()
+        .contains("unknown parameter"));
+    assert_eq!(params.temperature, Some(0.2));
+
+    params.unset("temperature").unwrap();
+    params.unset("stop").unwrap();
+    params.unset("logit_bias").unwrap();
+    assert!(params.is_empty());
+}
+
+#[test]
+fn test_overrides_and_toml() {
+    let config: Config = toml::from_str(
+        r#"
+[sampling]
+temperature = 0.3
+seed = 42
+stop = ["END"]
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.sampling.temperature, Some(0.3));
+
+    let overrides = SamplingParams {
+        temperature: Some(0.9),
+        top_p: Some(0.5),
+        ..SamplingParams::default()
+    };
+    let merged = config.sampling.overridden_by(&overrides);
+    assert_eq!(merged.temperature, Some(0.9));
+    assert_eq!(merged.top_p, Some(0.5));
+    assert_eq!(merged.seed, Some(42));
+    assert_eq!(merged.stop, ["END"]);
+}
+
+#[tokio::test]
+async fn test_unset_params_are_omitted() {
+    let mock_server = MockServer::start().await;
+    mount_completion(&mock_server).await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    let body = request_body(&mock_server).await;
+    assert_eq!(body["max_tokens"], 100);
+    for name in llm_cli::sampling::PARAMETER_NAMES {
+        assert!(body.get(name).is_none(), "{name} should not be sent");
+    }
+}
+
+#[tokio::test]
+async fn test_openai_sends_configured_and_request_params() {
+    let mock_server = MockServer::start().await;
+    mount_completion(&mock_server).await;
+
+    let mut config = create_test_config(&mock_server);
+    config.sampling.temperature = Some(0.2);
+    config.sampling.seed = Some(1);
+    config.sampling.logit_bias.insert("50256".to_string(), -100);
+
+    let overrides = SamplingParams {
+        seed: Some(7),
+        stop: vec!["\n\n".to_string()],
+        presence_penalty: Some(-0.5),
+        max_completion_tokens: Some(50),
+        ..SamplingParams::default()
+    };
+    let client = OpenAIClient::new(config).unwrap();
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]).with_sampling(overrides))
+        .await
+        .unwrap();
+
+    let body = request_body(&mock_server).await;
+    assert_eq!(body["temperature"], 0.2);
+    assert_eq!(body["seed"], 7);
+    assert_eq!(body["stop"], serde_json::json!(["\n\n"]));
+    assert_eq!(body["presence_penalty"], -0.5);
+    assert_eq!(body["logit_bias"], serde_json::json!({"50256": -100}));
+    assert_eq!(body["max_completion_tokens"], 50);
+    // Newer models reject max_tokens alongside max_completion_tokens
+    assert!(body.get("max_tokens").is_none());
+}
+
+#[tokio::test]
+async fn test_anthropic_and_ollama_mapping() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [{"type": "text", "text": "Hi"}],
+            "stop_reason": "end_turn"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {"role": "assistant", "content": "Hi"},
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let sampling = SamplingParams {
+        temperature: Some(0.4),
+        top_p: Some(0.9),
+        seed: Some(3),
+        stop: vec!["END".to_string()],
+        frequency_penalty: Some(1.0),
+        max_completion_tokens: Some(64),
+        ..SamplingParams::default()
+    };
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+    config.sampling = sampling.clone();
+    let client = AnthropicClient::new(config).unwrap();
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+    config
+        .ollama
+        .options
+        .insert("temperature".to_string(), serde_json::json!(0.8));
+    config
+        .ollama
+        .options
+        .insert("num_ctx".to_string(), serde_json::json!(8192));
+    let client = OllamaClient::new(config).unwrap();
+    client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]).with_sampling(sampling))
+        .await
+        .unwrap();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let anthropic: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(anthropic["max_tokens"], 64);
+    assert_eq!(anthropic["temperature"], 0.4);
+    assert_eq!(anthropic["top_p"], 0.9);
+    assert_eq!(anthropic["stop_sequences"], serde_json::json!(["END"]));
+    assert!(anthropic.get("seed").is_none());
+    assert!(anthropic.get("frequency_penalty").is_none());
+
+    // Sampling parameters take precedence over raw Ollama options
+    let ollama: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(
+        ollama["options"],
+        serde_json::json!({
+            "num_ctx": 8192,
+            "temperature": 0.4,
+            "top_p": 0.9,
+            "seed": 3,
+            "stop": ["END"],
+            "frequency_penalty": 1.0,
+            "num_predict": 64
+        })
+    );
+}
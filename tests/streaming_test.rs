@@ -1,9 +1,11 @@
 //! Tests for streaming functionality
 
+use futures_util::stream::BoxStream;
 use futures_util::{Stream, StreamExt};
 use llm_cli::api::{Delta, Message, OpenAIClient, StreamChoice, StreamChunk};
 use llm_cli::config::Config;
 use llm_cli::error::{AppError, Result};
+use llm_cli::provider::{ChatRequest, EventStream, StreamEvent};
 use std::pin::Pin;
 use std::time::Duration;
 use wiremock::matchers::{method, path, header};
@@ -12,6 +14,7 @@ use wiremock::{Mock, MockServer, ResponseTemplate};
 /// Helper function to create a test config pointing to mock server
 async fn create_test_config(mock_server: &MockServer) -> Config {
     let mut config = Config::default();
+    config.usage.ledger = false;
     config.api_key = Some("test-key".to_string()); // Set API key directly
     config.base_url = mock_server.uri();
     config.api_path = "/v1/chat/completions".to_string();
@@ -34,9 +37,12 @@ fn create_sse_chunk(content: &str, finish_reason: Option<&str>) -> String {
             delta: Delta {
                 role: None,
                 content: if content.is_empty() { None } else { Some(content.to_string()) },
+                reasoning_content: None,
+                tool_calls: Vec::new(),
             },
             finish_reason: finish_reason.map(String::from),
         }],
+        usage: None,
     };
     
     format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap())
@@ -59,9 +65,12 @@ fn create_streaming_response(chunks: Vec<&str>) -> String {
                 delta: Delta {
                     role: Some("assistant".to_string()),
                     content: None,
+                    reasoning_content: None,
+                    tool_calls: Vec::new(),
                 },
                 finish_reason: None,
             }],
+            usage: None,
         }).unwrap()
     ));
     
@@ -80,6 +89,22 @@ fn create_streaming_response(chunks: Vec<&str>) -> String {
     response
 }
 
+/// Helper function to reduce a stream of events to its text, reporting truncation as an error
+fn text_chunks(events: EventStream) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text(text)) => Some(Ok(text)),
+                Ok(StreamEvent::Finish(reason)) if reason == "length" => {
+                    Some(Err(AppError::TokenLimitExceeded))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .boxed()
+}
+
 #[tokio::test]
 async fn test_streaming_response_parsing() {
     let mock_server = MockServer::start().await;
@@ -117,7 +142,7 @@ async fn test_streaming_response_parsing() {
         Message::user("Hello!"),
     ];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     let mut collected_response = String::new();
     
     while let Some(chunk_result) = stream.next().await {
@@ -161,7 +186,7 @@ async fn test_streaming_with_empty_chunks() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     let mut collected_response = String::new();
     
     while let Some(chunk_result) = stream.next().await {
@@ -197,7 +222,7 @@ async fn test_streaming_error_handling() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let result = client.complete_stream(messages).await;
+    let result = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks);
     
     match result {
         Err(AppError::RateLimitExceeded) => {
@@ -238,7 +263,7 @@ async fn test_streaming_with_multiline_content() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     let mut collected_response = String::new();
     
     while let Some(chunk_result) = stream.next().await {
@@ -280,7 +305,7 @@ async fn test_streaming_with_special_characters() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     let mut collected_response = String::new();
     
     while let Some(chunk_result) = stream.next().await {
@@ -312,7 +337,7 @@ async fn test_streaming_timeout() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let result = client.complete_stream(messages).await;
+    let result = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks);
     
     // Should timeout
     assert!(result.is_err());
@@ -344,7 +369,7 @@ async fn test_streaming_large_response() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     let mut collected_response = String::new();
     let mut chunk_count = 0;
     
@@ -384,7 +409,7 @@ async fn test_streaming_with_malformed_data() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let mut stream = client.complete_stream(messages).await.unwrap();
+    let mut stream = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks).unwrap();
     
     // Should handle malformed data gracefully
     while let Some(chunk_result) = stream.next().await {
@@ -423,7 +448,7 @@ async fn test_streaming_api_error_response() {
     let client = OpenAIClient::new(config).unwrap();
     let messages = vec![Message::user("Test")];
     
-    let result = client.complete_stream(messages).await;
+    let result = client.stream_chat(ChatRequest::new(messages)).await.map(text_chunks);
     
     match result {
         Err(AppError::ApiError { message }) => {
@@ -606,4 +631,124 @@ mod streaming_table_tests {
         
         assert!(!table_found, "Should not detect false positive tables");
     }
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_streaming_in_stream_error_object() {
+    let mock_server = MockServer::start().await;
+    let config = create_test_config(&mock_server).await;
+
+    let mut response_body = create_sse_chunk("Partial", None);
+    response_body.push_str(
+        "data: {\"error\":{\"message\":\"The server had an error\",\"type\":\"server_error\"}}\n\n",
+    );
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(response_body)
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(config).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Test")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    assert_eq!(stream.next().await.unwrap().unwrap(), "Partial");
+    match stream.next().await {
+        Some(Err(AppError::ApiError { message })) => {
+            assert_eq!(message, "The server had an error");
+        }
+        other => panic!("Expected in-stream ApiError, got {:?}", other.map(|r| r.is_ok())),
+    }
+}
+
+#[tokio::test]
+async fn test_streaming_malformed_json_is_reported() {
+    let mock_server = MockServer::start().await;
+    let config = create_test_config(&mock_server).await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("data: {\"choices\": [\n\ndata: [DONE]\n\n")
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(config).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Test")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    assert!(matches!(stream.next().await, Some(Err(AppError::ParseError(_)))));
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_streaming_comments_and_crlf_line_endings() {
+    let mock_server = MockServer::start().await;
+    let config = create_test_config(&mock_server).await;
+
+    let response_body = create_streaming_response(vec!["Line", " endings"])
+        .replace("\n\n", "\r\n\r\n: keep-alive\r\n\r\n");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(response_body)
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(config).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Test")]))
+        .await
+        .map(text_chunks)
+        .unwrap();
+
+    let mut collected = String::new();
+    while let Some(chunk) = stream.next().await {
+        collected.push_str(&chunk.unwrap());
+    }
+
+    assert_eq!(collected, "Line endings");
+}
+
+#[tokio::test]
+async fn test_sse_events_across_split_frames() {
+    let body = create_streaming_response(vec!["你好", " 👋", " world"]);
+    let bytes = body.into_bytes();
+
+    // Deliver the body in 5-byte frames so events and UTF-8 sequences are split
+    let frames: Vec<std::result::Result<Vec<u8>, std::io::Error>> =
+        bytes.chunks(5).map(|c| Ok(c.to_vec())).collect();
+
+    let mut events = Box::pin(llm_cli::sse::events(futures_util::stream::iter(frames)));
+    let mut collected = String::new();
+
+    while let Some(event) = events.next().await {
+        let event = event.unwrap();
+        if event.data == "[DONE]" {
+            break;
+        }
+        let chunk: StreamChunk = serde_json::from_str(&event.data).unwrap();
+        for choice in chunk.choices {
+            collected.push_str(&choice.delta.content.unwrap_or_default());
+        }
+    }
+
+    assert_eq!(collected, "你好 👋 world");
+}
@@ -0,0 +1,326 @@
+//! Tests for schema-constrained JSON replies
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::error::AppError;
+use llm_cli::ollama::OllamaClient;
+use llm_cli::structured::{self, Schema};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create the schema used by most tests
+fn person_schema() -> Schema {
+    Schema::new(
+        "person",
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0}
+            },
+            "required": ["name", "age"]
+        }),
+    )
+    .unwrap()
+}
+
+/// Helper function to create a successful completion body
+fn completion_body(content: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": "chatcmpl-123",
+        "object": "chat.completion",
+        "created": 1_677_652_288,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }]
+    })
+}
+
+/// Helper function to read the JSON bodies of all requests the server received
+async fn request_bodies(mock_server: &MockServer) -> Vec<serde_json::Value> {
+    mock_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_validate_and_extract_json() {
+    let schema = person_schema();
+
+    assert!(schema
+        .validate(&serde_json::json!({"name": "Ada", "age": 36}))
+        .is_empty());
+
+    let errors = schema.validate(&serde_json::json!({"name": "Ada", "age": -1}));
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("/age: "));
+
+    assert!(Schema::new("bad", serde_json::json!({"type": "no-such-type"})).is_err());
+
+    assert_eq!(
+        structured::extract_json("```json\n{\"a\": 1}\n```"),
+        Some(serde_json::json!({"a": 1}))
+    );
+    assert_eq!(
+        structured::extract_json("Sure! Here it is: [1, 2] Hope that helps."),
+        Some(serde_json::json!([1, 2]))
+    );
+    assert_eq!(structured::extract_json("no json here"), None);
+}
+
+#[tokio::test]
+async fn test_sends_response_format() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {"name": "person", "schema": {"required": ["name", "age"]}}
+            }
+        })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(completion_body(r#"{"name": "Ada", "age": 36}"#)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let value =
+        structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 2)
+            .await
+            .unwrap();
+
+    assert_eq!(value, serde_json::json!({"name": "Ada", "age": 36}));
+}
+
+#[tokio::test]
+async fn test_repairs_invalid_reply() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(completion_body(r#"{"name": "Ada"}"#)),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(completion_body(r#"{"name": "Ada", "age": 36}"#)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let value =
+        structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 2)
+            .await
+            .unwrap();
+    assert_eq!(value["age"], 36);
+
+    // The second request carries the bad reply and the validation errors
+    let bodies = request_bodies(&mock_server).await;
+    assert_eq!(bodies.len(), 2);
+    let messages = bodies[1]["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1]["content"], r#"{"name": "Ada"}"#);
+    assert!(messages[2]["content"]
+        .as_str()
+        .unwrap()
+        .contains("\"age\" is a required property"));
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_repairs() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion_body("I don't know")))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let error =
+        structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 1)
+            .await
+            .unwrap_err();
+
+    assert!(matches!(error, AppError::SchemaError(_)));
+    assert!(error.to_string().contains("not valid JSON"));
+}
+
+#[tokio::test]
+async fn test_falls_back_to_prompt_when_response_format_rejected() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(
+            serde_json::json!({"response_format": {"type": "json_schema"}}),
+        ))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": {
+                "message": "'response_format' is not supported",
+                "type": "invalid_request_error",
+                "code": null
+            }
+        })))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion_body(
+            "```json\n{\"name\": \"Ada\", \"age\": 36}\n```",
+        )))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let messages = vec![Message::system("Be helpful"), Message::user("Who?")];
+    let value = structured::complete_json(&client, messages, &person_schema(), 0)
+        .await
+        .unwrap();
+    assert_eq!(value["name"], "Ada");
+
+    let bodies = request_bodies(&mock_server).await;
+    assert!(bodies[1].get("response_format").is_none());
+    let messages = bodies[1]["messages"].as_array().unwrap();
+    assert_eq!(messages[1]["role"], "system");
+    assert!(messages[1]["content"]
+        .as_str()
+        .unwrap()
+        .contains("JSON Schema"));
+    assert_eq!(messages[2]["role"], "user");
+}
+
+#[tokio::test]
+async fn test_other_errors_do_not_fall_back_to_prompt() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "error": {
+                "message": "Incorrect API key provided",
+                "type": "invalid_request_error",
+                "code": "invalid_api_key"
+            }
+        })))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("upstream failed"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.retry.max_attempts = 1;
+    let client = OpenAIClient::new(config).unwrap();
+
+    for expected in ["Incorrect API key", "status 500"] {
+        let error =
+            structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 0)
+                .await
+                .unwrap_err();
+        assert!(matches!(error, AppError::ApiError { .. }));
+        assert!(error.to_string().contains(expected), "{error}");
+    }
+
+    // Neither error was retried without response_format
+    let bodies = request_bodies(&mock_server).await;
+    assert!(bodies
+        .iter()
+        .all(|body| body.get("response_format").is_some()));
+}
+
+#[tokio::test]
+async fn test_anthropic_and_ollama_schema_mapping() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [{"type": "text", "text": "{\"name\": \"Ada\", \"age\": 36}"}],
+            "stop_reason": "end_turn"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(
+            serde_json::json!({"format": {"required": ["name", "age"]}}),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {"role": "assistant", "content": "{\"name\": \"Ada\", \"age\": 36}"},
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+    let client = AnthropicClient::new(config).unwrap();
+    let value =
+        structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 0)
+            .await
+            .unwrap();
+    assert_eq!(value["age"], 36);
+
+    // The Messages API has no response_format, so the schema goes in the system prompt
+    let body = &request_bodies(&mock_server).await[0];
+    assert!(body.get("response_format").is_none());
+    assert!(body["system"].as_str().unwrap().contains("\"required\""));
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+    let client = OllamaClient::new(config).unwrap();
+    let value =
+        structured::complete_json(&client, vec![Message::user("Who?")], &person_schema(), 0)
+            .await
+            .unwrap();
+    assert_eq!(value["name"], "Ada");
+}
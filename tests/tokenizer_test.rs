@@ -0,0 +1,145 @@
+//! Tests for local token counting and the context window check
+
+#![allow(clippy::unwrap_used)]
+
+use base64::Engine;
+use llm_cli::api::Message;
+use llm_cli::config::Config;
+use llm_cli::context::{self, ContextBudget};
+use llm_cli::provider::ChatRequest;
+use llm_cli::sampling::SamplingParams;
+use llm_cli::tokenizer::{Encoding, Tokenizer};
+use std::path::PathBuf;
+
+/// Helper function to create a test config for a model
+fn create_test_config(model: &str, max_tokens: u32) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        "http://localhost".to_string(),
+        model.to_string(),
+        max_tokens,
+    )
+}
+
+/// Helper function to write a `.tiktoken` vocabulary to a scratch file
+fn write_vocab(lines: &[(&str, u32)]) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("llm-cli-vocab-{}.tiktoken", uuid::Uuid::new_v4()));
+    let contents = lines
+        .iter()
+        .map(|(token, rank)| {
+            format!(
+                "{} {}\n",
+                base64::engine::general_purpose::STANDARD.encode(token),
+                rank
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat();
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_bundled_encodings() {
+    let cl100k = Tokenizer::new(Encoding::Cl100kBase);
+    assert_eq!(cl100k.encode("hello world"), [15339, 1917]);
+    assert_eq!(cl100k.count(""), 0);
+
+    let o200k = Tokenizer::new(Encoding::O200kBase);
+    assert_eq!(o200k.encode("hello world"), [24912, 2375]);
+    assert_eq!(o200k.name(), "o200k_base");
+
+    assert_eq!(Encoding::for_model("gpt-4o-mini"), Encoding::O200kBase);
+    assert_eq!(Encoding::for_model("o3-mini"), Encoding::O200kBase);
+    assert_eq!(Encoding::for_model("gpt-4-turbo"), Encoding::Cl100kBase);
+    assert_eq!(Encoding::for_model("llama3.2"), Encoding::Cl100kBase);
+}
+
+#[test]
+fn test_vocabulary_file() {
+    let path = write_vocab(&[("a", 0), ("b", 1), ("c", 2), ("ab", 3), ("abc", 4)]);
+    let tokenizer = Tokenizer::from_file(&path, Encoding::Cl100kBase).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(tokenizer.encode("abc"), [4]);
+    assert_eq!(tokenizer.encode("abcab"), [4, 3]);
+    assert_eq!(tokenizer.name(), path.display().to_string());
+}
+
+#[test]
+fn test_invalid_vocabulary_files() {
+    let duplicate = write_vocab(&[("a", 0), ("b", 0)]);
+    let error = Tokenizer::from_file(&duplicate, Encoding::Cl100kBase).unwrap_err();
+    std::fs::remove_file(&duplicate).unwrap();
+    assert!(error.to_string().contains("rank 0 appears more than once"));
+
+    let malformed =
+        std::env::temp_dir().join(format!("llm-cli-vocab-{}.tiktoken", uuid::Uuid::new_v4()));
+    std::fs::write(&malformed, "YQ== 0\nnot-a-token-line\n").unwrap();
+    let error = Tokenizer::from_file(&malformed, Encoding::Cl100kBase).unwrap_err();
+    std::fs::remove_file(&malformed).unwrap();
+    assert!(error.to_string().contains("line 2"));
+
+    let missing = std::env::temp_dir().join("llm-cli-vocab-missing.tiktoken");
+    assert!(Tokenizer::from_file(&missing, Encoding::Cl100kBase).is_err());
+}
+
+#[test]
+fn test_count_messages() {
+    let tokenizer = Tokenizer::new(Encoding::Cl100kBase);
+    let messages = [Message::system("hello world"), Message::user("hello world")];
+
+    // 3 for the reply, then per message 4 for the framing and 2 for the text
+    assert_eq!(tokenizer.count_messages(&messages), 3 + 2 * (4 + 2));
+    assert_eq!(tokenizer.count_messages(&[]), 3);
+}
+
+#[test]
+fn test_context_window_lookup() {
+    assert_eq!(context::context_window("gpt-4o-2024-08-06"), Some(128_000));
+    assert_eq!(context::context_window("gpt-4-32k-0613"), Some(32_768));
+    assert_eq!(context::context_window("gpt-4-0613"), Some(8_192));
+    assert_eq!(context::context_window("gpt-4.5-preview"), Some(128_000));
+    assert_eq!(
+        context::context_window("claude-3-5-sonnet-latest"),
+        Some(200_000)
+    );
+    assert_eq!(context::context_window("llama3.2"), None);
+}
+
+#[test]
+fn test_overflow_check() {
+    let config = create_test_config("gpt-4", 4000);
+    let budget = ContextBudget::from_config(&config).unwrap().unwrap();
+
+    let short = ChatRequest::new(vec![Message::user("hello world")]);
+    assert_eq!(budget.check(&short), None);
+
+    let long = ChatRequest::new(vec![Message::user("hello ".repeat(5000))]);
+    let overflow = budget.check(&long).unwrap();
+    assert_eq!(overflow.window, 8_192);
+    assert_eq!(overflow.reply_tokens, 4000);
+    assert_eq!(overflow.prompt_tokens, budget.prompt_tokens(&long));
+    assert!(overflow.prompt_tokens > 5000);
+
+    // A smaller reply limit on the request makes the same prompt fit
+    let trimmed = long.with_sampling(SamplingParams {
+        max_completion_tokens: Some(100),
+        ..SamplingParams::default()
+    });
+    assert_eq!(budget.check(&trimmed), None);
+}
+
+#[test]
+fn test_budget_needs_a_known_window() {
+    let mut config = create_test_config("llama3.2", 100);
+    assert!(ContextBudget::from_config(&config).unwrap().is_none());
+
+    config.context_window = Some(8);
+    let budget = ContextBudget::from_config(&config).unwrap().unwrap();
+    assert_eq!(budget.window(), 8);
+    assert!(budget
+        .check(&ChatRequest::new(vec![Message::user("hi")]))
+        .is_some());
+}
@@ -0,0 +1,445 @@
+//! Tests for tool calling across the message model, providers and sessions
+
+#![allow(clippy::unwrap_used)]
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{Message, OpenAIClient, Role, ToolCall, ToolChoice, ToolDefinition};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::{ChatRequest, Completion, StreamEvent};
+use llm_cli::session::Session;
+use llm_cli::tools::{Tool, ToolRegistry};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to define a weather tool
+fn weather_tool() -> ToolDefinition {
+    ToolDefinition::function(
+        "get_weather",
+        "Get the current weather for a city",
+        serde_json::json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"]
+        }),
+    )
+}
+
+/// Helper function to create a conversation that already contains a tool round trip
+fn conversation_with_tool_result() -> Vec<Message> {
+    vec![
+        Message::user("Weather in Paris?"),
+        Message::assistant_with_tool_calls(
+            "",
+            vec![ToolCall::function(
+                "call_1",
+                "get_weather",
+                r#"{"city":"Paris"}"#,
+            )],
+        ),
+        Message::tool("call_1", "18°C and sunny"),
+    ]
+}
+
+#[test]
+fn test_tool_choice_from_config_value() {
+    let config: Config = toml::from_str("[tools]\nchoice = \"required\"").unwrap();
+    assert_eq!(config.tools.choice.as_deref(), Some("required"));
+
+    assert_eq!(ToolChoice::from("auto"), ToolChoice::Auto);
+    assert_eq!(ToolChoice::from("none"), ToolChoice::None);
+    assert_eq!(ToolChoice::from("required"), ToolChoice::Required);
+    assert_eq!(
+        ToolChoice::from("get_weather"),
+        ToolChoice::Function("get_weather".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_openai_sends_tools_and_parses_tool_calls() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}],
+            "tool_choice": {"type": "function", "function": {"name": "get_weather"}}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let request = ChatRequest::new(vec![Message::user("Weather in Paris?")])
+        .with_tools(vec![weather_tool()])
+        .with_tool_choice(ToolChoice::Function("get_weather".to_string()));
+
+    let completion = client.complete_chat(request).await.unwrap();
+
+    assert_eq!(completion.finish_reason.as_deref(), Some("tool_calls"));
+    assert_eq!(completion.message.content, "");
+    assert_eq!(
+        completion.message.tool_calls,
+        vec![ToolCall::function(
+            "call_abc",
+            "get_weather",
+            r#"{"city":"Paris"}"#
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_openai_sends_tool_messages() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                    }]
+                },
+                {"role": "tool", "tool_call_id": "call_1", "content": "18°C and sunny"}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-124",
+            "object": "chat.completion",
+            "created": 1_677_652_289,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "It is 18°C and sunny in Paris."},
+                "finish_reason": "stop"
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(conversation_with_tool_result()))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "It is 18°C and sunny in Paris.");
+}
+
+#[tokio::test]
+async fn test_openai_streaming_accumulates_tool_call_fragments() {
+    let mock_server = MockServer::start().await;
+
+    let chunks = [
+        r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":"Checking"},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"ci"}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","type":"function","function":{"name":"get_weather","arguments":"{\"city\":"}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"ty\":\"Paris\"}"}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"function":{"arguments":"\"Rome\"}"}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+    ];
+    let mut body = chunks
+        .iter()
+        .map(|c| format!("data: {c}\n\n"))
+        .collect::<Vec<_>>()
+        .concat();
+    body.push_str("data: [DONE]\n\n");
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(body)
+                .append_header("content-type", "text/event-stream"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let mut stream = client
+        .stream_chat(
+            ChatRequest::new(vec![Message::user("Weather?")]).with_tools(vec![weather_tool()]),
+        )
+        .await
+        .unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = stream.next().await {
+        events.push(event.unwrap());
+    }
+
+    // Without usage from the server, an estimate closes the stream
+    assert!(matches!(events.pop(), Some(StreamEvent::Usage(usage)) if usage.estimated));
+    assert_eq!(
+        events,
+        vec![
+            StreamEvent::Text("Checking".to_string()),
+            StreamEvent::ToolCalls(vec![
+                ToolCall::function("call_a", "get_weather", r#"{"city":"Paris"}"#),
+                ToolCall::function("call_b", "get_weather", r#"{"city":"Rome"}"#),
+            ]),
+            StreamEvent::Finish("tool_calls".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_anthropic_tool_use_mapping() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(body_partial_json(serde_json::json!({
+            "tools": [{"name": "get_weather", "input_schema": {"type": "object"}}],
+            "tool_choice": {"type": "any"},
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {
+                    "role": "assistant",
+                    "content": [{"type": "tool_use", "id": "call_1", "name": "get_weather", "input": {"city": "Paris"}}]
+                },
+                {
+                    "role": "user",
+                    "content": [{"type": "tool_result", "tool_use_id": "call_1", "content": "18°C and sunny"}]
+                }
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [
+                {"type": "text", "text": "Let me check Rome too."},
+                {"type": "tool_use", "id": "toolu_2", "name": "get_weather", "input": {"city": "Rome"}}
+            ],
+            "stop_reason": "tool_use"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+
+    let client = AnthropicClient::new(config).unwrap();
+    let request = ChatRequest::new(conversation_with_tool_result())
+        .with_tools(vec![weather_tool()])
+        .with_tool_choice(ToolChoice::Required);
+    let completion = client.complete_chat(request).await.unwrap();
+
+    assert_eq!(completion.finish_reason.as_deref(), Some("tool_calls"));
+    assert_eq!(completion.message.content, "Let me check Rome too.");
+    assert_eq!(
+        completion.message.tool_calls,
+        vec![ToolCall::function(
+            "toolu_2",
+            "get_weather",
+            r#"{"city":"Rome"}"#
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming_input_json_deltas() {
+    let mock_server = MockServer::start().await;
+
+    let events = [
+        serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}}}),
+        serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{\"city\": "}}),
+        serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "\"Oslo\"}"}}),
+        serde_json::json!({"type": "content_block_stop", "index": 0}),
+        serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}}),
+    ];
+    let body = events
+        .iter()
+        .map(|e| format!("event: {}\ndata: {e}\n\n", e["type"].as_str().unwrap()))
+        .collect::<Vec<_>>()
+        .concat();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+
+    let client = AnthropicClient::new(config).unwrap();
+    let mut stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Weather in Oslo?")]))
+        .await
+        .unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = stream.next().await {
+        events.push(event.unwrap());
+    }
+
+    // Without usage from the server, an estimate closes the stream
+    assert!(matches!(events.pop(), Some(StreamEvent::Usage(usage)) if usage.estimated));
+    assert_eq!(
+        events,
+        vec![
+            StreamEvent::ToolCalls(vec![ToolCall::function(
+                "toolu_1",
+                "get_weather",
+                r#"{"city": "Oslo"}"#
+            )]),
+            StreamEvent::Finish("tool_calls".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ollama_tool_calls() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(serde_json::json!({
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}],
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "tool_calls": [{"function": {"name": "get_weather", "arguments": {"city": "Paris"}}}]},
+                {"role": "tool", "content": "18°C and sunny"}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "get_weather", "arguments": {"city": "Lyon"}}}]
+            },
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+
+    let client = OllamaClient::new(config).unwrap();
+    let completion = client
+        .complete_chat(
+            ChatRequest::new(conversation_with_tool_result()).with_tools(vec![weather_tool()]),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(completion.message.tool_calls.len(), 1);
+    let call = &completion.message.tool_calls[0];
+    assert!(call.id.starts_with("call_"));
+    assert_eq!(
+        *call,
+        ToolCall::function(call.id.clone(), "get_weather", r#"{"city":"Lyon"}"#)
+    );
+}
+
+#[tokio::test]
+async fn test_session_round_trips_tool_fields() {
+    let mut session = Session::new("gpt-4o".to_string());
+    for message in conversation_with_tool_result() {
+        session.add_message(message);
+    }
+
+    let path = std::env::temp_dir().join(format!("llm-cli-tool-session-{}.json", session.id));
+    session.save(Some(path.clone())).await.unwrap();
+    let loaded = Session::load(path.clone()).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.messages.len(), 3);
+    assert_eq!(
+        loaded.messages[1].tool_calls,
+        session.messages[1].tool_calls
+    );
+    assert_eq!(loaded.messages[2].role, Role::Tool);
+    assert_eq!(loaded.messages[2].tool_call_id.as_deref(), Some("call_1"));
+
+    // Plain messages keep the original compact JSON shape
+    let json = serde_json::to_value(&loaded.messages[0]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"role": "user", "content": "Weather in Paris?"})
+    );
+
+    let markdown = loaded.to_markdown();
+    assert!(markdown.contains("## Tool"));
+    assert!(markdown.contains("`get_weather`"));
+}
+
+/// Test tool that echoes its `text` argument
+struct EchoTool;
+
+#[async_trait]
+impl Tool for EchoTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition::function(
+            "echo",
+            "Echo the input",
+            serde_json::json!({"type": "object", "properties": {"text": {"type": "string"}}}),
+        )
+    }
+
+    async fn call(&self, arguments: serde_json::Value) -> llm_cli::Result<String> {
+        Ok(arguments["text"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_tool_registry_execute() {
+    let mut registry = ToolRegistry::new();
+    registry.register(EchoTool);
+
+    assert_eq!(registry.definitions()[0].function.name, "echo");
+
+    let output = registry
+        .execute(&ToolCall::function("1", "echo", r#"{"text":"hi"}"#))
+        .await;
+    assert_eq!(output, "hi");
+
+    let output = registry
+        .execute(&ToolCall::function("2", "missing", "{}"))
+        .await;
+    assert!(output.contains("unknown tool"));
+
+    let output = registry
+        .execute(&ToolCall::function("3", "echo", "{not json"))
+        .await;
+    assert!(output.starts_with("Error:"));
+}
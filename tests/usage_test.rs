@@ -0,0 +1,246 @@
+//! Tests for token usage reporting and estimation
+
+#![allow(clippy::unwrap_used)]
+
+use futures_util::StreamExt;
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::{ChatRequest, EventStream, StreamEvent};
+use llm_cli::session::Session;
+use llm_cli::usage::{self, Usage};
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Helper function to create a test config pointing to mock server
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to collect the usage events of a stream
+async fn usage_events(mut stream: EventStream) -> Vec<Usage> {
+    let mut usages = Vec::new();
+    while let Some(event) = stream.next().await {
+        if let StreamEvent::Usage(usage) = event.unwrap() {
+            usages.push(usage);
+        }
+    }
+    usages
+}
+
+#[tokio::test]
+async fn test_openai_completion_usage() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi!"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 9, "completion_tokens": 3, "total_tokens": 12}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let completion = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    assert_eq!(completion.usage, Usage::new(9, 3));
+}
+
+#[tokio::test]
+async fn test_huge_usage_counts_saturate() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi!"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 4_000_000_000u32, "completion_tokens": 4_000_000_000u32}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let completion = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    assert_eq!(completion.usage.total_tokens, u32::MAX);
+    assert_eq!(Usage::new(u32::MAX, 1).total_tokens, u32::MAX);
+}
+
+#[tokio::test]
+async fn test_openai_stream_requests_usage() {
+    let mock_server = MockServer::start().await;
+
+    let body = [
+        r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":"Hi"},"finish_reason":null}]}"#,
+        r#"{"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+        r#"{"choices":[],"usage":{"prompt_tokens":9,"completion_tokens":1,"total_tokens":10}}"#,
+        "[DONE]",
+    ]
+    .iter()
+    .map(|data| format!("data: {data}\n\n"))
+    .collect::<Vec<_>>()
+    .concat();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({
+            "stream": true,
+            "stream_options": {"include_usage": true}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.usage.stream_usage = Some(true);
+    let client = OpenAIClient::new(config).unwrap();
+    let stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    // The server's count is used as is, without an extra estimate
+    assert_eq!(usage_events(stream).await, [Usage::new(9, 1)]);
+
+    // Compatible servers are not asked unless configured
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let _ = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await;
+    let requests = mock_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert!(body.get("stream_options").is_none());
+}
+
+#[tokio::test]
+async fn test_usage_estimated_when_missing() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "local-model",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Twelve chars"},
+                "finish_reason": "stop"
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let messages = vec![Message::system("Be brief."), Message::user("Hello")];
+    let completion = client
+        .complete_chat(ChatRequest::new(messages.clone()))
+        .await
+        .unwrap();
+
+    let expected = Usage {
+        estimated: true,
+        ..Usage::new(usage::estimate_prompt_tokens(&messages), 4 + 3)
+    };
+    assert_eq!(completion.usage, expected);
+
+    // Session totals add up every request
+    let mut session = Session::new("local-model".to_string());
+    session.record_usage(&completion.usage);
+    session.record_usage(&Usage::new(5, 5));
+    assert_eq!(session.total_tokens, expected.total_tokens + 10);
+}
+
+#[tokio::test]
+async fn test_anthropic_stream_usage() {
+    let mock_server = MockServer::start().await;
+
+    let events = [
+        serde_json::json!({"type": "message_start", "message": {"id": "msg_1", "usage": {"input_tokens": 25, "output_tokens": 1}}}),
+        serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Hi"}}),
+        serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 6}}),
+        serde_json::json!({"type": "message_stop"}),
+    ];
+    let body = events
+        .iter()
+        .map(|e| format!("event: {}\ndata: {e}\n\n", e["type"].as_str().unwrap()))
+        .collect::<Vec<_>>()
+        .concat();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+
+    let client = AnthropicClient::new(config).unwrap();
+    let stream = client
+        .stream_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    assert_eq!(usage_events(stream).await, [Usage::new(25, 6)]);
+}
+
+#[tokio::test]
+async fn test_ollama_usage_counts() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llama3.2",
+            "message": {"role": "assistant", "content": "Hi"},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 26,
+            "eval_count": 2
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+
+    let client = OllamaClient::new(config).unwrap();
+    let completion = client
+        .complete_chat(ChatRequest::new(vec![Message::user("Hello")]))
+        .await
+        .unwrap();
+
+    assert_eq!(completion.usage, Usage::new(26, 2));
+}
@@ -0,0 +1,209 @@
+//! Tests for image attachments sent to vision models
+
+#![allow(clippy::unwrap_used)]
+
+use llm_cli::anthropic::AnthropicClient;
+use llm_cli::api::{ImageAttachment, Message, OpenAIClient};
+use llm_cli::config::{Config, ProviderKind};
+use llm_cli::ollama::OllamaClient;
+use llm_cli::provider::{ChatRequest, Completion};
+use llm_cli::session::Session;
+use llm_cli::vision;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// PNG signature followed by a few bytes of an IHDR chunk
+const PNG_BYTES: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+
+/// Helper function to create a test config
+fn create_test_config(mock_server: &MockServer) -> Config {
+    Config::test_config_with(
+        Some("test-key".to_string()),
+        mock_server.uri(),
+        "gpt-4o".to_string(),
+        100,
+    )
+}
+
+/// Helper function to create a small attached image
+fn screenshot() -> ImageAttachment {
+    ImageAttachment {
+        path: "screenshot.png".to_string(),
+        mime_type: "image/png".to_string(),
+        data: "iVBORw0KGgo=".to_string(),
+    }
+}
+
+#[test]
+fn test_sniff_mime_type() {
+    assert_eq!(vision::sniff_mime_type(PNG_BYTES), Some("image/png"));
+    assert_eq!(
+        vision::sniff_mime_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+        Some("image/jpeg")
+    );
+    assert_eq!(
+        vision::sniff_mime_type(b"GIF89a\x01\x00"),
+        Some("image/gif")
+    );
+    assert_eq!(
+        vision::sniff_mime_type(b"RIFF\x24\x00\x00\x00WEBPVP8 "),
+        Some("image/webp")
+    );
+    assert_eq!(
+        vision::sniff_mime_type(b"RIFF\x24\x00\x00\x00WAVEfmt "),
+        None
+    );
+    assert_eq!(vision::sniff_mime_type(b"%PDF-1.7"), None);
+    assert_eq!(vision::sniff_mime_type(&[]), None);
+}
+
+#[tokio::test]
+async fn test_load_image_checks_type_and_size() {
+    let dir = std::env::temp_dir().join(format!("llm-cli-vision-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("tiny.png"), PNG_BYTES).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+    let image = vision::load_image(&dir.join("tiny.png"), 1024)
+        .await
+        .unwrap();
+    assert_eq!(image.mime_type, "image/png");
+    assert_eq!(image.data, "iVBORw0KGgoAAAAN");
+    assert!(image.data_url().starts_with("data:image/png;base64,iVBOR"));
+
+    let error = vision::load_image(&dir.join("tiny.png"), 4)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("over the 4 byte limit"));
+
+    let error = vision::load_image(&dir.join("notes.txt"), 1024)
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("not a PNG, JPEG, GIF or WebP image"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_openai_sends_content_parts() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(serde_json::json!({
+            "messages": [
+                {"role": "system", "content": "Be helpful"},
+                {
+                    "role": "user",
+                    "content": [
+                        {"type": "text", "text": "What is in this image?"},
+                        {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}}
+                    ]
+                }
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1_677_652_288,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "A terminal window."},
+                "finish_reason": "stop"
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let client = OpenAIClient::new(create_test_config(&mock_server)).unwrap();
+    let response = client
+        .complete_chat(ChatRequest::new(vec![
+            Message::system("Be helpful"),
+            Message::user_with_images("What is in this image?", vec![screenshot()]),
+        ]))
+        .await
+        .and_then(Completion::into_text)
+        .unwrap();
+
+    assert_eq!(response, "A terminal window.");
+}
+
+#[tokio::test]
+async fn test_anthropic_and_ollama_image_mapping() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(body_partial_json(serde_json::json!({
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}},
+                    {"type": "text", "text": "Describe it"}
+                ]
+            }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [{"type": "text", "text": "A chart."}],
+            "stop_reason": "end_turn"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(serde_json::json!({
+            "messages": [{"role": "user", "content": "Describe it", "images": ["iVBORw0KGgo="]}]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "llava",
+            "message": {"role": "assistant", "content": "A diagram."},
+            "done": true,
+            "done_reason": "stop"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let messages = vec![Message::user_with_images("Describe it", vec![screenshot()])];
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Anthropic;
+    let client = AnthropicClient::new(config).unwrap();
+    let reply = client
+        .complete_chat(ChatRequest::new(messages.clone()))
+        .await
+        .unwrap();
+    assert_eq!(reply.message.content, "A chart.");
+
+    let mut config = create_test_config(&mock_server);
+    config.provider = ProviderKind::Ollama;
+    let client = OllamaClient::new(config).unwrap();
+    let reply = client
+        .complete_chat(ChatRequest::new(messages))
+        .await
+        .unwrap();
+    assert_eq!(reply.message.content, "A diagram.");
+}
+
+#[test]
+fn test_session_records_images() {
+    let mut session = Session::new("gpt-4o".to_string());
+    session.add_message(Message::user_with_images("Look", vec![screenshot()]));
+    session.add_message(Message::assistant("Nice"));
+
+    let json = serde_json::to_value(&session).unwrap();
+    assert_eq!(json["messages"][0]["images"][0]["path"], "screenshot.png");
+    assert!(json["messages"][1].get("images").is_none());
+
+    let restored: Session = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.messages[0].images, vec![screenshot()]);
+
+    let markdown = session.to_markdown();
+    assert!(markdown.contains("*Attached image* `screenshot.png` (image/png)"));
+}